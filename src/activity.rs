@@ -0,0 +1,138 @@
+//! Activities: things the player starts with a single key that then carry on
+//! turn after turn by themselves, until they're done or something comes up
+//! that needs the player's attention.
+
+use crate::companion::is_companion;
+use crate::game::Game;
+use crate::map::GameMap;
+use crate::object::{Object, PLAYER};
+use crate::pathfinding::{player_route, DistanceMap};
+use tcod::map::Map as FovMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActivityKind {
+    // walk to the nearest unexplored tile, over and over
+    Explore,
+    // walk to a tile the player picked, by the shortest way they know
+    Travel { target: (i32, i32) },
+    // pass turns until the player's back to full health
+    Rest,
+}
+
+impl ActivityKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ActivityKind::Explore => "exploring",
+            ActivityKind::Travel { .. } => "travelling",
+            ActivityKind::Rest => "resting",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Activity {
+    pub kind: ActivityKind,
+    // the player's hp as of the last step, losing any stops the activity
+    hp: i32,
+    // items that were already in view, so only new ones stop the activity
+    seen_items: Vec<(i32, i32)>,
+}
+
+impl Activity {
+    pub fn start(kind: ActivityKind, fov: &FovMap, objects: &[Object]) -> Self {
+        let mut activity = Activity {
+            kind,
+            hp: 0,
+            seen_items: vec![],
+        };
+        // whatever is around when it starts is no reason to stop
+        activity.interruption(fov, objects);
+        activity
+    }
+
+    /// true once there's nothing more to do
+    pub fn done(&self, game: &Game, objects: &[Object]) -> bool {
+        let player = &objects[PLAYER];
+        match self.kind {
+            ActivityKind::Explore => false,
+            ActivityKind::Travel { target } => player.pos() == target,
+            ActivityKind::Rest => player.fighter.map_or(0, |f| f.hp) >= player.max_hp(game),
+        }
+    }
+
+    /// What the player should stop for, if anything: an enemy in sight, an
+    /// item they haven't seen yet or getting hurt
+    pub fn interruption(&mut self, fov: &FovMap, objects: &[Object]) -> Option<String> {
+        let player = &objects[PLAYER];
+        let hp = player.fighter.map_or(0, |f| f.hp);
+        let hurt = hp < self.hp;
+        self.hp = hp;
+
+        let enemy = objects.iter().find(|object| {
+            object.alive
+                && object.fighter.is_some()
+                && fov.is_in_fov(object.x, object.y)
+                && object.visible_to(player)
+                && player.is_hostile_to(object)
+        });
+        if let Some(enemy) = enemy {
+            return Some(format!("You see a {}.", enemy.name));
+        }
+
+        let new_items: Vec<_> = objects
+            .iter()
+            .filter(|object| object.item.is_some() && fov.is_in_fov(object.x, object.y))
+            .filter(|object| !self.seen_items.contains(&object.pos()))
+            .collect();
+        self.seen_items
+            .extend(new_items.iter().map(|object| object.pos()));
+        if let Some(item) = new_items.first() {
+            return Some(format!("You spot a {}.", item.name));
+        }
+
+        hurt.then(|| "You've been hurt!".to_string())
+    }
+}
+
+/// Which way the player should step to get to the nearest unexplored tile
+/// they can reach, or why they can't
+pub fn explore_step(map: &GameMap, objects: &[Object]) -> Result<(i32, i32), &'static str> {
+    let unexplored = (0..map.width())
+        .flat_map(|x| (0..map.height()).map(move |y| (x, y)))
+        .filter(|&(x, y)| !map.tile(x, y).explored && map.tile(x, y).passable());
+    let distances = DistanceMap::to_nearest(map, unexplored);
+
+    let (x, y) = objects[PLAYER].pos();
+    let Some(here) = distances.distance(x, y) else {
+        return Err("There's nothing left to explore.");
+    };
+    // companions can be swapped with, anything else in the way is walked
+    // around
+    let occupied = |x, y| {
+        objects
+            .iter()
+            .any(|object| object.blocks && object.occupies(x, y) && !is_companion(object))
+    };
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| !occupied(x + dx, y + dy))
+        .filter_map(|(dx, dy)| Some((distances.distance(x + dx, y + dy)?, (dx, dy))))
+        .filter(|&(distance, _)| distance < here)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, step)| step)
+        .ok_or("Something is in the way.")
+}
+
+/// Which way the player should step next on the way to `target`, or why
+/// they can't
+pub fn travel_step(
+    target: (i32, i32),
+    map: &GameMap,
+    objects: &[Object],
+) -> Result<(i32, i32), &'static str> {
+    let (x, y) = objects[PLAYER].pos();
+    player_route(target, map, objects)
+        .and_then(|route| route.next_step())
+        .map(|(step_x, step_y)| (step_x - x, step_y - y))
+        .ok_or("You don't know a way there.")
+}
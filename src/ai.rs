@@ -0,0 +1,580 @@
+//! Monster AI: the `Ai` states and the behaviors that run them.
+
+use crate::game::Game;
+use crate::map::{is_blocked, new_monster};
+use crate::object::{
+    move_away_from, move_by, move_towards, mut_two, random_direction, Damage, DamageKind, Faction,
+    Object, PLAYER,
+};
+use crate::pathfinding::{move_along_path, move_downhill, move_uphill};
+use crate::ui::Tcod;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::line::Line;
+
+// chance each turn that an unaware monster turns to face another way
+const LOOK_AROUND_CHANCE: f32 = 0.2;
+// hp a fleeing monster gets back each turn it spends out of the player's sight
+const FLEE_RECOVERY: i32 = 1;
+// the boss throws a bolt every this many turns, more often once enraged
+const BOSS_BOLT_EVERY: u32 = 3;
+const ENRAGED_BOLT_EVERY: u32 = 2;
+const BOSS_BOLT_RANGE: f32 = 8.0;
+const BOSS_BOLT_DAMAGE: Damage = Damage {
+    amount: 6,
+    kind: DamageKind::Lightning,
+};
+// below this share of its hp the boss is enraged and calls for help
+const BOSS_ENRAGE_BELOW: f32 = 0.5;
+const BOSS_SUMMON_EVERY: u32 = 8;
+const BOSS_MINIONS: usize = 2;
+// how far monsters see anyone other than the player, who has their FOV
+const SIGHT_RANGE: f32 = 8.0;
+// allies with nobody to fight stay about this close to the player
+const FOLLOW_DISTANCE: f32 = 3.0;
+// companions only take on enemies this close to the player
+const COMPANION_LEASH: f32 = 6.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Ai {
+    Basic,
+    // unaware of the player until it spots them within `sight_radius` in
+    // front of it, or gets hurt, then turns Basic for good
+    Sleeping {
+        sight_radius: f32,
+    },
+    // shoots from up to `range` away and backs off when the player closes in
+    Ranged {
+        range: f32,
+    },
+    // stumbles around for `num_turns`, then goes back to `previous_ai`
+    Confused {
+        previous_ai: Box<Ai>,
+        num_turns: i32,
+    },
+    // runs from the player until its hp recovers, then goes back to `previous_ai`
+    Fleeing {
+        previous_ai: Box<Ai>,
+    },
+    // heads for a noise it heard, then goes back to `previous_ai`
+    Investigating {
+        target: (i32, i32),
+        previous_ai: Box<Ai>,
+    },
+    // guards its arena: fights up close and throws bolts every few turns,
+    // then summons minions once it's `enraged` by losing half its hp
+    Boss {
+        turn: u32,
+        enraged: bool,
+    },
+    // the player's companion: sticks close to them and fights what they fight
+    Follower,
+}
+
+impl Ai {
+    /// The stored `Ai` is just the state; this builds the behavior that runs it.
+    /// New monster types implement `MonsterBehavior` and get a variant here.
+    fn behavior(&self) -> Box<dyn MonsterBehavior> {
+        use Ai::*;
+        match self {
+            Basic => Box::new(BasicMonster),
+            Sleeping { sight_radius } => Box::new(SleepingMonster {
+                sight_radius: *sight_radius,
+            }),
+            Ranged { range } => Box::new(RangedMonster { range: *range }),
+            Confused {
+                previous_ai,
+                num_turns,
+            } => Box::new(ConfusedMonster {
+                previous_ai: (**previous_ai).clone(),
+                num_turns: *num_turns,
+            }),
+            Fleeing { previous_ai } => Box::new(FleeingMonster {
+                previous_ai: (**previous_ai).clone(),
+            }),
+            Investigating {
+                target,
+                previous_ai,
+            } => Box::new(InvestigatingMonster {
+                target: *target,
+                previous_ai: (**previous_ai).clone(),
+            }),
+            Boss { turn, enraged } => Box::new(BossMonster {
+                turn: *turn,
+                enraged: *enraged,
+            }),
+            Follower => Box::new(FollowerMonster),
+        }
+    }
+
+    /// The state after hearing a noise at `target`. Sleeping monsters wake
+    /// up and fighting ones come over to look; the rest are too busy.
+    pub fn hear(self, target: (i32, i32)) -> Ai {
+        use Ai::*;
+        let previous_ai = match self {
+            Sleeping { .. } => Basic,
+            Basic | Ranged { .. } => self,
+            Investigating { previous_ai, .. } => *previous_ai,
+            Confused { .. } | Fleeing { .. } | Boss { .. } | Follower => return self,
+        };
+        Investigating {
+            target,
+            previous_ai: Box::new(previous_ai),
+        }
+    }
+}
+
+trait MonsterBehavior {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]);
+}
+
+/// walks up to the nearest enemy and hits it
+struct BasicMonster;
+
+/// dozes and looks around until it notices the player
+struct SleepingMonster {
+    sight_radius: f32,
+}
+
+/// keeps its enemies at arm's length and shoots them
+struct RangedMonster {
+    range: f32,
+}
+
+/// moves at random until the confusion wears off, lashing out at whoever
+/// it stumbles into
+struct ConfusedMonster {
+    previous_ai: Ai,
+    num_turns: i32,
+}
+
+/// runs away, and fights back only when cornered
+struct FleeingMonster {
+    previous_ai: Ai,
+}
+
+/// walks over to where it heard something
+struct InvestigatingMonster {
+    target: (i32, i32),
+    previous_ai: Ai,
+}
+
+/// the master of the last level, see `Ai::Boss`
+struct BossMonster {
+    turn: u32,
+    enraged: bool,
+}
+
+/// stays by the player's side, see `Ai::Follower`
+struct FollowerMonster;
+
+/// true if nothing between the two points blocks a projectile. Walks the
+/// Bresenham line between them; the end points themselves don't count.
+pub fn has_line_of_fire(from: (i32, i32), to: (i32, i32), game: &Game) -> bool {
+    Line::new(from, to)
+        .take_while(|&point| point != to)
+        .all(|(x, y)| !game.map.tile(x, y).block_site)
+}
+
+/// Head for the player. Monsters a single tile big all follow the shared
+/// distance map; bigger ones need a route their whole footprint fits through.
+fn chase_player(monster_id: usize, game: &mut Game, objects: &mut [Object]) {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    if objects[monster_id].size != (1, 1) {
+        move_along_path(monster_id, player_x, player_y, &mut game.map, objects);
+    } else if !move_downhill(monster_id, &game.player_distances, &mut game.map, objects) {
+        // the way is crowded, try to squeeze past
+        move_towards(monster_id, player_x, player_y, &mut game.map, objects);
+    }
+}
+
+/// The closest living fighter of another faction the monster can see. The
+/// player's FOV doubles as the line of sight to the player; anyone else has
+/// to be within SIGHT_RANGE with nothing in between.
+fn pick_target(monster_id: usize, tcod: &Tcod, game: &Game, objects: &[Object]) -> Option<usize> {
+    let monster = &objects[monster_id];
+    objects
+        .iter()
+        .enumerate()
+        .filter(|&(id, other)| {
+            id != monster_id
+                && other.fighter.is_some_and(|f| f.hp > 0)
+                && monster.is_hostile_to(other)
+                && other.visible_to(monster)
+        })
+        .filter(|&(id, other)| {
+            if id == PLAYER {
+                tcod.fov.is_in_fov(monster.x, monster.y)
+            } else {
+                monster.distance_to(other) <= SIGHT_RANGE
+                    && has_line_of_fire(monster.pos(), other.pos(), game)
+            }
+        })
+        .map(|(id, other)| (id, monster.distance_to(other)))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(id, _)| id)
+}
+
+/// Head for the target: the player by the shared distance map, anyone else
+/// along a route of its own
+fn chase(monster_id: usize, target_id: usize, game: &mut Game, objects: &mut [Object]) {
+    if target_id == PLAYER {
+        chase_player(monster_id, game, objects);
+    } else {
+        let (target_x, target_y) = objects[target_id].pos();
+        move_along_path(monster_id, target_x, target_y, &mut game.map, objects);
+    }
+}
+
+/// true if the monster can see the player: close enough, with nothing in
+/// between, somewhere in front of it and not invisible to it. Sneaking players have to come
+/// twice as close.
+fn spots_player(
+    monster_id: usize,
+    sight_radius: f32,
+    tcod: &Tcod,
+    game: &Game,
+    objects: &[Object],
+) -> bool {
+    let monster = &objects[monster_id];
+    let (monster_x, monster_y) = monster.pos();
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let sight_radius = if game.sneaking {
+        sight_radius / 2.0
+    } else {
+        sight_radius
+    };
+
+    // the player's FOV doubles as the monster's line of sight
+    let (facing_x, facing_y) = monster.facing;
+    let in_front = facing_x * (player_x - monster_x) + facing_y * (player_y - monster_y) >= 0;
+    tcod.fov.is_in_fov(monster_x, monster_y)
+        && monster.distance_to(&objects[PLAYER]) <= sight_radius
+        && in_front
+        && objects[PLAYER].visible_to(monster)
+}
+
+/// true if the monster is hurt badly enough to run away
+fn frightened(monster: &Object, game: &Game) -> bool {
+    monster
+        .fighter
+        .is_some_and(|f| (f.hp as f32) < f.flee_below * monster.max_hp(game) as f32)
+}
+
+pub fn ai_take_turn(monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+    // only monsters that are out to fight lose their nerve, and the
+    // player's allies never run from them
+    let monster = &mut objects[monster_id];
+    let fighting = matches!(monster.ai, Some(Ai::Basic) | Some(Ai::Ranged { .. }))
+        && monster.allegiance() != Faction::Player;
+    if fighting && frightened(monster, game) {
+        if let Some(previous_ai) = monster.ai.take() {
+            monster.ai = Some(Ai::Fleeing {
+                previous_ai: Box::new(previous_ai),
+            });
+            game.messages
+                .add(format!("The {} turns to flee!", monster.name), LIGHT_YELLOW);
+        }
+    }
+
+    if let Some(behavior) = objects[monster_id].ai.as_ref().map(Ai::behavior) {
+        behavior.take_turn(monster_id, tcod, game, objects);
+    }
+}
+
+impl MonsterBehavior for BasicMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        match pick_target(monster_id, tcod, game, objects) {
+            // move towards the target if not already next to it
+            Some(target_id) if objects[monster_id].distance_to(&objects[target_id]) >= 2.0 => {
+                chase(monster_id, target_id, game, objects);
+            }
+            Some(target_id) => {
+                let (monster, target) = mut_two(monster_id, target_id, objects);
+                monster.attack(target, &mut rand::thread_rng(), game);
+            }
+            // allies with nothing to fight tag along
+            None if objects[monster_id].allegiance() == Faction::Player
+                && objects[monster_id].distance_to(&objects[PLAYER]) > FOLLOW_DISTANCE =>
+            {
+                chase_player(monster_id, game, objects);
+            }
+            None => {}
+        }
+    }
+}
+
+impl MonsterBehavior for SleepingMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        let monster = &objects[monster_id];
+        let hurt = monster.fighter.is_some_and(|f| f.hp < monster.max_hp(game));
+        if hurt {
+            objects[monster_id].ai = Some(Ai::Basic);
+            game.messages.add(
+                format!("The {} wakes up!", objects[monster_id].name),
+                LIGHT_YELLOW,
+            );
+        } else if spots_player(monster_id, self.sight_radius, tcod, game, objects) {
+            // noticing the player takes the monster's whole turn
+            objects[monster_id].ai = Some(Ai::Basic);
+            game.messages.add(
+                format!("The {} spots you!", objects[monster_id].name),
+                LIGHT_YELLOW,
+            );
+        } else if rand::random::<f32>() < LOOK_AROUND_CHANCE {
+            objects[monster_id].facing = random_direction(&mut rand::thread_rng());
+        }
+    }
+}
+
+impl MonsterBehavior for RangedMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        let Some(target_id) = pick_target(monster_id, tcod, game, objects) else {
+            return;
+        };
+
+        let (target_x, target_y) = objects[target_id].pos();
+        let distance = objects[monster_id].distance_to(&objects[target_id]);
+        if distance < 2.0 {
+            // too close, back off and only fight in melee when cornered
+            let retreated = move_away_from(monster_id, target_x, target_y, &mut game.map, objects);
+            if !retreated {
+                let (monster, target) = mut_two(monster_id, target_id, objects);
+                monster.attack(target, &mut rand::thread_rng(), game);
+            }
+        } else if distance <= self.range
+            && has_line_of_fire(objects[monster_id].pos(), (target_x, target_y), game)
+        {
+            let power = objects[monster_id].power(game);
+            let (monster, target) = mut_two(monster_id, target_id, objects);
+            monster.shoot(target, power, "arrow", &mut rand::thread_rng(), game);
+        } else {
+            chase(monster_id, target_id, game, objects);
+        }
+    }
+}
+
+impl MonsterBehavior for BossMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        // it waits in its arena for the player to show up
+        let (monster_x, monster_y) = objects[monster_id].pos();
+        if !tcod.fov.is_in_fov(monster_x, monster_y) {
+            return;
+        }
+
+        let turn = self.turn + 1;
+        let monster = &objects[monster_id];
+        let enraged = self.enraged
+            || monster
+                .fighter
+                .is_some_and(|f| (f.hp as f32) < BOSS_ENRAGE_BELOW * monster.max_hp(game) as f32);
+        if enraged && !self.enraged {
+            game.messages.add(
+                format!(
+                    "The {} shrieks and calls the dead to its side!",
+                    objects[monster_id].name
+                ),
+                LIGHT_RED,
+            );
+        }
+        objects[monster_id].ai = Some(Ai::Boss { turn, enraged });
+
+        let player_alive = objects[PLAYER].fighter.is_some_and(|f| f.hp > 0);
+        let (player_x, player_y) = objects[PLAYER].pos();
+        let distance = objects[monster_id].distance_to(&objects[PLAYER]);
+        let bolt_every = if enraged {
+            ENRAGED_BOLT_EVERY
+        } else {
+            BOSS_BOLT_EVERY
+        };
+
+        if enraged && (!self.enraged || turn.is_multiple_of(BOSS_SUMMON_EVERY)) {
+            summon_minions(monster_id, game, objects);
+        } else if turn.is_multiple_of(bolt_every)
+            && distance <= BOSS_BOLT_RANGE
+            && has_line_of_fire((monster_x, monster_y), (player_x, player_y), game)
+            && player_alive
+        {
+            game.messages.add(
+                format!(
+                    "The {} hurls a bolt of lightning at you for {} hit points!",
+                    objects[monster_id].name,
+                    objects[PLAYER].resist(BOSS_BOLT_DAMAGE)
+                ),
+                LIGHT_BLUE,
+            );
+            objects[PLAYER].take_damage(BOSS_BOLT_DAMAGE, game);
+        } else if distance >= 2.0 {
+            chase_player(monster_id, game, objects);
+        } else if player_alive {
+            let (monster, player) = mut_two(monster_id, PLAYER, objects);
+            monster.attack(player, &mut rand::thread_rng(), game);
+        }
+    }
+}
+
+/// raise minions on the free tiles around the monster, they join the level
+/// once the turn is over
+fn summon_minions(monster_id: usize, game: &mut Game, objects: &[Object]) {
+    let (x, y) = objects[monster_id].pos();
+    let mut spots: Vec<_> = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+        .filter(|&(spot_x, spot_y)| {
+            game.map.in_bounds(spot_x, spot_y)
+                && !game.map.tile(spot_x, spot_y).blocked
+                && !objects
+                    .iter()
+                    .any(|object| object.blocks && object.occupies(spot_x, spot_y))
+        })
+        .collect();
+    let mut rng = rand::thread_rng();
+    rng.shuffle(&mut spots);
+
+    for &(spot_x, spot_y) in spots.iter().take(BOSS_MINIONS) {
+        let mut minion = new_monster("skeleton", spot_x, spot_y);
+        minion.ai = Some(Ai::Basic);
+        minion.alive = true;
+        game.spawned.push(minion);
+    }
+}
+
+impl MonsterBehavior for ConfusedMonster {
+    fn take_turn(&self, monster_id: usize, _tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        if self.num_turns > 0 {
+            // move in a random direction, and use up one of the confused turns
+            let mut rng = rand::thread_rng();
+            let (dx, dy) = (rng.gen_range(-1, 2), rng.gen_range(-1, 2));
+            let (x, y) = objects[monster_id].pos();
+            // friend or foe, it can't tell them apart
+            let bumped_id = objects.iter().position(|other| {
+                other.fighter.is_some_and(|f| f.hp > 0)
+                    && other.occupies(x + dx, y + dy)
+                    && !other.occupies(x, y)
+            });
+            match bumped_id {
+                Some(bumped_id) => {
+                    let (monster, bumped) = mut_two(monster_id, bumped_id, objects);
+                    monster.attack(bumped, &mut rng, game);
+                }
+                None => {
+                    move_by(monster_id, dx, dy, &mut game.map, objects);
+                }
+            }
+            objects[monster_id].ai = Some(Ai::Confused {
+                previous_ai: Box::new(self.previous_ai.clone()),
+                num_turns: self.num_turns - 1,
+            });
+        } else {
+            // restore the previous AI (this one will be deleted)
+            objects[monster_id].ai = Some(self.previous_ai.clone());
+            game.messages.add(
+                format!("The {} is no longer confused!", objects[monster_id].name),
+                RED,
+            );
+        }
+    }
+}
+
+impl MonsterBehavior for FleeingMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        let (monster_x, monster_y) = objects[monster_id].pos();
+        let in_sight = tcod.fov.is_in_fov(monster_x, monster_y);
+        if !in_sight {
+            // out of the player's sight it gets to catch its breath
+            objects[monster_id].heal(FLEE_RECOVERY, game);
+        }
+
+        if !frightened(&objects[monster_id], game) {
+            objects[monster_id].ai = Some(self.previous_ai.clone());
+            game.messages.add(
+                format!("The {} regains its courage!", objects[monster_id].name),
+                RED,
+            );
+            return;
+        }
+
+        if in_sight && !move_uphill(monster_id, &game.player_distances, &mut game.map, objects) {
+            // nowhere left to run, so it fights back if it can
+            let adjacent = objects[monster_id].distance_to(&objects[PLAYER]) < 2.0;
+            let seen = objects[PLAYER].visible_to(&objects[monster_id]);
+            if adjacent && seen && objects[PLAYER].fighter.is_some_and(|f| f.hp > 0) {
+                let (monster, player) = mut_two(monster_id, PLAYER, objects);
+                monster.attack(player, &mut rand::thread_rng(), game);
+            }
+        }
+    }
+}
+
+impl MonsterBehavior for InvestigatingMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        let (monster_x, monster_y) = objects[monster_id].pos();
+        let (target_x, target_y) = self.target;
+        // done once it finds the player or gets there; it gives up when stuck
+        let done = tcod.fov.is_in_fov(monster_x, monster_y)
+            || objects[monster_id].distance(target_x, target_y) < 2.0
+            || {
+                move_along_path(monster_id, target_x, target_y, &mut game.map, objects);
+                objects[monster_id].pos() == (monster_x, monster_y)
+            };
+        if done {
+            objects[monster_id].ai = Some(self.previous_ai.clone());
+        }
+    }
+}
+
+impl MonsterBehavior for FollowerMonster {
+    fn take_turn(&self, monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+        // whatever the player is fighting: the enemy in view closest to them
+        let companion = &objects[monster_id];
+        let player = &objects[PLAYER];
+        let target_id = objects
+            .iter()
+            .enumerate()
+            .filter(|&(_, other)| {
+                other.fighter.is_some_and(|f| f.hp > 0)
+                    && companion.is_hostile_to(other)
+                    && other.visible_to(companion)
+                    && other.tiles().any(|(x, y)| tcod.fov.is_in_fov(x, y))
+                    && player.distance_to(other) <= COMPANION_LEASH
+            })
+            .map(|(id, other)| (id, player.distance_to(other)))
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(id, _)| id);
+
+        match target_id {
+            Some(target_id) if objects[monster_id].distance_to(&objects[target_id]) >= 2.0 => {
+                chase(monster_id, target_id, game, objects);
+            }
+            Some(target_id) => {
+                let (companion, target) = mut_two(monster_id, target_id, objects);
+                companion.attack(target, &mut rand::thread_rng(), game);
+            }
+            None if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 => {
+                chase_player(monster_id, game, objects);
+            }
+            None => step_out_of_doorway(monster_id, game, objects),
+        }
+    }
+}
+
+/// Move off a doorway onto a free tile that's still next to the player, so
+/// an idle companion never keeps a door from closing
+fn step_out_of_doorway(monster_id: usize, game: &mut Game, objects: &mut [Object]) {
+    let (x, y) = objects[monster_id].pos();
+    if game.map.tile(x, y).door.is_none() {
+        return;
+    }
+    let player = &objects[PLAYER];
+    let spot = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+        .find(|&(spot_x, spot_y)| {
+            game.map.in_bounds(spot_x, spot_y)
+                && game.map.tile(spot_x, spot_y).door.is_none()
+                && !is_blocked(spot_x, spot_y, &game.map, objects)
+                && player.distance(spot_x, spot_y) < 2.0
+        });
+    if let Some((spot_x, spot_y)) = spot {
+        move_by(monster_id, spot_x - x, spot_y - y, &mut game.map, objects);
+    }
+}
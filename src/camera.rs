@@ -0,0 +1,43 @@
+//! The camera: which part of a map too big for the screen is shown, kept
+//! centered on the player.
+
+/// A window onto the map, `width` by `height` cells with its top-left
+/// corner at map tile (x, y)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Camera {
+    pub fn new(width: i32, height: i32) -> Self {
+        Camera {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Center on tile (x, y), but stop at the map's edges rather than show
+    /// what's past them
+    pub fn center_on(&mut self, (x, y): (i32, i32), map_width: i32, map_height: i32) {
+        self.x = (x - self.width / 2).clamp(0, (map_width - self.width).max(0));
+        self.y = (y - self.height / 2).clamp(0, (map_height - self.height).max(0));
+    }
+
+    /// where map tile (x, y) is on screen, `None` if it's out of view
+    pub fn to_screen(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let (screen_x, screen_y) = (x - self.x, y - self.y);
+        let on_screen = (0..self.width).contains(&screen_x) && (0..self.height).contains(&screen_y);
+        on_screen.then_some((screen_x, screen_y))
+    }
+
+    /// the map tile under screen cell (x, y), `None` outside the view
+    pub fn to_map(self, screen_x: i32, screen_y: i32) -> Option<(i32, i32)> {
+        let on_screen = (0..self.width).contains(&screen_x) && (0..self.height).contains(&screen_y);
+        on_screen.then_some((screen_x + self.x, screen_y + self.y))
+    }
+}
@@ -0,0 +1,116 @@
+//! Classes the player picks from when starting a game, each with its own
+//! stats and starting kit.
+
+use crate::items::{AmmoKind, Item};
+use crate::map::new_item;
+use crate::object::{DeathCallback, Fighter, Object, Resistances};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Class {
+    #[default]
+    Warrior,
+    Mage,
+    Rogue,
+}
+
+/// every class, in the order the new game menu lists them
+pub const CLASSES: &[Class] = &[Class::Warrior, Class::Mage, Class::Rogue];
+
+impl Class {
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Warrior => "Warrior",
+            Class::Mage => "Mage",
+            Class::Rogue => "Rogue",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Class::Warrior => "tough, with a sword and shield",
+            Class::Mage => "frail, with a bag of scrolls",
+            Class::Rogue => "quick to hit hard, with a sling and a trap",
+        }
+    }
+}
+
+/// The player's stats and starting inventory for a class. Weapons and armour
+/// come equipped.
+pub fn starting_loadout(class: Class) -> (Fighter, Vec<Object>) {
+    let fighter = |base_max_hp, base_defense, base_power, base_accuracy, crit_chance| Fighter {
+        base_max_hp,
+        hp: base_max_hp,
+        base_defense,
+        base_power,
+        base_accuracy,
+        crit_chance,
+        flee_below: 0.0,
+        xp: 0,
+        on_death: DeathCallback::Player,
+        resistances: Resistances::NONE,
+    };
+    let (fighter, items) = match class {
+        Class::Warrior => (
+            fighter(30, 2, 5, 2, 0.1),
+            vec![Item::Sword, Item::Shield, Item::Heal],
+        ),
+        Class::Mage => (
+            fighter(20, 1, 3, 2, 0.1),
+            vec![
+                Item::Lightning,
+                Item::Lightning,
+                Item::Confuse,
+                Item::Fireball,
+                Item::Heal,
+            ],
+        ),
+        Class::Rogue => (
+            fighter(25, 2, 4, 4, 0.2),
+            vec![
+                Item::Sling,
+                Item::Ammo {
+                    kind: AmmoKind::Stone,
+                    count: 20,
+                },
+                Item::PlaceTrap,
+                Item::Heal,
+            ],
+        ),
+    };
+
+    let inventory = items
+        .into_iter()
+        .map(|item| {
+            let mut object = new_item(item, 0, 0);
+            if let Some(equipment) = object.equipment.as_mut() {
+                equipment.equipped = true;
+            }
+            object
+        })
+        .collect();
+    (fighter, inventory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_class_starts_healthy_and_equipped() {
+        for &class in CLASSES {
+            let (fighter, inventory) = starting_loadout(class);
+            assert_eq!(fighter.hp, fighter.base_max_hp);
+            assert!(!inventory.is_empty());
+            let equipped: Vec<_> = inventory
+                .iter()
+                .filter_map(|object| object.equipment)
+                .collect();
+            assert!(equipped.iter().all(|equipment| equipment.equipped));
+            // at most one thing in each slot
+            for (i, a) in equipped.iter().enumerate() {
+                assert!(equipped[i + 1..].iter().all(|b| b.slot != a.slot));
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! The attack roll: whether a blow lands, how hard it hits and whether it's
+//! a critical hit. Only numbers go in and out, the fighters and the
+//! messages stay in `object`.
+
+use rand::Rng;
+
+// chance to hit when accuracy and evasion are even, and how much each point
+// of difference moves it
+const BASE_HIT_CHANCE: f32 = 0.7;
+const HIT_CHANCE_PER_POINT: f32 = 0.05;
+// there's always some chance either way
+const MIN_HIT_CHANCE: f32 = 0.05;
+const MAX_HIT_CHANCE: f32 = 0.95;
+// without a weapon, hits land anywhere within DAMAGE_SPREAD of the
+// attacker's power
+const DAMAGE_SPREAD: i32 = 1;
+const CRIT_MULTIPLIER: i32 = 2;
+
+/// How an attack roll came out, with the damage done before resistances
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackRoll {
+    Miss,
+    Hit(i32),
+    // CRIT_MULTIPLIER times the damage of a hit
+    Crit(i32),
+}
+
+/// chance in [0, 1] of an attacker with `accuracy` hitting a defender with
+/// `evasion`
+pub fn hit_chance(accuracy: i32, evasion: i32) -> f32 {
+    let chance = BASE_HIT_CHANCE + (accuracy - evasion) as f32 * HIT_CHANCE_PER_POINT;
+    chance.clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE)
+}
+
+/// Lowest and highest damage of a blow from an attacker with `power`. A
+/// weapon adds its own roll on top, bare hands land within DAMAGE_SPREAD.
+pub fn damage_range(power: i32, weapon: Option<(i32, i32)>) -> (i32, i32) {
+    match weapon {
+        Some((min, max)) => (power + min, power + max),
+        None => (power - DAMAGE_SPREAD, power + DAMAGE_SPREAD),
+    }
+}
+
+/// damage of a blow anywhere in `(min, max)`, never below 0
+pub fn roll_damage<R: Rng>((min, max): (i32, i32), rng: &mut R) -> i32 {
+    rng.gen_range(min, max + 1).max(0)
+}
+
+/// Roll to hit, then for the damage, then for a critical hit
+pub fn attack_roll<R: Rng>(
+    accuracy: i32,
+    evasion: i32,
+    damage: (i32, i32),
+    crit_chance: f32,
+    rng: &mut R,
+) -> AttackRoll {
+    if rng.gen::<f32>() >= hit_chance(accuracy, evasion) {
+        return AttackRoll::Miss;
+    }
+    let damage = roll_damage(damage, rng);
+    if rng.gen::<f32>() < crit_chance {
+        AttackRoll::Crit(damage * CRIT_MULTIPLIER)
+    } else {
+        AttackRoll::Hit(damage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, XorShiftRng};
+
+    // rolls as high as an rng can, so no hit chance is ever good enough
+    struct HighRoller;
+
+    impl Rng for HighRoller {
+        fn next_u32(&mut self) -> u32 {
+            u32::MAX
+        }
+    }
+
+    #[test]
+    fn hit_chance_stays_within_its_bounds() {
+        assert_eq!(hit_chance(0, 0), BASE_HIT_CHANCE);
+        for &(accuracy, evasion) in &[(-1000, 1000), (0, 100), (-50, 0)] {
+            assert_eq!(hit_chance(accuracy, evasion), MIN_HIT_CHANCE);
+        }
+        for &(accuracy, evasion) in &[(1000, -1000), (100, 0), (0, -50)] {
+            assert_eq!(hit_chance(accuracy, evasion), MAX_HIT_CHANCE);
+        }
+        for accuracy in -30..=30 {
+            let chance = hit_chance(accuracy, 0);
+            assert!((MIN_HIT_CHANCE..=MAX_HIT_CHANCE).contains(&chance));
+        }
+    }
+
+    #[test]
+    fn a_miss_does_no_damage() {
+        // even the best odds leave a miss possible
+        let roll = attack_roll(1000, 0, (10, 20), 1.0, &mut HighRoller);
+        assert_eq!(roll, AttackRoll::Miss);
+    }
+
+    #[test]
+    fn damage_never_goes_below_zero() {
+        for seed in 0..20 {
+            let mut rng = XorShiftRng::from_seed([seed + 1, 2, 3, 4]);
+            for power in -5..=5 {
+                for &weapon in &[None, Some((0, 2)), Some((1, 5))] {
+                    let range = damage_range(power, weapon);
+                    let damage = roll_damage(range, &mut rng);
+                    assert!(damage >= 0);
+                    assert!(damage <= range.1.max(0));
+                    match attack_roll(0, 0, range, 0.5, &mut rng) {
+                        AttackRoll::Miss => (),
+                        AttackRoll::Hit(damage) | AttackRoll::Crit(damage) => {
+                            assert!(damage >= 0)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
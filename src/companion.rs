@@ -0,0 +1,89 @@
+//! Companions: strays the player can win over with food, who then follow
+//! them around, fight at their side and come along down the stairs.
+
+use crate::ai::Ai;
+use crate::game::Game;
+use crate::items::Item;
+use crate::map::is_blocked;
+use crate::object::{Faction, Object, PLAYER};
+use tcod::colors::*;
+
+// how far from the player a companion can be put down on a new level
+const ARRIVAL_RADIUS: i32 = 3;
+
+/// true for the player's followers, the ones that come along between levels
+pub fn is_companion(object: &Object) -> bool {
+    object.alive && object.ai == Some(Ai::Follower)
+}
+
+/// Feed a ration to the stray at `stray_id`, turning it into a companion.
+/// Returns false if the player has nothing to give it.
+pub fn befriend(stray_id: usize, game: &mut Game, objects: &mut [Object]) -> bool {
+    let food_id = game
+        .inventory
+        .iter()
+        .position(|item| item.item == Some(Item::Food));
+    let stray = &mut objects[stray_id];
+    let Some(food_id) = food_id else {
+        game.messages.add(
+            format!("The {} sniffs at you hopefully.", stray.name),
+            LIGHT_GREY,
+        );
+        return false;
+    };
+
+    game.inventory.remove(food_id);
+    stray.faction = Faction::Player;
+    stray.ai = Some(Ai::Follower);
+    // it's been saving up energy all this time with nothing to spend it on
+    stray.energy = 0;
+    game.messages.add(
+        format!(
+            "The {} wolfs down your food and wags its tail. It will follow you now!",
+            stray.name
+        ),
+        LIGHT_GREEN,
+    );
+    true
+}
+
+/// Trade places with the ally at `ally_id`, so the player can't be boxed in
+/// by their own friends. Returns false for allies too big to swap with.
+pub fn swap_places(ally_id: usize, objects: &mut [Object]) -> bool {
+    if objects[ally_id].size != (1, 1) {
+        return false;
+    }
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let (ally_x, ally_y) = objects[ally_id].pos();
+    objects[PLAYER].set_pos(ally_x, ally_y);
+    objects[ally_id].set_pos(player_x, player_y);
+    objects[ally_id].path = None;
+    true
+}
+
+/// Put the companions that followed the player down the stairs on free
+/// tiles around them. Any that can't find room are left behind on the
+/// stairs, so to speak, and lost.
+pub fn arrive_with(companions: Vec<Object>, game: &mut Game, objects: &mut Vec<Object>) {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    for mut companion in companions {
+        let spot = (1..=ARRIVAL_RADIUS)
+            .flat_map(|radius| {
+                (-radius..=radius)
+                    .flat_map(move |dx| (-radius..=radius).map(move |dy| (dx, dy)))
+                    .map(|(dx, dy)| (player_x + dx, player_y + dy))
+            })
+            .find(|&(x, y)| game.map.in_bounds(x, y) && !is_blocked(x, y, &game.map, objects));
+        match spot {
+            Some((x, y)) => {
+                companion.set_pos(x, y);
+                companion.path = None;
+                objects.push(companion);
+            }
+            None => game.messages.add(
+                format!("Your {} gets lost on the way.", companion.name),
+                LIGHT_GREY,
+            ),
+        }
+    }
+}
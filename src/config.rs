@@ -0,0 +1,463 @@
+//! Key bindings: which keys do what. Every action has default keys built
+//! in, and a `keys.toml` file shipped with the game can change them, one
+//! action per line:
+//!
+//! ```text
+//! # comments start with a hash
+//! MoveUp = ["Up", "NumPad8", "k"]
+//! Pickup = "g"
+//! ```
+//!
+//! A key is either the single character it types, so `"g"` and `"G"` are
+//! different keys, or the name of a special key like `"Up"`, `"Tab"`,
+//! `"F2"` or `"NumPad8"`. An action listed in the file loses its default
+//! keys; anything the file leaves out or gets wrong keeps them.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tcod::input::{Key, KeyCode};
+
+/// Everything the player can do with a key press on the map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Wait,
+    Rest,
+    Explore,
+    Search,
+    CloseDoors,
+    CastSpell,
+    Sneak,
+    Pickup,
+    Descend,
+    Ascend,
+    Inventory,
+    Eat,
+    Fire,
+    Throw,
+    Drop,
+    DebugGrid,
+    Minimap,
+    MonsterHealth,
+    DamageNumbers,
+    MessageLog,
+    Quit,
+    Help,
+    Examine,
+    Character,
+}
+
+impl Action {
+    /// which way a movement action steps, `None` for everything else
+    pub fn step(self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveUp => Some((0, -1)),
+            Action::MoveDown => Some((0, 1)),
+            Action::MoveLeft => Some((-1, 0)),
+            Action::MoveRight => Some((1, 0)),
+            Action::MoveUpLeft => Some((-1, -1)),
+            Action::MoveUpRight => Some((1, -1)),
+            Action::MoveDownLeft => Some((-1, 1)),
+            Action::MoveDownRight => Some((1, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// A key as the bindings know it: a special key by its code, or a printable
+/// one by the character it types
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Code(KeyCode),
+    Char(char),
+}
+
+impl Binding {
+    pub fn matches(self, key: Key) -> bool {
+        match self {
+            Binding::Code(code) => key.code == code,
+            Binding::Char(c) => key.code == KeyCode::Text && key.text().starts_with(c),
+        }
+    }
+
+    /// read a key the way `keys.toml` writes it
+    fn parse(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Binding::Char(c)),
+            _ => KEY_NAMES
+                .iter()
+                .find(|&&(key_name, _)| key_name == name)
+                .map(|&(_, code)| Binding::Code(code)),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Char(c) => write!(f, "{}", c),
+            Binding::Code(code) => {
+                let name = KEY_NAMES
+                    .iter()
+                    .find(|&&(_, key_code)| key_code == code)
+                    .map_or("?", |&(name, _)| name);
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+// the special keys a binding can name
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Backspace", KeyCode::Backspace),
+    ("Tab", KeyCode::Tab),
+    ("Enter", KeyCode::Enter),
+    ("Escape", KeyCode::Escape),
+    ("Space", KeyCode::Spacebar),
+    ("NumPad0", KeyCode::NumPad0),
+    ("NumPad1", KeyCode::NumPad1),
+    ("NumPad2", KeyCode::NumPad2),
+    ("NumPad3", KeyCode::NumPad3),
+    ("NumPad4", KeyCode::NumPad4),
+    ("NumPad5", KeyCode::NumPad5),
+    ("NumPad6", KeyCode::NumPad6),
+    ("NumPad7", KeyCode::NumPad7),
+    ("NumPad8", KeyCode::NumPad8),
+    ("NumPad9", KeyCode::NumPad9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+];
+
+// every action, the name `keys.toml` knows it by, its default keys and what
+// the help screen says it does
+const DEFAULT_KEYS: &[(Action, &str, &[&str], &str)] = &[
+    (
+        Action::MoveUp,
+        "MoveUp",
+        &["Up", "NumPad8", "k"],
+        "move or attack up",
+    ),
+    (
+        Action::MoveDown,
+        "MoveDown",
+        &["Down", "NumPad2", "j"],
+        "move or attack down",
+    ),
+    (
+        Action::MoveLeft,
+        "MoveLeft",
+        &["Left", "NumPad4", "h"],
+        "move or attack left",
+    ),
+    (
+        Action::MoveRight,
+        "MoveRight",
+        &["Right", "NumPad6", "l"],
+        "move or attack right",
+    ),
+    (
+        Action::MoveUpLeft,
+        "MoveUpLeft",
+        &["NumPad7", "y"],
+        "move or attack up-left",
+    ),
+    (
+        Action::MoveUpRight,
+        "MoveUpRight",
+        &["NumPad9", "u"],
+        "move or attack up-right",
+    ),
+    (
+        Action::MoveDownLeft,
+        "MoveDownLeft",
+        &["NumPad1", "b"],
+        "move or attack down-left",
+    ),
+    (
+        Action::MoveDownRight,
+        "MoveDownRight",
+        &["NumPad3", "n"],
+        "move or attack down-right",
+    ),
+    (Action::Wait, "Wait", &[".", "NumPad5"], "wait a turn"),
+    (Action::Rest, "Rest", &["R"], "rest until healed"),
+    (Action::Explore, "Explore", &["o"], "explore the level"),
+    (
+        Action::Search,
+        "Search",
+        &["s"],
+        "search for secret passages and traps",
+    ),
+    (
+        Action::CloseDoors,
+        "CloseDoors",
+        &["c"],
+        "close the doors nearby",
+    ),
+    (Action::CastSpell, "CastSpell", &["z"], "cast a spell"),
+    (Action::Sneak, "Sneak", &["Z"], "start or stop sneaking"),
+    (Action::Pickup, "Pickup", &["g"], "pick up an item"),
+    (Action::Descend, "Descend", &[">"], "go down the stairs"),
+    (Action::Ascend, "Ascend", &["<"], "go up the stairs"),
+    (Action::Inventory, "Inventory", &["i"], "use an item"),
+    (Action::Eat, "Eat", &["e"], "eat some food"),
+    (
+        Action::Fire,
+        "Fire",
+        &["f"],
+        "shoot the equipped bow or sling",
+    ),
+    (Action::Throw, "Throw", &["t"], "throw an item"),
+    (Action::Drop, "Drop", &["d"], "drop an item"),
+    (
+        Action::DebugGrid,
+        "DebugGrid",
+        &["F2"],
+        "show the coordinate grid",
+    ),
+    (Action::Minimap, "Minimap", &["Tab"], "show the minimap"),
+    (
+        Action::MonsterHealth,
+        "MonsterHealth",
+        &["H"],
+        "tint monsters by their health",
+    ),
+    (
+        Action::DamageNumbers,
+        "DamageNumbers",
+        &["N"],
+        "show the damage from every hit",
+    ),
+    (
+        Action::MessageLog,
+        "MessageLog",
+        &["p"],
+        "show the message log",
+    ),
+    (Action::Quit, "Quit", &["Escape"], "save and quit"),
+    (Action::Help, "Help", &["?"], "show this help"),
+    (Action::Examine, "Examine", &["x"], "look around the map"),
+    (
+        Action::Character,
+        "Character",
+        &["C"],
+        "show the character screen",
+    ),
+];
+
+/// The keys bound to every action
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Binding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_KEYS
+            .iter()
+            .map(|&(action, _, keys, _)| {
+                let keys = keys.iter().filter_map(|key| Binding::parse(key)).collect();
+                (action, keys)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// The defaults with whatever `text` changes. Lines that can't be used
+    /// are handed back as warnings, along with the actions left out.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut keymap = Keymap::default();
+        let mut warnings = vec![];
+        let mut listed = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match keymap.bind_line(line) {
+                Ok(action) => listed.push(action),
+                Err(error) => warnings.push(format!("line {}: {}", number + 1, error)),
+            }
+        }
+
+        let missing: Vec<_> = DEFAULT_KEYS
+            .iter()
+            .filter(|(action, _, _, _)| !listed.contains(action))
+            .map(|&(_, name, _, _)| name)
+            .collect();
+        if !missing.is_empty() {
+            warnings.push(format!("using the default keys for {}", missing.join(", ")));
+        }
+        warnings.extend(keymap.duplicates());
+        (keymap, warnings)
+    }
+
+    /// A warning for every key bound to more than one action. Only the
+    /// first of them, in the order the help screen lists them, gets the key.
+    fn duplicates(&self) -> Vec<String> {
+        let names: Vec<_> = DEFAULT_KEYS.iter().map(|&(_, name, _, _)| name).collect();
+        let mut warnings = vec![];
+        for (index, (_, keys)) in self.bindings.iter().enumerate() {
+            for key in keys {
+                let first = self.bindings[..index]
+                    .iter()
+                    .position(|(_, earlier)| earlier.contains(key));
+                if let Some(first) = first {
+                    warnings.push(format!(
+                        "{} is bound to both {} and {}, only {} gets it",
+                        key, names[first], names[index], names[first]
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Rebind the action on one `Action = "key"` or `Action = ["key", ...]` line
+    fn bind_line(&mut self, line: &str) -> Result<Action, String> {
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected `Action = \"key\"`, found `{}`", line))?;
+        let name = name.trim();
+        let action = DEFAULT_KEYS
+            .iter()
+            .find(|&&(_, action_name, _, _)| action_name == name)
+            .map(|&(action, _, _, _)| action)
+            .ok_or_else(|| format!("there is no action called {}", name))?;
+
+        let value = value.trim();
+        let list = value
+            .strip_prefix('[')
+            .and_then(|value| value.strip_suffix(']'))
+            .unwrap_or(value);
+        let keys = quoted_strings(list)?
+            .iter()
+            .map(|key| Binding::parse(key).ok_or_else(|| format!("unknown key \"{}\"", key)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(format!("no keys given for {}", name));
+        }
+
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(bound, _)| *bound == action) {
+            *bound = keys;
+        }
+        Ok(action)
+    }
+
+    /// every action with its keys and what it does, for the help screen
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        self.bindings
+            .iter()
+            .zip(DEFAULT_KEYS)
+            .map(|((_, keys), &(_, _, _, description))| {
+                let keys: Vec<_> = keys.iter().map(Binding::to_string).collect();
+                (keys.join(", "), description)
+            })
+            .collect()
+    }
+
+    /// the action the key is bound to, if any
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|binding| binding.matches(key)))
+            .map(|&(action, _)| action)
+    }
+}
+
+/// The `"..."` strings in a comma separated list, with `\"` and `\\` for a
+/// quote or a backslash inside one
+fn quoted_strings(list: &str) -> Result<Vec<String>, String> {
+    let mut strings = vec![];
+    let mut chars = list.trim().chars().peekable();
+    while chars.peek().is_some() {
+        if chars.next() != Some('"') {
+            return Err(format!(
+                "keys need to be in quotes, found `{}`",
+                list.trim()
+            ));
+        }
+        let mut string = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => string.extend(chars.next()),
+                Some(c) => string.push(c),
+                None => return Err(format!("unclosed quote in `{}`", list.trim())),
+            }
+        }
+        strings.push(string);
+
+        // then on to the next one, if there is one
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.next() {
+            None => break,
+            Some(',') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            Some(c) => return Err(format!("expected a comma, found `{}`", c)),
+        }
+    }
+    Ok(strings)
+}
+
+/// Read the keymap from the file at `path`
+pub fn load_keymap(path: &Path) -> Result<(Keymap, Vec<String>), String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    Ok(Keymap::parse(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shipped_keys_parse_cleanly() {
+        let (_, warnings) = Keymap::parse(include_str!("../keys.toml"));
+        assert_eq!(warnings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn a_key_bound_twice_is_warned_about() {
+        // the default for CloseDoors is "c" too
+        let (_, warnings) = Keymap::parse("Sneak = [\"Z\", \"c\"]");
+        let duplicate = "c is bound to both CloseDoors and Sneak, only CloseDoors gets it";
+        assert!(warnings.iter().any(|warning| warning == duplicate));
+    }
+}
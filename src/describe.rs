@@ -0,0 +1,137 @@
+//! What the player can tell about a tile and whatever's on it, shared by
+//! the names under the mouse and the `x` examine mode.
+
+use crate::ai::Ai;
+use crate::game::Game;
+use crate::identify::{is_identified, item_name};
+use crate::items::Item;
+use crate::map::{Door, Terrain, Tile};
+use crate::object::{Faction, Object, PLAYER};
+use crate::status::StatusKind;
+use tcod::map::Map as FovMap;
+
+/// One thing on a tile: what it's called and a line about it, which may be
+/// empty when there's nothing more to say
+#[derive(Clone, Debug, PartialEq)]
+pub struct Description {
+    pub name: String,
+    pub detail: String,
+}
+
+/// Everything the player knows about tile (x, y): the objects on it they can
+/// see, then the tile itself. Empty for tiles they've never seen.
+pub fn describe(x: i32, y: i32, game: &Game, objects: &[Object], fov: &FovMap) -> Vec<Description> {
+    if !game.map.in_bounds(x, y) || !game.map.tile(x, y).explored {
+        return vec![];
+    }
+
+    let mut descriptions = vec![];
+    if fov.is_in_fov(x, y) {
+        descriptions.extend(
+            objects
+                .iter()
+                .filter(|object| object.occupies(x, y) && !object.hidden())
+                .filter(|object| object.visible_to(&objects[PLAYER]))
+                .map(|object| describe_object(object, game, &objects[PLAYER])),
+        );
+    }
+    descriptions.push(describe_tile(game.map.tile(x, y)));
+    descriptions
+}
+
+fn describe_object(object: &Object, game: &Game, player: &Object) -> Description {
+    let name = item_name(object, game);
+    let detail = if let Some(item) = object.item {
+        describe_item(object, item, game)
+    } else if let Some(trap) = object.trap {
+        trap.kind.description().to_string()
+    } else if object.alive && object.fighter.is_some() {
+        describe_fighter(object, game, player)
+    } else {
+        String::new()
+    };
+    Description { name, detail }
+}
+
+fn describe_item(object: &Object, item: Item, game: &Game) -> String {
+    if !is_identified(item, game) {
+        return "You don't know what it does yet.".to_string();
+    }
+    let mut detail = item.description().to_string();
+    if let Some(equipment) = object.equipment {
+        let mut bonuses: Vec<_> = equipment
+            .damage
+            .map(|(min, max)| format!("{}-{} damage", min, max))
+            .into_iter()
+            .collect();
+        bonuses.extend(
+            [
+                (equipment.power_bonus, "power"),
+                (equipment.defense_bonus, "defense"),
+                (equipment.max_hp_bonus, "max hp"),
+            ]
+            .iter()
+            .filter(|&&(bonus, _)| bonus != 0)
+            .map(|&(bonus, stat)| format!("{:+} {}", bonus, stat)),
+        );
+        if !bonuses.is_empty() {
+            detail = format!("{} {}.", detail, bonuses.join(", "));
+        }
+    }
+    detail
+}
+
+/// how hurt it looks and what it's up to
+fn describe_fighter(object: &Object, game: &Game, player: &Object) -> String {
+    let hp = object.fighter.map_or(0, |f| f.hp);
+    let fraction = hp as f32 / object.max_hp(game).max(1) as f32;
+    let health = if fraction >= 1.0 {
+        "unhurt"
+    } else if fraction > 0.66 {
+        "lightly wounded"
+    } else if fraction > 0.33 {
+        "wounded"
+    } else if fraction > 0.1 {
+        "badly wounded"
+    } else {
+        "nearly dead"
+    };
+    if object.name == "player" {
+        return format!("You are {}.", health);
+    }
+
+    let attitude = if object.status.has(StatusKind::Charmed) {
+        "charmed"
+    } else if object.allegiance() == Faction::Neutral {
+        "not looking for a fight"
+    } else if !player.is_hostile_to(object) {
+        "on your side"
+    } else {
+        match object.ai {
+            Some(Ai::Sleeping { .. }) => "asleep",
+            Some(Ai::Fleeing { .. }) => "fleeing",
+            Some(Ai::Confused { .. }) => "confused",
+            _ => "hostile",
+        }
+    };
+    format!("It is {} and {}.", health, attitude)
+}
+
+fn describe_tile(tile: &Tile) -> Description {
+    let (name, detail) = match (tile.door, tile.terrain) {
+        (Some(Door::Open), _) => ("open door", "It can be closed again."),
+        (Some(Door::Closed), _) => ("closed door", "Walk into it to open it."),
+        (Some(Door::Locked { .. }), _) => {
+            ("locked door", "It needs the right key, or a good kick.")
+        }
+        // secret passages pass for wall until they're found
+        (None, _) if tile.blocked => ("wall", "Solid rock."),
+        (None, Terrain::Floor) => ("floor", ""),
+        (None, Terrain::Water) => ("water", "Wading through it is slow."),
+        (None, Terrain::Lava) => ("lava", "It burns anything that isn't flying."),
+    };
+    Description {
+        name: name.to_string(),
+        detail: detail.to_string(),
+    }
+}
@@ -0,0 +1,1173 @@
+//! The `Game` state, the main menu and the turn loop, saving and loading.
+
+use crate::activity::{explore_step, travel_step, Activity, ActivityKind};
+use crate::ai::{ai_take_turn, Ai};
+use crate::class::{starting_loadout, Class, CLASSES};
+use crate::companion::{arrive_with, befriend, is_companion, swap_places};
+use crate::config::Action;
+use crate::hunger::{tick_hunger, Hunger, MAX_NUTRITION};
+use crate::identify::{identify, random_appearances};
+use crate::items::{
+    carry_capacity, drop_item, fire, open_locked_door, overloaded, pick_up, throw, total_weight,
+    use_item, Item, UseResult,
+};
+use crate::map::{
+    level_palette, level_theme, make_map, spawn_hunter, Door, GameMap, Palette, Terrain, Tile,
+    DEEPEST_LEVEL, STAIRS_DOWN, STAIRS_UP,
+};
+use crate::noise::{hear_noises, Noise};
+use crate::object::{
+    move_by, mut_two, remove_object, Damage, DamageKind, Faction, Fighter, Object, ACTION_COST,
+    PLAYER,
+};
+use crate::pathfinding::DistanceMap;
+use crate::shop::trade;
+use crate::spells::{cast_menu, regen_mana, tick_summon, Spell, Spellcaster};
+use crate::status::{tick_status, StatusKind};
+use crate::traps::{find_traps, notice_traps, spring_traps};
+use crate::ui::{
+    examine, initialise_fov, inventory_menu, menu, menu_background, msgbox, play_screen_effects,
+    queue_hit_effects, remember_monsters, render_all, show_help, show_message_log, Hit, Tcod,
+    CHARACTER_SCREEN_WIDTH, FOV_ALGO, FOV_LIGHT_WALLS, INVENTORY_WIDTH, LEVEL_SCREEN_WIDTH,
+    MAIN_MENU_WIDTH, TORCH_RADIUS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use tcod::colors::*;
+use tcod::console::*;
+use tcod::image;
+use tcod::input::{self, Event, KeyCode};
+
+const SAVE_FILE: &str = "savegame";
+const LAVA_DAMAGE: Damage = Damage {
+    amount: 3,
+    kind: DamageKind::Fire,
+};
+const SEARCH_CHANCE: f32 = 0.4;
+// chance each turn of a monster coming after the player once the Amulet is taken
+const HUNTER_SPAWN_CHANCE: f32 = 0.05;
+// energy a sneaking step costs on top of the move itself
+const SNEAK_MOVE_COST: i32 = ACTION_COST / 2;
+// energy every action costs on top while the player carries too much
+const OVERLOAD_COST: i32 = ACTION_COST / 2;
+// mana the player starts out with, a full pool
+const PLAYER_MANA: i32 = 10;
+// experience needed to level up
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlayerAction {
+    TookTurn,
+    DidntTakeTurn,
+    Exit,
+    // left the dungeon with the Amulet
+    Won,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Messages {
+    messages: Vec<(String, Color)>,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Self { messages: vec![] }
+    }
+
+    pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
+        self.messages.push((message.into(), color))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
+        self.messages.iter()
+    }
+}
+
+/// A floor the player has left, kept as it was for when they come back
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+    pub map: GameMap,
+    // everything on the floor except the player, who goes with them
+    pub objects: Vec<Object>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: GameMap,
+    pub messages: Messages,
+    pub inventory: Vec<Object>,
+    pub dungeon_level: u32,
+    // every other floor visited so far, by dungeon level
+    pub levels: BTreeMap<u32, Level>,
+    // set for good once the Amulet is picked up, which sends monsters after
+    // the player for the rest of the game
+    pub amulet_taken: bool,
+    // picked once when the level is generated so a floor keeps its palette
+    pub palette: Palette,
+    // set when tiles change, so `render_all` redraws the cached map
+    pub dirty: bool,
+    // share of the floor the player has seen, updated with the map cache
+    pub explored: f32,
+    // sneaking players are harder to spot but move slower
+    pub sneaking: bool,
+    // turns until the player goes hungry, below zero once they're starving
+    pub nutrition: i32,
+    pub gold: u32,
+    // turns left of seeing every monster on the level, wherever it is
+    pub detect_monsters: i32,
+    // what each unknown potion and scroll looks like this game
+    pub appearances: Vec<(Item, String)>,
+    // the kinds the player has found out, shown by their real names
+    pub identified: Vec<Item>,
+    // picked when the game started
+    #[serde(default)]
+    pub class: Class,
+    // made since the monsters last got to hear them
+    #[serde(skip)]
+    pub noises: Vec<Noise>,
+    // monsters and items that turned up mid-turn, added to the level's
+    // objects once the turn is over
+    #[serde(skip)]
+    pub spawned: Vec<Object>,
+    // damage dealt since the screen was last drawn
+    #[serde(skip)]
+    pub hits: Vec<Hit>,
+    // steps to the player from everywhere, worked out again every turn
+    #[serde(skip)]
+    pub player_distances: DistanceMap,
+    // what the player is busy doing over several turns, if anything
+    #[serde(skip)]
+    pub activity: Option<Activity>,
+}
+
+impl Game {
+    /// A game starting out on level 1 of `map`
+    pub fn new(map: GameMap) -> Self {
+        Game {
+            map,
+            messages: Messages::new(),
+            inventory: vec![],
+            dungeon_level: 1,
+            levels: BTreeMap::new(),
+            amulet_taken: false,
+            // level 1 starts the first theme, on that theme's own colors
+            palette: level_theme(1).palette,
+            dirty: true,
+            explored: 0.0,
+            sneaking: false,
+            nutrition: MAX_NUTRITION,
+            gold: 0,
+            detect_monsters: 0,
+            appearances: random_appearances(&mut rand::thread_rng()),
+            identified: vec![],
+            noises: vec![],
+            spawned: vec![],
+            player_distances: DistanceMap::default(),
+            activity: None,
+            hits: vec![],
+            class: Class::default(),
+        }
+    }
+
+    pub fn hunger(&self) -> Hunger {
+        Hunger::from_nutrition(self.nutrition)
+    }
+}
+
+/// Title screen: start a new game, pick up the saved one, or quit. Playing
+/// comes back here when the player quits or dies.
+pub fn main_menu(tcod: &mut Tcod) {
+    let background = menu_background(tcod.root.width(), tcod.root.height());
+
+    while !tcod.root.window_closed() {
+        // show the background image, at twice the regular console resolution
+        image::blit_2x(&background, (0, 0), (-1, -1), &mut tcod.root, (0, 0));
+
+        tcod.root.set_default_foreground(LIGHT_YELLOW);
+        tcod.root.print_ex(
+            tcod.root.width() / 2,
+            tcod.root.height() / 2 - 4,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "TOMBS OF THE ANCIENT KINGS",
+        );
+        tcod.root.print_ex(
+            tcod.root.width() / 2,
+            tcod.root.height() - 2,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "By Yours Truly",
+        );
+
+        // show options and wait for the player's choice
+        let choices = &["Play a new game", "Continue last game", "Quit"];
+        let choice = menu("", choices, MAIN_MENU_WIDTH, &mut tcod.root);
+
+        match choice {
+            Some(0) => {
+                // new game, unless the player backs out of picking a class
+                if let Some(class) = choose_class(tcod) {
+                    let (mut game, mut objects) = new_game(class, tcod);
+                    play_game(tcod, &mut game, &mut objects);
+                }
+            }
+            Some(1) => {
+                // load game
+                match load_game(tcod) {
+                    Ok((mut game, mut objects)) => {
+                        initialise_fov(tcod, &game);
+                        play_game(tcod, &mut game, &mut objects);
+                    }
+                    Err(error) => {
+                        msgbox(
+                            &format!("\nNo saved game to load: {}\n", error),
+                            MAIN_MENU_WIDTH,
+                            &mut tcod.root,
+                        );
+                        continue;
+                    }
+                }
+            }
+            Some(2) => {
+                // quit
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ask what class to play, `None` if the player backs out
+fn choose_class(tcod: &mut Tcod) -> Option<Class> {
+    let options: Vec<String> = CLASSES
+        .iter()
+        .map(|class| format!("{}: {}", class.name(), class.description()))
+        .collect();
+    menu(
+        "Choose your class:\n",
+        &options,
+        INVENTORY_WIDTH,
+        &mut tcod.root,
+    )
+    .map(|choice| CLASSES[choice])
+}
+
+/// The player as they start every game, with the stats of their class
+fn new_player(fighter: Fighter) -> Object {
+    let mut player = Object::new(0, 0, '@', "player", WHITE, true);
+    player.alive = true;
+    player.faction = Faction::Player;
+    // the player gets the first move
+    player.energy = ACTION_COST;
+    player.fighter = Some(fighter);
+    player.caster = Some(Spellcaster::new(
+        PLAYER_MANA,
+        vec![Spell::Heal, Spell::Lightning],
+    ));
+    player
+}
+
+/// A fresh level 1 dungeon with a brand new player of the given class
+fn new_game(class: Class, tcod: &mut Tcod) -> (Game, Vec<Object>) {
+    // Set up player, npc and vector of objects (players are objects)
+    let (fighter, inventory) = starting_loadout(class);
+    let mut objects = vec![new_player(fighter)];
+
+    let (map_width, map_height) = tcod.fov.size();
+    let map = make_map(
+        map_width,
+        map_height,
+        1,
+        tcod.map_style,
+        &tcod.vaults,
+        &mut objects,
+        &mut rand::thread_rng(),
+    );
+    let mut game = Game::new(map);
+    game.class = class;
+    // a class knows the tools of its trade
+    for item in inventory.iter().filter_map(|object| object.item) {
+        identify(item, &mut game);
+    }
+    game.inventory = inventory;
+
+    initialise_fov(tcod, &game);
+
+    // a warm welcoming message!
+    game.messages.add(
+        "Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.",
+        RED,
+    );
+    game.messages.add(
+        format!(
+            "Bring the Amulet up from level {} to win, if you can.",
+            DEEPEST_LEVEL
+        ),
+        LIGHT_YELLOW,
+    );
+
+    (game, objects)
+}
+
+fn play_game(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
+    // leftovers from the last game shouldn't play out in this one
+    tcod.screen_effects.clear();
+    game.dirty = true;
+
+    // force FOV "recompute" first time through the game loop
+    let mut previous_player_position = (-1, -1);
+
+    // Game loop
+    while !tcod.root.window_closed() {
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => tcod.mouse = m,
+            Some((_, Event::Key(k))) => tcod.key = k,
+            _ => tcod.key = Default::default(),
+        }
+
+        tcod.con.clear();
+
+        let fov_recompute = previous_player_position != (objects[PLAYER].x, objects[PLAYER].y);
+        render_all(tcod, game, objects, fov_recompute);
+
+        tcod.root.flush();
+
+        // play out any screen effects before waiting on the next key
+        play_screen_effects(tcod, game, objects);
+
+        // level up if needed
+        level_up(tcod, game, objects);
+
+        previous_player_position = objects[PLAYER].pos();
+        let player_action = if game.activity.is_some() {
+            continue_activity(tcod, game, objects)
+        } else {
+            handle_keys(tcod, game, objects)
+        };
+        if player_action == PlayerAction::Exit {
+            save_or_forget(game, objects);
+            break;
+        }
+        if player_action == PlayerAction::Won {
+            victory_screen(tcod, objects);
+            forget_save();
+            break;
+        }
+
+        // monsters turn
+        if objects[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
+            let hp_before = objects[PLAYER].fighter.map_or(0, |f| f.hp);
+            objects[PLAYER].energy -= ACTION_COST;
+            if overloaded(&objects[PLAYER], game) {
+                objects[PLAYER].energy -= OVERLOAD_COST;
+            }
+            notice_traps(game, objects);
+            tick_hunger(game, objects);
+            game.detect_monsters = (game.detect_monsters - 1).max(0);
+            if game.amulet_taken && rand::random::<f32>() < HUNTER_SPAWN_CHANCE {
+                spawn_hunter(
+                    game.dungeon_level,
+                    &game.map,
+                    objects,
+                    |x, y| !tcod.fov.is_in_fov(x, y),
+                    &mut rand::thread_rng(),
+                );
+            }
+            run_until_player_ready(tcod, game, objects);
+            // summons that faded away or were destroyed leave nothing behind
+            let faded: Vec<_> = (0..objects.len())
+                .filter(|&id| objects[id].summon_turns == Some(0))
+                .collect();
+            for id in faded.into_iter().rev() {
+                remove_object(id, objects);
+            }
+            objects.append(&mut game.spawned);
+            queue_hit_effects(tcod, hp_before, &objects[PLAYER], game);
+            // opened doors change what can be seen, the cached map has to be
+            // redrawn for them
+            if game.map.take_opened_doors() {
+                game.dirty = true;
+            }
+            // a player who stayed put gets no FOV recompute, so remember the
+            // monsters in view here; one who moved gets them redone with the
+            // new FOV when the map is redrawn
+            if objects[PLAYER].pos() == previous_player_position {
+                remember_monsters(tcod, game, objects);
+            }
+        }
+    }
+
+    // closing the window mid-game keeps the game for next time too
+    if tcod.root.window_closed() {
+        save_or_forget(game, objects);
+    }
+}
+
+/// Save the game for later, unless the player died, in which case the old
+/// save goes too so the dead can't be continued
+fn save_or_forget(game: &Game, objects: &[Object]) {
+    if !objects[PLAYER].alive {
+        forget_save();
+    } else if let Err(error) = save_game(game, objects) {
+        eprintln!("Could not update the save file {}: {}", SAVE_FILE, error);
+    }
+}
+
+/// delete the save file, the game it holds is over
+fn forget_save() {
+    match fs::remove_file(SAVE_FILE) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("Could not delete the save file {}: {}", SAVE_FILE, error);
+        }
+        _ => (),
+    }
+}
+
+/// The player's stats and load
+fn character_screen(tcod: &mut Tcod, game: &Game, objects: &[Object]) {
+    let player = &objects[PLAYER];
+    let text = format!(
+        "Character information\n\nClass: {}\nLevel: {}\nExperience: {}\nExperience to level up: {}\n\n\
+         Maximum HP: {}\nAttack: {}\nDamage: {}-{}\nDefense: {}\nCarrying: {} / {}",
+        game.class.name(),
+        player.level,
+        player.fighter.map_or(0, |fighter| fighter.xp),
+        LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR,
+        player.max_hp(game),
+        player.power(game),
+        player.damage_range(game).0,
+        player.damage_range(game).1,
+        player.defense(game),
+        total_weight(&game.inventory),
+        carry_capacity(player),
+    );
+    msgbox(&text, CHARACTER_SCREEN_WIDTH, &mut tcod.root);
+}
+
+/// The end of a winning game
+fn victory_screen(tcod: &mut Tcod, objects: &[Object]) {
+    let text = format!(
+        "\nYou climb out of the Tombs of the Ancient Kings into the daylight, the Amulet \
+         in your hand.\n\nYou have won!\n\nCharacter level: {}\nExperience: {}\n",
+        objects[PLAYER].level,
+        objects[PLAYER].fighter.map_or(0, |fighter| fighter.xp),
+    );
+    msgbox(&text, LEVEL_SCREEN_WIDTH, &mut tcod.root);
+}
+
+fn save_game(game: &Game, objects: &[Object]) -> Result<(), Box<dyn Error>> {
+    let save_data = serde_json::to_string(&(game, objects))?;
+    let mut file = File::create(SAVE_FILE)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+/// Read the saved game back. It only fits if it was made with the same
+/// map size as the current window.
+fn load_game(tcod: &Tcod) -> Result<(Game, Vec<Object>), Box<dyn Error>> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(SAVE_FILE)?;
+    file.read_to_string(&mut json_save_state)?;
+    let (game, objects) = serde_json::from_str::<(Game, Vec<Object>)>(&json_save_state)?;
+
+    let (map_width, map_height) = tcod.fov.size();
+    if (game.map.width(), game.map.height()) != (map_width, map_height) {
+        return Err(format!(
+            "it was saved with a {}x{} map, this game is set up for {}x{}",
+            game.map.width(),
+            game.map.height(),
+            map_width,
+            map_height
+        )
+        .into());
+    }
+    Ok((game, objects))
+}
+
+/// Take the stairs to dungeon level `level`. The floor being left stays as
+/// it is, and the one being entered is brought back exactly as the player
+/// left it, or generated if they've never been there.
+fn change_level(level: u32, tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) {
+    let going_down = level > game.dungeon_level;
+    // everything but the player and their companions stays behind
+    let (companions, left_objects): (Vec<_>, Vec<_>) = objects
+        .split_off(PLAYER + 1)
+        .into_iter()
+        .partition(is_companion);
+
+    let map = match game.levels.remove(&level) {
+        Some(stored) => {
+            objects.extend(stored.objects);
+            // come out at the other end of the stairs just taken
+            let arrival = if going_down { STAIRS_UP } else { STAIRS_DOWN };
+            if let Some((x, y)) = objects
+                .iter()
+                .find(|object| object.name == arrival)
+                .map(Object::pos)
+            {
+                objects[PLAYER].set_pos(x, y);
+            }
+            let message = if going_down {
+                "You descend the stairs again."
+            } else {
+                "You climb back up the stairs."
+            };
+            game.messages.add(message, LIGHT_VIOLET);
+            stored.map
+        }
+        None => {
+            game.messages.add(
+                "You take a moment to rest, and recover your strength.",
+                VIOLET,
+            );
+            let heal_hp = objects[PLAYER].max_hp(game) / 2;
+            objects[PLAYER].heal(heal_hp, game);
+            game.messages.add(
+                "After a rare moment of peace, you descend deeper into the heart of the dungeon...",
+                RED,
+            );
+            let theme = level_theme(level);
+            if *theme.levels.start() == level {
+                game.messages
+                    .add(format!("You have reached the {}.", theme.name), LIGHT_AZURE);
+            }
+            make_map(
+                game.map.width(),
+                game.map.height(),
+                level,
+                tcod.map_style,
+                &tcod.vaults,
+                objects,
+                &mut rand::thread_rng(),
+            )
+        }
+    };
+
+    let left_map = mem::replace(&mut game.map, map);
+    game.levels.insert(
+        game.dungeon_level,
+        Level {
+            map: left_map,
+            objects: left_objects,
+        },
+    );
+    game.dungeon_level = level;
+    game.palette = level_palette(level);
+    game.noises.clear();
+    objects[PLAYER].path = None;
+    game.dirty = true;
+    arrive_with(companions, game, objects);
+    initialise_fov(tcod, game);
+
+    // the player may land on the same spot as on the last floor, so the
+    // main loop can't be trusted to notice the FOV needs recomputing
+    let (player_x, player_y) = objects[PLAYER].pos();
+    tcod.fov
+        .compute_fov(player_x, player_y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+
+    // a crash further down shouldn't cost more than the floor being played
+    if going_down && tcod.autosave {
+        game.messages.add("Autosaving...", DARK_GREY);
+        if let Err(error) = save_game(game, objects) {
+            game.messages
+                .add(format!("Could not autosave: {}", error), LIGHT_RED);
+        }
+    }
+}
+
+/// The stairs take a turn's worth of time, so whatever the player is under
+/// wears on them once on the way. False if that kills them before they get
+/// to the other end.
+fn take_the_stairs(game: &mut Game, objects: &mut [Object]) -> bool {
+    tick_status(&mut objects[PLAYER], game);
+    objects[PLAYER].alive
+}
+
+/// true if the player stands on an object called `name`
+fn player_on(name: &str, objects: &[Object]) -> bool {
+    objects
+        .iter()
+        .any(|object| object.pos() == objects[PLAYER].pos() && object.name == name)
+}
+
+/// If the player has enough experience, raise their level and let them pick
+/// a stat to improve
+fn level_up(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    let player = &mut objects[PLAYER];
+    let level_up_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
+    // see if the player's experience is enough to level-up
+    if player.fighter.map_or(0, |f| f.xp) < level_up_xp {
+        return;
+    }
+
+    // it is! level up
+    player.level += 1;
+    game.messages.add(
+        format!(
+            "Your battle skills grow stronger! You reached level {}!",
+            player.level
+        ),
+        YELLOW,
+    );
+
+    let fighter = player.fighter.as_mut().unwrap();
+    let mut choice = None;
+    while choice.is_none() && !tcod.root.window_closed() {
+        // keep asking until a choice is made
+        choice = menu(
+            "Level up! Choose a stat to raise:\n",
+            &[
+                format!("Constitution (+20 HP, from {})", fighter.base_max_hp),
+                format!("Strength (+1 attack, from {})", fighter.base_power),
+                format!("Agility (+1 defense, from {})", fighter.base_defense),
+                format!("Dexterity (+1 accuracy, from {})", fighter.base_accuracy),
+            ],
+            LEVEL_SCREEN_WIDTH,
+            &mut tcod.root,
+        );
+    }
+    fighter.xp -= level_up_xp;
+    // closing the window mid-choice still has to leave a level that adds up
+    match choice.unwrap_or(0) {
+        0 => {
+            fighter.base_max_hp += 20;
+            fighter.hp += 20;
+        }
+        1 => {
+            fighter.base_power += 1;
+        }
+        2 => {
+            fighter.base_defense += 1;
+        }
+        3 => {
+            fighter.base_accuracy += 1;
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> PlayerAction {
+    use PlayerAction::*;
+
+    let key = tcod.key;
+    let player_alive = objects[PLAYER].alive;
+
+    // clicking an explored tile walks the player there
+    if mem::take(&mut tcod.mouse.lbutton_pressed) && player_alive {
+        let clicked = tcod
+            .camera
+            .to_map(tcod.mouse.cx as i32, tcod.mouse.cy as i32)
+            .filter(|&(x, y)| game.map.in_bounds(x, y) && game.map.tile(x, y).explored)
+            .filter(|&target| target != objects[PLAYER].pos());
+        if let Some(target) = clicked {
+            let travel = ActivityKind::Travel { target };
+            game.activity = Some(Activity::start(travel, &tcod.fov, objects));
+        }
+        return DidntTakeTurn;
+    }
+
+    // toggle fullscreen, Alt+Enter whatever the keymap says
+    if key.code == KeyCode::Enter && key.alt {
+        let fullscreen = tcod.root.is_fullscreen();
+        tcod.root.set_fullscreen(!fullscreen);
+        return DidntTakeTurn;
+    }
+
+    let action = tcod.keymap.action(key);
+    // movement keys
+    if let (Some((dx, dy)), true) = (action.and_then(Action::step), player_alive) {
+        player_move_or_attack(dx, dy, tcod, game, objects);
+        return TookTurn;
+    }
+
+    match (action, player_alive) {
+        (Some(Action::Search), true) => {
+            search(tcod, game, objects);
+            TookTurn
+        }
+        (Some(Action::CloseDoors), true) => {
+            if close_doors(game, objects) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::CastSpell), true) => {
+            if cast_menu(tcod, game, objects) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::Sneak), true) => {
+            game.sneaking = !game.sneaking;
+            let message = if game.sneaking {
+                "You start sneaking."
+            } else {
+                "You stop sneaking."
+            };
+            game.messages.add(message, LIGHT_GREY);
+            DidntTakeTurn
+        }
+        (Some(Action::Wait), true) => {
+            // wait a turn
+            TookTurn
+        }
+        (Some(Action::Rest), true) => {
+            let player = &objects[PLAYER];
+            if player.fighter.map_or(0, |f| f.hp) < player.max_hp(game) {
+                game.activity = Some(Activity::start(ActivityKind::Rest, &tcod.fov, objects));
+            } else {
+                game.messages.add("You don't need to rest.", LIGHT_GREY);
+            }
+            DidntTakeTurn
+        }
+        (Some(Action::Explore), true) => {
+            game.activity = Some(Activity::start(ActivityKind::Explore, &tcod.fov, objects));
+            DidntTakeTurn
+        }
+        (Some(Action::Pickup), true) => {
+            // pick up what's lying under the player
+            if pick_up(tcod, game, objects) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::Descend), true) => {
+            // go down the stairs, if the player is on them
+            if player_on(STAIRS_DOWN, objects) && take_the_stairs(game, objects) {
+                change_level(game.dungeon_level + 1, tcod, game, objects);
+            }
+            DidntTakeTurn
+        }
+        (Some(Action::Ascend), true) => {
+            if !player_on(STAIRS_UP, objects) {
+                DidntTakeTurn
+            } else if game.dungeon_level > 1 {
+                if take_the_stairs(game, objects) {
+                    change_level(game.dungeon_level - 1, tcod, game, objects);
+                }
+                DidntTakeTurn
+            } else if game
+                .inventory
+                .iter()
+                .any(|item| item.item == Some(Item::Amulet))
+            {
+                Won
+            } else {
+                game.messages.add(
+                    "You can't leave without the Amulet of the Ancient Kings, it lies \
+                     at the bottom of the dungeon.",
+                    LIGHT_GREY,
+                );
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::Inventory), true) => {
+            let inventory_index = inventory_menu(
+                game,
+                "Press the key next to an item to use it, or any other to cancel.\n",
+                &mut tcod.root,
+            );
+            match inventory_index.map(|index| use_item(index, tcod, game, objects)) {
+                Some(UseResult::UsedUp) | Some(UseResult::UsedAndKept) => TookTurn,
+                _ => DidntTakeTurn,
+            }
+        }
+        (Some(Action::Eat), true) => {
+            let food_id = game
+                .inventory
+                .iter()
+                .position(|item| item.item == Some(Item::Food));
+            match food_id.map(|food_id| use_item(food_id, tcod, game, objects)) {
+                Some(UseResult::UsedUp) => TookTurn,
+                Some(_) => DidntTakeTurn,
+                None => {
+                    game.messages.add("You have nothing to eat.", LIGHT_GREY);
+                    DidntTakeTurn
+                }
+            }
+        }
+        (Some(Action::Fire), true) => {
+            if fire(tcod, game, objects) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::Throw), true) => {
+            if throw(tcod, game, objects) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        (Some(Action::Drop), true) => {
+            let inventory_index = inventory_menu(
+                game,
+                "Press the key next to an item to drop it, or any other to cancel.\n",
+                &mut tcod.root,
+            );
+            match inventory_index {
+                Some(inventory_index) => {
+                    drop_item(inventory_index, game, objects);
+                    TookTurn
+                }
+                None => DidntTakeTurn,
+            }
+        }
+
+        // toggle the coordinate debug grid
+        (Some(Action::DebugGrid), _) => {
+            tcod.show_debug_grid = !tcod.show_debug_grid;
+            DidntTakeTurn
+        }
+        // toggle the minimap
+        (Some(Action::Minimap), _) => {
+            tcod.show_minimap = !tcod.show_minimap;
+            DidntTakeTurn
+        }
+        // toggle the floating damage numbers
+        (Some(Action::DamageNumbers), _) => {
+            tcod.show_damage_numbers = !tcod.show_damage_numbers;
+            DidntTakeTurn
+        }
+        // toggle the monster health tint
+        (Some(Action::MonsterHealth), _) => {
+            tcod.show_monster_health = !tcod.show_monster_health;
+            DidntTakeTurn
+        }
+        // show the full message history
+        (Some(Action::MessageLog), _) => {
+            show_message_log(tcod, &game.messages);
+            DidntTakeTurn
+        }
+        (Some(Action::Examine), _) => {
+            examine(tcod, game, objects);
+            DidntTakeTurn
+        }
+        (Some(Action::Character), _) => {
+            character_screen(tcod, game, objects);
+            DidntTakeTurn
+        }
+        (Some(Action::Help), _) => {
+            show_help(tcod);
+            DidntTakeTurn
+        }
+        // exit game
+        (Some(Action::Quit), _) => Exit,
+        _ => DidntTakeTurn,
+    }
+}
+
+/// Take the next step of whatever the player is busy with, unless a key
+/// press or something coming up stops them
+fn continue_activity(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> PlayerAction {
+    // put back at the end if the player carries on
+    let Some(mut activity) = game.activity.take() else {
+        return PlayerAction::DidntTakeTurn;
+    };
+    // any key or a right click stops the player, like clicking does in menus
+    let cancelled = tcod.key.pressed || tcod.mouse.rbutton_pressed;
+    tcod.mouse.rbutton_pressed = false;
+    if cancelled || !objects[PLAYER].alive {
+        let message = format!("You stop {}.", activity.kind.name());
+        game.messages.add(message, LIGHT_GREY);
+        return PlayerAction::DidntTakeTurn;
+    }
+    if activity.done(game, objects) {
+        if activity.kind == ActivityKind::Rest {
+            game.messages.add("You feel rested.", LIGHT_GREY);
+        }
+        return PlayerAction::DidntTakeTurn;
+    }
+    if let Some(reason) = activity.interruption(&tcod.fov, objects) {
+        game.messages.add(reason, LIGHT_GREY);
+        return PlayerAction::DidntTakeTurn;
+    }
+
+    let step = match activity.kind {
+        ActivityKind::Explore => explore_step(&game.map, objects),
+        ActivityKind::Travel { target } => travel_step(target, &game.map, objects),
+        ActivityKind::Rest => Ok((0, 0)),
+    };
+    match step {
+        // standing still
+        Ok((0, 0)) => (),
+        Ok((dx, dy)) => player_move_or_attack(dx, dy, tcod, game, objects),
+        Err(reason) => {
+            game.messages.add(reason, LIGHT_GREY);
+            return PlayerAction::DidntTakeTurn;
+        }
+    }
+    game.activity = Some(activity);
+    PlayerAction::TookTurn
+}
+
+fn player_move_or_attack(
+    dx: i32,
+    dy: i32,
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+) {
+    let x = objects[PLAYER].x + dx;
+    let y = objects[PLAYER].y + dy;
+
+    // no reaching around corners either
+    if game
+        .map
+        .cuts_corner(objects[PLAYER].x, objects[PLAYER].y, dx, dy)
+    {
+        return;
+    }
+
+    // locked doors need their key, or a good kick
+    if let Some(Door::Locked { key }) = game.map.tile(x, y).door {
+        open_locked_door(x, y, key, game, objects);
+        return;
+    }
+
+    // with a pickaxe in hand walls are only in the way for a turn
+    let wall = game.map.tile(x, y).blocked && game.map.tile(x, y).door.is_none();
+    if wall && wielding(Item::Pickaxe, game) {
+        dig(x, y, game, tcod);
+        return;
+    }
+
+    // walking into a shopkeeper starts a trade
+    let shopkeeper_id = objects
+        .iter()
+        .position(|object| object.shop.is_some() && object.occupies(x, y));
+    if let Some(shopkeeper_id) = shopkeeper_id {
+        trade(shopkeeper_id, tcod, game, objects);
+        return;
+    }
+
+    // try to find an attackable object in the forecasted position
+    // js equivalent array.find()
+    let target_id = objects
+        .iter()
+        .position(|object| object.fighter.is_some() && object.occupies(x, y));
+
+    // attack if target found, move otherwise; allies get swapped with and
+    // strays fed instead
+    match target_id {
+        Some(target_id) if objects[target_id].allegiance() == Faction::Neutral => {
+            befriend(target_id, game, objects);
+        }
+        Some(target_id) if !objects[PLAYER].is_hostile_to(&objects[target_id]) => {
+            if swap_places(target_id, objects) {
+                spring_traps(PLAYER, tcod, game, objects);
+            }
+        }
+        Some(target_id) => {
+            // monsters that haven't noticed the player yet are easy prey
+            let unaware = matches!(objects[target_id].ai, Some(Ai::Sleeping { .. }));
+            let (player, target) = mut_two(PLAYER, target_id, objects);
+            if unaware {
+                player.backstab(target, &mut rand::thread_rng(), game);
+            } else {
+                player.attack(target, &mut rand::thread_rng(), game);
+            }
+        }
+        None => {
+            let (x, y) = objects[PLAYER].pos();
+            if move_by(PLAYER, dx, dy, &mut game.map, objects) && game.sneaking {
+                objects[PLAYER].energy -= SNEAK_MOVE_COST;
+            }
+            // opening a door doesn't move the player onto anything
+            if objects[PLAYER].pos() != (x, y) {
+                spring_traps(PLAYER, tcod, game, objects);
+            }
+        }
+    }
+}
+
+/// Hand out energy tick by tick until the player can act again. Every
+/// monster acts once for each ACTION_COST it has saved up, so fast monsters
+/// can get several turns in between the player's and slow ones none.
+fn run_until_player_ready(tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+    // the player doesn't move until their next turn, so one map does for
+    // every monster in between
+    game.player_distances = DistanceMap::new(&game.map, objects[PLAYER].pos());
+    // a dead player never gets another turn
+    while objects[PLAYER].alive && objects[PLAYER].energy < ACTION_COST {
+        hear_noises(tcod, game, objects);
+        for object in objects.iter_mut().filter(|object| object.alive) {
+            // stunned objects lose their turns until it wears off
+            if !object.status.has(StatusKind::Stun) {
+                object.energy += object.current_speed();
+            }
+            burn_in_lava(object, game);
+            tick_status(object, game);
+            regen_mana(object);
+            tick_summon(object, game);
+        }
+
+        for id in 0..objects.len() {
+            while objects[id].ai.is_some() && objects[id].energy >= ACTION_COST {
+                let position = objects[id].pos();
+                ai_take_turn(id, tcod, game, objects);
+                if objects[id].pos() != position {
+                    spring_traps(id, tcod, game, objects);
+                }
+                objects[id].energy -= ACTION_COST;
+            }
+        }
+    }
+}
+
+/// one tick of lava damage, if the object is standing in it
+fn burn_in_lava(object: &mut Object, game: &mut Game) {
+    let in_lava = object
+        .tiles()
+        .any(|(x, y)| game.map.tile(x, y).terrain == Terrain::Lava);
+    if in_lava && !object.flying && object.fighter.is_some() {
+        game.messages
+            .add(format!("The {} burns in the lava!", object.name), ORANGE);
+        object.take_damage(LAVA_DAMAGE, game);
+    }
+}
+
+/// Close the open doors around the player, unless something is standing in
+/// the doorway. Returns false if there was nothing to close.
+fn close_doors(game: &mut Game, objects: &[Object]) -> bool {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let mut closed = false;
+    let mut jammed = false;
+
+    for x in (player_x - 1)..=(player_x + 1) {
+        for y in (player_y - 1)..=(player_y + 1) {
+            if !game.map.in_bounds(x, y) || game.map.tile(x, y).door != Some(Door::Open) {
+                continue;
+            }
+            if objects.iter().any(|object| object.occupies(x, y)) {
+                jammed = true;
+            } else {
+                game.map.tile_mut(x, y).close_door();
+                game.dirty = true;
+                closed = true;
+            }
+        }
+    }
+
+    if closed {
+        game.messages.add("You close the door.", LIGHT_GREY);
+    } else if jammed {
+        game.messages
+            .add("Something is in the way of the door.", LIGHT_GREY);
+    } else {
+        game.messages
+            .add("There is no open door next to you.", LIGHT_GREY);
+    }
+    closed
+}
+
+/// true if the player has `item` equipped
+fn wielding(item: Item, game: &Game) -> bool {
+    game.inventory.iter().any(|object| {
+        object.item == Some(item) && object.equipment.is_some_and(|equipment| equipment.equipped)
+    })
+}
+
+/// Dig out the wall at x, y. The outer edge of the map is left alone, or the
+/// player could dig their way off it.
+fn dig(x: i32, y: i32, game: &mut Game, tcod: &mut Tcod) {
+    let on_edge = x <= 0 || y <= 0 || x >= game.map.width() - 1 || y >= game.map.height() - 1;
+    if on_edge {
+        game.messages
+            .add("The rock here is too hard to dig through.", LIGHT_GREY);
+        return;
+    }
+
+    let mut tile = Tile::empty();
+    tile.explored = true;
+    *game.map.tile_mut(x, y) = tile;
+    tcod.fov.set(x, y, true, true);
+    // `render_all` redoes the FOV for a dirty map
+    game.dirty = true;
+    game.messages.add("You dig through the wall.", LIGHT_GREY);
+}
+
+/// Look for secret passages and traps on the tiles around the player. Each
+/// hidden tile has a chance of being found, which turns it into floor for
+/// good; found traps stay on the map.
+fn search(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let mut found = false;
+
+    for x in (player_x - 1)..=(player_x + 1) {
+        for y in (player_y - 1)..=(player_y + 1) {
+            if !game.map.in_bounds(x, y) || !game.map.tile(x, y).secret {
+                continue;
+            }
+
+            if rand::random::<f32>() < SEARCH_CHANCE {
+                *game.map.tile_mut(x, y) = Tile::empty();
+                tcod.fov.set(x, y, true, true);
+                game.dirty = true;
+                found = true;
+            }
+        }
+    }
+
+    let found_traps = find_traps(SEARCH_CHANCE, game, objects);
+    if found {
+        game.messages.add("You found a secret passage!", LIGHT_BLUE);
+        // the map changed under the player, so the FOV has to be redone
+        tcod.fov
+            .compute_fov(player_x, player_y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+    } else if found_traps == 0 {
+        game.messages
+            .add("You search around but find nothing.", LIGHT_GREY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    fn player_on_a_floor() -> (Game, Vec<Object>) {
+        (
+            Game::new(GameMap::new(10, 10, Tile::empty())),
+            vec![new_player(starting_loadout(Class::Warrior).0)],
+        )
+    }
+
+    fn poison(object: &Object) -> Option<i32> {
+        object
+            .status
+            .iter()
+            .find(|effect| effect.kind == StatusKind::Poison)
+            .map(|effect| effect.turns)
+    }
+
+    #[test]
+    fn poison_keeps_ticking_on_the_stairs() {
+        let (mut game, mut objects) = player_on_a_floor();
+        objects[PLAYER].status.add(StatusKind::Poison, 5, 2);
+
+        assert!(take_the_stairs(&mut game, &mut objects));
+        assert_eq!(poison(&objects[PLAYER]), Some(4));
+        assert_eq!(objects[PLAYER].fighter.unwrap().hp, 28);
+    }
+
+    #[test]
+    fn poison_can_kill_on_the_stairs() {
+        let (mut game, mut objects) = player_on_a_floor();
+        objects[PLAYER].fighter.as_mut().unwrap().hp = 2;
+        objects[PLAYER].status.add(StatusKind::Poison, 5, 2);
+
+        assert!(!take_the_stairs(&mut game, &mut objects));
+        assert!(!objects[PLAYER].alive);
+        assert_eq!(objects[PLAYER].char, '%');
+    }
+}
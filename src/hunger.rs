@@ -0,0 +1,110 @@
+//! The player's hunger: a clock that runs down a little every turn, and the
+//! food that winds it back up.
+
+use crate::game::Game;
+use crate::object::{Damage, DamageKind, Object, PLAYER};
+use std::cmp;
+use tcod::colors::*;
+
+// turns of food a new player starts with, also the most they can eat up to
+pub const MAX_NUTRITION: i32 = 1500;
+const HUNGRY_BELOW: i32 = 300;
+// turns of food in one ration
+const FOOD_NUTRITION: i32 = 800;
+// a starving player loses this much hp every few turns
+const STARVE_DAMAGE: Damage = Damage {
+    amount: 1,
+    kind: DamageKind::Physical,
+};
+const STARVE_DAMAGE_EVERY: i32 = 10;
+// a player who isn't going hungry slowly heals, a hp every few turns
+const HEAL_EVERY: i32 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hunger {
+    Satiated,
+    // weaker in a fight
+    Hungry,
+    // weaker still, and slowly dying
+    Starving,
+}
+
+impl Hunger {
+    /// how hungry the player is with `nutrition` turns of food left
+    pub fn from_nutrition(nutrition: i32) -> Self {
+        if nutrition <= 0 {
+            Hunger::Starving
+        } else if nutrition < HUNGRY_BELOW {
+            Hunger::Hungry
+        } else {
+            Hunger::Satiated
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hunger::Satiated => "Satiated",
+            Hunger::Hungry => "Hungry",
+            Hunger::Starving => "Starving",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Hunger::Satiated => LIGHT_GREY,
+            Hunger::Hungry => YELLOW,
+            Hunger::Starving => LIGHT_RED,
+        }
+    }
+
+    pub fn power_penalty(self) -> i32 {
+        match self {
+            Hunger::Satiated => 0,
+            Hunger::Hungry => 1,
+            Hunger::Starving => 2,
+        }
+    }
+
+    pub fn defense_penalty(self) -> i32 {
+        match self {
+            Hunger::Satiated | Hunger::Hungry => 0,
+            Hunger::Starving => 1,
+        }
+    }
+}
+
+/// One player turn's worth of hunger. Past the point of starving the count
+/// keeps going below zero, which times the starvation damage. The count
+/// times the slow healing of a well fed player too.
+pub fn tick_hunger(game: &mut Game, objects: &mut [Object]) {
+    let before = game.hunger();
+    game.nutrition -= 1;
+    let after = game.hunger();
+
+    if after != before {
+        match after {
+            Hunger::Hungry => game.messages.add("You are getting hungry.", YELLOW),
+            Hunger::Starving => game.messages.add("You are starving!", LIGHT_RED),
+            Hunger::Satiated => (),
+        }
+    }
+    if after == Hunger::Starving && game.nutrition % STARVE_DAMAGE_EVERY == 0 {
+        game.messages.add("You are weak with hunger.", LIGHT_RED);
+        objects[PLAYER].take_damage(STARVE_DAMAGE, game);
+    }
+    if after == Hunger::Satiated && game.nutrition % HEAL_EVERY == 0 {
+        objects[PLAYER].heal(1, game);
+    }
+}
+
+/// Eat a ration. Returns false, leaving the food uneaten, if the player is
+/// too full for all of it.
+pub fn eat(game: &mut Game) -> bool {
+    if game.nutrition > MAX_NUTRITION - FOOD_NUTRITION {
+        game.messages.add("You are too full to eat.", RED);
+        return false;
+    }
+    game.nutrition = cmp::max(game.nutrition, 0) + FOOD_NUTRITION;
+    game.messages.add("That food really hit the spot!", GREEN);
+    true
+}
@@ -0,0 +1,107 @@
+//! Identification: potions and scrolls look different every game, and go by
+//! their looks until the player finds out what they do, by using one or by
+//! reading a scroll of identify.
+
+use crate::game::Game;
+use crate::items::Item;
+use crate::map::new_item;
+use crate::object::Object;
+use rand::Rng;
+use tcod::colors::*;
+
+// the consumables that start out unknown
+const POTIONS: &[Item] = &[
+    Item::Heal,
+    Item::LiquidFire,
+    Item::Speed,
+    Item::Invisibility,
+    Item::SeeInvisible,
+];
+const SCROLLS: &[Item] = &[
+    Item::Lightning,
+    Item::Confuse,
+    Item::Fireball,
+    Item::Identify,
+    Item::Charm,
+    Item::Summon,
+    Item::Slow,
+    Item::Blink,
+    Item::Teleport,
+    Item::MagicMapping,
+    Item::DetectMonsters,
+];
+// what they can look like, dealt out at random when a game starts
+const POTION_LOOKS: &[&str] = &[
+    "bubbling blue",
+    "murky green",
+    "fizzing red",
+    "smoky grey",
+    "glowing orange",
+    "oily black",
+];
+const SCROLL_LABELS: &[&str] = &[
+    "ZELGO MER",
+    "FOOBIE BLETCH",
+    "XIXAXA XOXAXA",
+    "VELOX NEB",
+    "KERNOD WEL",
+    "ELBIB YLOH",
+    "PRATYAVAYAH",
+    "DAIYEN FOOELS",
+    "LEP GEX VEN ZEA",
+    "VERR YED HORRE",
+    "ANDOVA BEGARIN",
+    "KIRJE",
+];
+
+/// Deal every unknown potion and scroll a different look for this game
+pub fn random_appearances<R: Rng>(rng: &mut R) -> Vec<(Item, String)> {
+    let mut looks = POTION_LOOKS.to_vec();
+    rng.shuffle(&mut looks);
+    let mut labels = SCROLL_LABELS.to_vec();
+    rng.shuffle(&mut labels);
+
+    let potions = POTIONS
+        .iter()
+        .zip(looks)
+        .map(|(&item, look)| (item, format!("{} potion", look)));
+    let scrolls = SCROLLS
+        .iter()
+        .zip(labels)
+        .map(|(&item, label)| (item, format!("scroll labelled {}", label)));
+    potions.chain(scrolls).collect()
+}
+
+/// whether the player knows what `item` does, anything without a random
+/// look always counts as known
+pub fn is_identified(item: Item, game: &Game) -> bool {
+    game.identified.contains(&item) || !game.appearances.iter().any(|&(unknown, _)| unknown == item)
+}
+
+/// The name the player knows an object by: its looks if it's an item they
+/// haven't identified yet, its real name otherwise
+pub fn item_name(object: &Object, game: &Game) -> String {
+    match object.item {
+        Some(item) if !is_identified(item, game) => game
+            .appearances
+            .iter()
+            .find(|&&(unknown, _)| unknown == item)
+            .map(|(_, look)| look.clone())
+            .unwrap_or_else(|| object.name.clone()),
+        _ => object.name.clone(),
+    }
+}
+
+/// Learn what `item` is, for every one of it the player will ever see.
+/// Returns false if it was already known.
+pub fn identify(item: Item, game: &mut Game) -> bool {
+    if is_identified(item, game) {
+        return false;
+    }
+    let object = new_item(item, 0, 0);
+    let look = item_name(&object, game);
+    game.identified.push(item);
+    game.messages
+        .add(format!("The {} was a {}!", look, object.name), LIGHT_CYAN);
+    true
+}
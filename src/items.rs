@@ -0,0 +1,985 @@
+//! Items: picking up, dropping and what each one does when used.
+
+use crate::ai::has_line_of_fire;
+use crate::game::Game;
+use crate::hunger::eat;
+use crate::identify::{identify, is_identified, item_name};
+use crate::map::new_item;
+use crate::noise::Noise;
+use crate::object::{mut_two, remove_object, Damage, DamageKind, Object, Slot, PLAYER};
+use crate::spells::{self, Spell};
+use crate::status::StatusKind;
+use crate::traps::place_trap;
+use crate::ui::{
+    animate_projectile, inventory_menu, menu, projectile_glyph, target_tile, Tcod, INVENTORY_WIDTH,
+    MAX_MENU_OPTIONS,
+};
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::line::Line;
+
+// a shattered liquid fire potion, and how long it leaves things burning
+const LIQUID_FIRE_DAMAGE: Damage = Damage {
+    amount: 6,
+    kind: DamageKind::Fire,
+};
+const LIQUID_FIRE_BURN_TURNS: i32 = 4;
+const LIQUID_FIRE_BURN_DAMAGE: i32 = 2;
+const THROW_RANGE: f32 = 6.0;
+// thrown potions splash the fighters next to where they break
+const SPLASH_RADIUS: f32 = 1.5;
+// one menu letter per item
+const MAX_INVENTORY: usize = MAX_MENU_OPTIONS;
+// bashing at a locked door: the chance it gives, and of getting hurt trying
+const BASH_CHANCE: f32 = 0.3;
+const BASH_HURT_CHANCE: f32 = 0.3;
+const BASH_DAMAGE: Damage = Damage {
+    amount: 2,
+    kind: DamageKind::Physical,
+};
+const BASH_LOUDNESS: u32 = 10;
+// how much the player can carry before they slow down, and how much more
+// every point of base power lets them carry
+const BASE_CARRY_CAPACITY: u32 = 20;
+const CARRY_PER_POWER: u32 = 4;
+
+/// Name and color of every kind of key, a level has at most one of each.
+/// Locked doors are drawn in the color of the key that opens them.
+pub const KEY_KINDS: &[(&str, Color)] = &[
+    ("brass key", GOLD),
+    ("iron key", LIGHT_GREY),
+    ("bone key", LIGHTEST_AMBER),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    // sets whoever drinks it alight, better thrown
+    LiquidFire,
+    Speed,
+    Invisibility,
+    SeeInvisible,
+    Lightning,
+    Confuse,
+    Fireball,
+    // tells the player what one of their unknown items is
+    Identify,
+    Charm,
+    Summon,
+    Slow,
+    // a short hop to a tile of the player's choosing
+    Blink,
+    // off to anywhere the player has been before
+    Teleport,
+    MagicMapping,
+    DetectMonsters,
+    Sword,
+    Shield,
+    // digs through walls when walked into, see `dig`
+    Pickaxe,
+    // a spike trap to set for the monsters, see `place_trap`
+    PlaceTrap,
+    // ranged weapons, fired with `f`, see `Item::launcher`
+    Bow,
+    Sling,
+    // a stack of what they shoot, one slot however many there are
+    Ammo { kind: AmmoKind, count: u32 },
+    // opens the locked door of the same kind, an index into KEY_KINDS
+    Key { kind: usize },
+    // what the player came for, lying at the bottom of the dungeon
+    Amulet,
+    Food,
+    // a pile of coins, counted in `Game::gold` instead of taking up a slot
+    Gold { amount: u32 },
+}
+
+impl Item {
+    /// What a shop sells it for, `None` for things that aren't for sale
+    pub fn price(self) -> Option<u32> {
+        match self {
+            Item::Heal => Some(20),
+            Item::LiquidFire => Some(30),
+            Item::Speed => Some(40),
+            Item::Invisibility => Some(50),
+            Item::SeeInvisible => Some(30),
+            Item::Lightning => Some(40),
+            Item::Confuse => Some(30),
+            Item::Fireball => Some(50),
+            Item::Identify => Some(25),
+            Item::Charm => Some(40),
+            Item::Summon => Some(60),
+            Item::Slow => Some(30),
+            Item::Blink => Some(35),
+            Item::Teleport => Some(45),
+            Item::MagicMapping => Some(50),
+            Item::DetectMonsters => Some(30),
+            Item::Sword => Some(80),
+            Item::Shield => Some(60),
+            Item::Pickaxe => Some(50),
+            Item::PlaceTrap => Some(35),
+            Item::Food => Some(10),
+            Item::Bow => Some(60),
+            Item::Sling => Some(25),
+            Item::Ammo { kind, count } => Some(kind.price() * count),
+            Item::Key { .. } | Item::Amulet | Item::Gold { .. } => None,
+        }
+    }
+
+    /// how heavy it is to carry, see `carry_capacity`
+    pub fn weight(self) -> u32 {
+        match self {
+            Item::Sword => 6,
+            Item::Shield => 8,
+            Item::Pickaxe => 5,
+            Item::Bow => 4,
+            Item::Food | Item::Amulet | Item::PlaceTrap => 2,
+            Item::Ammo { kind, count } => count.div_ceil(kind.per_weight()),
+            Item::Gold { .. } => 0,
+            _ => 1,
+        }
+    }
+
+    /// what a ranged weapon shoots, `None` for everything else
+    pub fn launcher(self) -> Option<Launcher> {
+        match self {
+            Item::Bow => Some(Launcher {
+                ammo: AmmoKind::Arrow,
+                range: 8,
+                power: 5,
+            }),
+            Item::Sling => Some(Launcher {
+                ammo: AmmoKind::Stone,
+                range: 5,
+                power: 3,
+            }),
+            _ => None,
+        }
+    }
+
+    /// the spell reading or drinking it casts, if any
+    pub fn spell(self) -> Option<Spell> {
+        match self {
+            Item::Heal => Some(Spell::Heal),
+            Item::Lightning => Some(Spell::Lightning),
+            Item::Confuse => Some(Spell::Confuse),
+            Item::Fireball => Some(Spell::Fireball),
+            Item::Charm => Some(Spell::Charm),
+            Item::Summon => Some(Spell::Summon),
+            Item::Speed => Some(Spell::Haste),
+            Item::Invisibility => Some(Spell::Invisibility),
+            Item::SeeInvisible => Some(Spell::SeeInvisible),
+            Item::Slow => Some(Spell::Slow),
+            Item::Blink => Some(Spell::Blink),
+            Item::Teleport => Some(Spell::Teleport),
+            Item::MagicMapping => Some(Spell::MagicMapping),
+            Item::DetectMonsters => Some(Spell::DetectMonsters),
+            _ => None,
+        }
+    }
+
+    /// a line about what it does, for examining it
+    pub fn description(self) -> &'static str {
+        match self {
+            Item::Heal => "Heals some wounds, and keeps on healing for a while.",
+            Item::LiquidFire => "Sets whoever drinks it alight. Better thrown.",
+            Item::Speed => "Makes whoever drinks it faster for a while.",
+            Item::Invisibility => "Hides whoever drinks it from most eyes for a while.",
+            Item::SeeInvisible => "Lets whoever drinks it see the unseen for a while.",
+            Item::Lightning => "Strikes the closest enemy with lightning.",
+            Item::Confuse => "Sends an enemy of your choosing stumbling about.",
+            Item::Fireball => "Burns everything around the spot it's aimed at.",
+            Item::Identify => "Tells you what one of your unknown items is.",
+            Item::Charm => "Turns an enemy to your side for a while.",
+            Item::Summon => "Calls up a spirit wolf to fight at your side.",
+            Item::Slow => "Slows an enemy of your choosing down.",
+            Item::Blink => "A short hop to a tile of your choosing.",
+            Item::Teleport => "Whisks you off to somewhere you've been before.",
+            Item::MagicMapping => "Shows you the whole level.",
+            Item::DetectMonsters => "Shows you every monster on the level for a while.",
+            Item::Sword => "A blade for your right hand.",
+            Item::Shield => "Something to hide behind, held in your left hand.",
+            Item::Pickaxe => "Digs through the walls you walk into, held in your right hand.",
+            Item::PlaceTrap => "A spike trap to set at your feet, only monsters set it off.",
+            Item::Bow => "Shoots arrows, slung on your back until it's fired.",
+            Item::Sling => "Shoots stones, slung on your back until it's fired.",
+            Item::Ammo { .. } => "Something to shoot with a bow or sling.",
+            Item::Key { .. } => "Opens the locked door of the same kind.",
+            Item::Amulet => "What you came for. Now get it out of the dungeon.",
+            Item::Food => "Keeps hunger at bay for a good while.",
+            Item::Gold { .. } => "Money to spend in the shops.",
+        }
+    }
+
+    /// how hard it hits when thrown, `None` for things that only bounce off
+    pub fn thrown_power(self) -> Option<i32> {
+        match self {
+            Item::Sword => Some(5),
+            Item::Ammo { .. } => Some(2),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AmmoKind {
+    Arrow,
+    Stone,
+}
+
+impl AmmoKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            AmmoKind::Arrow => "arrow",
+            AmmoKind::Stone => "sling stone",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            AmmoKind::Arrow => LIGHT_SEPIA,
+            AmmoKind::Stone => LIGHT_GREY,
+        }
+    }
+
+    // gold apiece
+    fn price(self) -> u32 {
+        match self {
+            AmmoKind::Arrow => 2,
+            AmmoKind::Stone => 1,
+        }
+    }
+
+    // how many of them weigh as much as one of anything else
+    fn per_weight(self) -> u32 {
+        match self {
+            AmmoKind::Arrow => 10,
+            AmmoKind::Stone => 5,
+        }
+    }
+}
+
+/// How far and how hard a ranged weapon shoots, and with what
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Launcher {
+    pub ammo: AmmoKind,
+    pub range: i32,
+    // used in place of the shooter's power
+    pub power: i32,
+}
+
+/// "12 arrows", "1 sling stone"
+pub fn ammo_name(kind: AmmoKind, count: u32) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{}", count, kind.name(), plural)
+}
+
+/// set how many are left in an ammo stack, and its name to match
+fn set_ammo_count(stack: &mut Object, count: u32) {
+    if let Some(Item::Ammo { kind, .. }) = stack.item {
+        stack.item = Some(Item::Ammo { kind, count });
+        stack.name = ammo_name(kind, count);
+    }
+}
+
+/// inventory index of the stack of `kind` the player carries, if any
+fn find_ammo(kind: AmmoKind, inventory: &[Object]) -> Option<usize> {
+    inventory.iter().position(
+        |object| matches!(object.item, Some(Item::Ammo { kind: stacked, .. }) if stacked == kind),
+    )
+}
+
+pub enum UseResult {
+    UsedUp,
+    // equipment isn't used up by putting it on
+    UsedAndKept,
+    Cancelled,
+}
+
+/// Move an item from the map into the player's inventory. Returns false
+/// if there was no room for it.
+pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) -> bool {
+    if let Some(Item::Gold { amount }) = objects[object_id].item {
+        remove_object(object_id, objects);
+        game.gold += amount;
+        game.messages
+            .add(format!("You pick up {} gold.", amount), GOLD);
+        return true;
+    }
+    // ammo goes onto the stack the player already has
+    if let Some(Item::Ammo { kind, count }) = objects[object_id].item {
+        if let Some(stack_id) = find_ammo(kind, &game.inventory) {
+            let picked_up = remove_object(object_id, objects);
+            let stack = &mut game.inventory[stack_id];
+            if let Some(Item::Ammo { count: carried, .. }) = stack.item {
+                set_ammo_count(stack, carried + count);
+            }
+            game.messages
+                .add(format!("You picked up {}!", picked_up.name), GREEN);
+            warn_if_overloaded(&objects[PLAYER], game);
+            return true;
+        }
+    }
+    if game.inventory.len() >= MAX_INVENTORY {
+        game.messages.add(
+            format!(
+                "Your inventory is full, cannot pick up {}.",
+                item_name(&objects[object_id], game)
+            ),
+            RED,
+        );
+        return false;
+    }
+
+    let item = remove_object(object_id, objects);
+    if item.item == Some(Item::Amulet) {
+        game.messages
+            .add(format!("You take the {}!", item.name), LIGHT_YELLOW);
+        if !game.amulet_taken {
+            game.amulet_taken = true;
+            game.messages.add(
+                "The dungeon shudders. Its inhabitants are coming for you...",
+                RED,
+            );
+        }
+    } else {
+        game.messages.add(
+            format!("You picked up a {}!", item_name(&item, game)),
+            GREEN,
+        );
+    }
+    game.inventory.push(item);
+    warn_if_overloaded(&objects[PLAYER], game);
+    true
+}
+
+/// Pick up what lies under the player. A lone item is taken straight away,
+/// with more than one the player picks which, or all of them. Returns false
+/// if nothing was picked up, which includes backing out of the menu.
+pub fn pick_up(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> bool {
+    let position = objects[PLAYER].pos();
+    // the last menu letter is for taking everything
+    let item_ids: Vec<usize> = objects
+        .iter()
+        .enumerate()
+        .filter(|(_, object)| object.pos() == position && object.item.is_some())
+        .map(|(id, _)| id)
+        .take(MAX_MENU_OPTIONS - 1)
+        .collect();
+    match item_ids.len() {
+        0 => false,
+        1 => pick_item_up(item_ids[0], game, objects),
+        _ => {
+            let mut options: Vec<String> = item_ids
+                .iter()
+                .map(|&id| item_name(&objects[id], game))
+                .collect();
+            options.push("All".to_string());
+            let header = "Press the key next to an item to pick it up.\n";
+            match menu(header, &options, INVENTORY_WIDTH, &mut tcod.root) {
+                Some(choice) if choice < item_ids.len() => {
+                    pick_item_up(item_ids[choice], game, objects)
+                }
+                Some(_) => {
+                    // from the back, so taking one doesn't move the ones
+                    // still to come
+                    let mut picked_up = false;
+                    for &id in item_ids.iter().rev() {
+                        if !pick_item_up(id, game, objects) {
+                            break;
+                        }
+                        picked_up = true;
+                    }
+                    picked_up
+                }
+                None => false,
+            }
+        }
+    }
+}
+
+/// everything in the inventory put together
+pub fn total_weight(inventory: &[Object]) -> u32 {
+    inventory
+        .iter()
+        .filter_map(|object| object.item)
+        .map(Item::weight)
+        .sum()
+}
+
+/// how much the player can carry before it slows them down
+pub fn carry_capacity(player: &Object) -> u32 {
+    let power = player.fighter.map_or(0, |fighter| fighter.base_power);
+    BASE_CARRY_CAPACITY + power.max(0) as u32 * CARRY_PER_POWER
+}
+
+/// true while the player carries more than they can
+pub fn overloaded(player: &Object, game: &Game) -> bool {
+    total_weight(&game.inventory) > carry_capacity(player)
+}
+
+fn warn_if_overloaded(player: &Object, game: &mut Game) {
+    if overloaded(player, game) {
+        game.messages.add("You are overloaded!", LIGHT_RED);
+    }
+}
+
+/// Put an item from the inventory back on the map at the player's feet
+pub fn drop_item(inventory_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
+    let mut item = game.inventory.remove(inventory_id);
+    if item.equipment.is_some() {
+        item.dequip(&mut game.messages);
+    }
+    let (x, y) = objects[PLAYER].pos();
+    item.set_pos(x, y);
+    game.messages
+        .add(format!("You dropped a {}.", item_name(&item, game)), YELLOW);
+    objects.push(item);
+}
+
+/// Use the item in the given inventory slot, dropping it from the
+/// inventory once it's used up
+pub fn use_item(
+    inventory_id: usize,
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    use Item::*;
+
+    let item = match game.inventory[inventory_id].item {
+        Some(item) => item,
+        None => {
+            game.messages.add(
+                format!(
+                    "The {} cannot be used.",
+                    item_name(&game.inventory[inventory_id], game)
+                ),
+                WHITE,
+            );
+            return UseResult::Cancelled;
+        }
+    };
+    let on_use = match item {
+        Heal | Speed | Invisibility | SeeInvisible | Lightning | Confuse | Fireball | Charm
+        | Summon | Slow | Blink | Teleport | MagicMapping | DetectMonsters => use_spell_item,
+        LiquidFire => drink_liquid_fire,
+        Identify => cast_identify,
+        PlaceTrap => set_trap,
+        Sword | Shield | Pickaxe | Bow | Sling => toggle_equipment,
+        Ammo { .. } => use_ammo,
+        Key { .. } => use_key,
+        Amulet => use_amulet,
+        Food => eat_food,
+        Gold { .. } => unreachable!("gold never goes in the inventory"),
+    };
+    let result = on_use(inventory_id, tcod, game, objects);
+    if let UseResult::UsedUp = result {
+        // seeing what it did gives it away
+        identify(item, game);
+        game.inventory.remove(inventory_id);
+    }
+    result
+}
+
+/// set a spike trap kit on the player's tile
+fn set_trap(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    if place_trap(game, objects) {
+        UseResult::UsedUp
+    } else {
+        UseResult::Cancelled
+    }
+}
+
+/// Keys work by walking into their door, there's nothing else to do with them
+fn use_key(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    game.messages
+        .add("Walk into its locked door to use the key.", WHITE);
+    UseResult::Cancelled
+}
+
+/// Cast the spell a potion or scroll holds, keeping the item if the spell
+/// had nothing to do
+fn use_spell_item(
+    inventory_id: usize,
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    let spell = game.inventory[inventory_id]
+        .item
+        .and_then(Item::spell)
+        .expect("only spell items get here");
+    if spell.cast(tcod, game, objects) {
+        UseResult::UsedUp
+    } else {
+        UseResult::Cancelled
+    }
+}
+
+/// Ammo is fired from its bow or sling, not used on its own
+fn use_ammo(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    game.messages.add(
+        "Put on a weapon to shoot it with and press f to fire.",
+        WHITE,
+    );
+    UseResult::Cancelled
+}
+
+/// The Amulet does nothing but win the game once it's carried out
+fn use_amulet(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    game.messages.add(
+        "The Amulet hums in your hand. Carry it back up to the surface!",
+        LIGHT_YELLOW,
+    );
+    UseResult::Cancelled
+}
+
+/// The player walked into a locked door: unlock it if they have its key,
+/// otherwise try to bash it open, which is loud and can hurt.
+pub fn open_locked_door(x: i32, y: i32, kind: usize, game: &mut Game, objects: &mut [Object]) {
+    let key_id = game
+        .inventory
+        .iter()
+        .position(|object| object.item == Some(Item::Key { kind }));
+    if let Some(key_id) = key_id {
+        let key = game.inventory.remove(key_id);
+        game.map.tile_mut(x, y).open_door();
+        game.dirty = true;
+        game.messages
+            .add(format!("You unlock the door with the {}.", key.name), GREEN);
+        return;
+    }
+
+    game.noises.push(Noise {
+        x,
+        y,
+        loudness: BASH_LOUDNESS,
+    });
+    if rand::random::<f32>() < BASH_CHANCE {
+        game.map.tile_mut(x, y).open_door();
+        game.dirty = true;
+        game.messages.add("You bash the locked door open!", ORANGE);
+    } else {
+        game.messages.add(
+            format!(
+                "The door is locked. The {} would open it.",
+                KEY_KINDS[kind].0
+            ),
+            WHITE,
+        );
+    }
+    if rand::random::<f32>() < BASH_HURT_CHANCE {
+        game.messages.add("You hurt yourself on the door.", RED);
+        objects[PLAYER].take_damage(BASH_DAMAGE, game);
+    }
+}
+
+/// Set the player on fire. Drinking it is a mistake made once.
+fn drink_liquid_fire(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &mut [Object],
+) -> UseResult {
+    game.messages
+        .add("Your throat burns! You're on fire!", ORANGE);
+    objects[PLAYER].status.add(
+        StatusKind::Burning,
+        LIQUID_FIRE_BURN_TURNS,
+        LIQUID_FIRE_BURN_DAMAGE,
+    );
+    UseResult::UsedUp
+}
+
+fn eat_food(
+    _inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    if eat(game) {
+        UseResult::UsedUp
+    } else {
+        UseResult::Cancelled
+    }
+}
+
+/// Put the item on, or take it off if it's already on. Whatever was in
+/// its slot before comes off first.
+fn toggle_equipment(
+    inventory_id: usize,
+    _tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        game.inventory[inventory_id].dequip(&mut game.messages);
+    } else {
+        // if the slot is already being used, dequip whatever is there first
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
+            game.inventory[current].dequip(&mut game.messages);
+        }
+        game.inventory[inventory_id].equip(&mut game.messages);
+    }
+    UseResult::UsedAndKept
+}
+
+/// Inventory index of the equipment in `slot`, if anything is on there
+fn get_equipped_in_slot(slot: Slot, inventory: &[Object]) -> Option<usize> {
+    inventory.iter().position(|item| {
+        item.equipment
+            .is_some_and(|equipment| equipment.equipped && equipment.slot == slot)
+    })
+}
+
+/// Ask which unknown item in the inventory to identify. A scroll read with
+/// nothing else left to identify is kept.
+fn cast_identify(
+    inventory_id: usize,
+    tcod: &mut Tcod,
+    game: &mut Game,
+    _objects: &mut [Object],
+) -> UseResult {
+    // reading it is enough to know what the scroll is
+    identify(Item::Identify, game);
+    let unknown: Vec<usize> = (0..game.inventory.len())
+        .filter(|&id| {
+            id != inventory_id
+                && game.inventory[id]
+                    .item
+                    .is_some_and(|item| !is_identified(item, game))
+        })
+        .collect();
+    if unknown.is_empty() {
+        game.messages
+            .add("You have nothing left to identify.", LIGHT_GREY);
+        return UseResult::Cancelled;
+    }
+
+    let options: Vec<String> = unknown
+        .iter()
+        .map(|&id| item_name(&game.inventory[id], game))
+        .collect();
+    let header = "Press the key next to an item to identify it.\n";
+    let choice = menu(header, &options, INVENTORY_WIDTH, &mut tcod.root);
+    match choice.and_then(|choice| game.inventory[unknown[choice]].item) {
+        Some(item) => {
+            identify(item, game);
+            UseResult::UsedUp
+        }
+        None => UseResult::Cancelled,
+    }
+}
+
+/// Shoot the equipped bow or sling at a tile the player picks. The shot
+/// flies along the line to it and hits the first fighter in the way, using
+/// up one piece of ammo either way. Returns false if nothing was fired.
+pub fn fire(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let launcher = game
+        .inventory
+        .iter()
+        .filter(|object| object.equipment.is_some_and(|equipment| equipment.equipped))
+        .find_map(|object| object.item.and_then(Item::launcher));
+    let Some(launcher) = launcher else {
+        game.messages
+            .add("You have nothing to shoot with.", LIGHT_GREY);
+        return false;
+    };
+    let Some(ammo_id) = find_ammo(launcher.ammo, &game.inventory) else {
+        game.messages.add(
+            format!("You are out of {}s.", launcher.ammo.name()),
+            LIGHT_GREY,
+        );
+        return false;
+    };
+
+    game.messages.add(
+        "Left-click a target tile to shoot at, or right-click to cancel.",
+        LIGHT_CYAN,
+    );
+    let Some(target) = target_tile(tcod, game, objects, Some(launcher.range as f32)) else {
+        return false;
+    };
+    let Some(Shot { flight, target_id }) = aim(target, game, objects) else {
+        return false;
+    };
+
+    if let Some(Item::Ammo { count, .. }) = game.inventory[ammo_id].item {
+        if count > 1 {
+            set_ammo_count(&mut game.inventory[ammo_id], count - 1);
+        } else {
+            game.inventory.remove(ammo_id);
+        }
+    }
+    let glyph = projectile_glyph(objects[PLAYER].pos(), target);
+    animate_projectile(tcod, game, objects, &flight, glyph, launcher.ammo.color());
+
+    let missile = launcher.ammo.name();
+    match target_id {
+        Some(target_id) => {
+            let (player, target) = mut_two(PLAYER, target_id, objects);
+            player.shoot(
+                target,
+                launcher.power,
+                missile,
+                &mut rand::thread_rng(),
+                game,
+            );
+        }
+        None => game
+            .messages
+            .add(format!("The {} hits nothing.", missile), LIGHT_GREY),
+    }
+    true
+}
+
+/// Ask for an item and a tile to throw it at. It stops at the first fighter
+/// in the way: weapons hit it, potions shatter and splash everything around
+/// them, and anything else bounces off. Whatever doesn't break lands there.
+/// Returns false if nothing was thrown.
+pub fn throw(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> bool {
+    let inventory_id = inventory_menu(
+        game,
+        "Press the key next to an item to throw it, or any other to cancel.\n",
+        &mut tcod.root,
+    );
+    let Some(inventory_id) = inventory_id else {
+        return false;
+    };
+    // checked before take_one, so nothing is lost if there is no item to throw
+    let Some(item) = game.inventory[inventory_id].item else {
+        return false;
+    };
+    game.messages.add(
+        "Left-click a target tile to throw at, or right-click to cancel.",
+        LIGHT_CYAN,
+    );
+    let Some(target) = target_tile(tcod, game, objects, Some(THROW_RANGE)) else {
+        return false;
+    };
+    let Some(Shot { flight, target_id }) = aim(target, game, objects) else {
+        return false;
+    };
+
+    let mut thrown = take_one(inventory_id, game);
+    animate_projectile(tcod, game, objects, &flight, thrown.char, thrown.color);
+    // "arrow" reads better than "1 arrow" in the messages
+    let name = match thrown.item {
+        Some(Item::Ammo { kind, .. }) => kind.name().to_string(),
+        _ => item_name(&thrown, game),
+    };
+    // walls stop it short of the tile it was thrown at
+    let (x, y) = flight
+        .iter()
+        .rev()
+        .find(|&&(x, y)| !game.map.tile(x, y).blocked)
+        .copied()
+        .unwrap_or_else(|| objects[PLAYER].pos());
+
+    if matches!(
+        item,
+        Item::Heal | Item::LiquidFire | Item::Speed | Item::Invisibility | Item::SeeInvisible
+    ) {
+        shatter(item, &name, (x, y), game, objects);
+        return true;
+    }
+    match (target_id, item.thrown_power()) {
+        (Some(target_id), Some(power)) => {
+            let (player, target) = mut_two(PLAYER, target_id, objects);
+            player.shoot(target, power, &name, &mut rand::thread_rng(), game);
+        }
+        (Some(target_id), None) => game.messages.add(
+            format!("The {} bounces off the {}.", name, objects[target_id].name),
+            LIGHT_GREY,
+        ),
+        (None, _) => game
+            .messages
+            .add(format!("The {} lands on the floor.", name), LIGHT_GREY),
+    }
+    thrown.set_pos(x, y);
+    objects.push(thrown);
+    true
+}
+
+/// Where a shot or a throw goes
+struct Shot {
+    // the tiles it flies over, ending where it stops
+    flight: Vec<(i32, i32)>,
+    // the fighter it stops at, if it doesn't reach the target tile
+    target_id: Option<usize>,
+}
+
+/// Follow a shot from the player at `target` up to the first fighter in
+/// the way. `None` if the target can't be hit from here.
+fn aim(target: (i32, i32), game: &mut Game, objects: &[Object]) -> Option<Shot> {
+    let from = objects[PLAYER].pos();
+    if target == from {
+        return None;
+    }
+    if !has_line_of_fire(from, target, game) {
+        game.messages
+            .add("You don't have a clear shot at that.", LIGHT_GREY);
+        return None;
+    }
+
+    let mut flight = vec![];
+    for point in Line::new(from, target) {
+        flight.push(point);
+        let target_id = objects.iter().position(|object| {
+            object.fighter.is_some() && object.alive && object.occupies(point.0, point.1)
+        });
+        if target_id.is_some() {
+            return Some(Shot { flight, target_id });
+        }
+    }
+    Some(Shot {
+        flight,
+        target_id: None,
+    })
+}
+
+/// Take one item out of the given inventory slot, splitting it off its
+/// stack if it's ammo. It comes off first if it was equipped.
+fn take_one(inventory_id: usize, game: &mut Game) -> Object {
+    if let Some(Item::Ammo { kind, count }) = game.inventory[inventory_id].item {
+        if count > 1 {
+            set_ammo_count(&mut game.inventory[inventory_id], count - 1);
+            return new_item(Item::Ammo { kind, count: 1 }, 0, 0);
+        }
+    }
+    let mut item = game.inventory.remove(inventory_id);
+    if item.equipment.is_some() {
+        item.dequip(&mut game.messages);
+    }
+    item
+}
+
+/// A thrown potion breaking at (x, y), doing what drinking it would to
+/// every fighter close by
+fn shatter(item: Item, name: &str, (x, y): (i32, i32), game: &mut Game, objects: &mut [Object]) {
+    let splashed = |object: &Object| {
+        object.alive && object.fighter.is_some() && object.distance(x, y) <= SPLASH_RADIUS
+    };
+    match item {
+        Item::Heal => {
+            game.messages.add(
+                format!("The {} shatters into a healing mist!", name),
+                LIGHT_VIOLET,
+            );
+            for object in objects.iter_mut().filter(|object| splashed(object)) {
+                object.heal(spells::HEAL_AMOUNT, game);
+            }
+        }
+        Item::LiquidFire => {
+            game.messages.add(
+                format!("The {} shatters and bursts into flames!", name),
+                ORANGE,
+            );
+            let mut xp_to_gain = 0;
+            for object in objects.iter_mut().filter(|object| splashed(object)) {
+                if let Some(xp) = object.take_damage(LIQUID_FIRE_DAMAGE, game) {
+                    xp_to_gain += xp;
+                } else if object.alive {
+                    object.status.add(
+                        StatusKind::Burning,
+                        LIQUID_FIRE_BURN_TURNS,
+                        LIQUID_FIRE_BURN_DAMAGE,
+                    );
+                }
+            }
+            if objects[PLAYER].alive {
+                objects[PLAYER].gain_xp(xp_to_gain);
+            }
+        }
+        Item::Speed => {
+            game.messages.add(
+                format!("The {} shatters into a shimmering haze!", name),
+                LIGHT_AZURE,
+            );
+            for object in objects.iter_mut().filter(|object| splashed(object)) {
+                object
+                    .status
+                    .add(StatusKind::Hasted, spells::HASTE_TURNS, 0);
+            }
+        }
+        Item::Invisibility => {
+            game.messages.add(
+                format!(
+                    "The {} shatters and everything nearby fades from sight!",
+                    name
+                ),
+                LIGHT_GREY,
+            );
+            for object in objects.iter_mut().filter(|object| splashed(object)) {
+                object
+                    .status
+                    .add(StatusKind::Invisible, spells::INVISIBILITY_TURNS, 0);
+            }
+        }
+        Item::SeeInvisible => {
+            game.messages.add(
+                format!("The {} shatters into a glittering cloud!", name),
+                LIGHTER_YELLOW,
+            );
+            for object in objects.iter_mut().filter(|object| splashed(object)) {
+                object
+                    .status
+                    .add(StatusKind::SeeInvisible, spells::SEE_INVISIBLE_TURNS, 0);
+            }
+        }
+        _ => unreachable!("only potions shatter"),
+    }
+    identify(item, game);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ammo_weighs_by_the_bundle() {
+        let arrows = |count| Item::Ammo {
+            kind: AmmoKind::Arrow,
+            count,
+        };
+        assert_eq!(arrows(0).weight(), 0);
+        assert_eq!(arrows(1).weight(), 1);
+        assert_eq!(arrows(10).weight(), 1);
+        assert_eq!(arrows(11).weight(), 2);
+    }
+
+    #[test]
+    fn the_inventory_weighs_what_its_items_do() {
+        let inventory = vec![
+            new_item(Item::Sword, 0, 0),
+            new_item(Item::Shield, 0, 0),
+            new_item(Item::Heal, 0, 0),
+            new_item(Item::Gold { amount: 500 }, 0, 0),
+        ];
+        assert_eq!(total_weight(&inventory), 6 + 8 + 1);
+    }
+}
@@ -0,0 +1,101 @@
+//! Weighted random tables: what monsters drop when they die, and every
+//! monster, item, trap and layout the level generator picks at random.
+
+use crate::items::{AmmoKind, Item};
+use rand::Rng;
+
+/// Entries picked in proportion to their weights. An entry with a weight
+/// of zero never comes up.
+#[derive(Clone, Debug)]
+pub struct LootTable<T> {
+    entries: Vec<(T, u32)>,
+}
+
+impl<T: Clone> LootTable<T> {
+    pub fn new(entries: Vec<(T, u32)>) -> Self {
+        LootTable { entries }
+    }
+
+    /// Pick an entry, `None` if every weight is zero
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> Option<T> {
+        let total: u32 = self.entries.iter().map(|&(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0, total);
+        for (entry, weight) in &self.entries {
+            if roll < *weight {
+                return Some(entry.clone());
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    /// never pick the entries `keep` turns down
+    pub fn retain(&mut self, keep: impl Fn(&T) -> bool) {
+        for (entry, weight) in self.entries.iter_mut() {
+            if !keep(entry) {
+                *weight = 0;
+            }
+        }
+    }
+}
+
+/// What a monster leaves behind when it dies, by its name. Rolling `None`
+/// means it dropped nothing.
+pub fn drop_table(name: &str) -> LootTable<Option<Item>> {
+    let entries = match name {
+        "orc" => vec![
+            (None, 55),
+            (Some(Item::Gold { amount: 10 }), 15),
+            (Some(Item::Heal), 15),
+            (Some(Item::Food), 10),
+            (Some(Item::Sword), 5),
+        ],
+        "goblin archer" => vec![
+            (None, 35),
+            (Some(Item::Gold { amount: 8 }), 15),
+            (
+                Some(Item::Ammo {
+                    kind: AmmoKind::Arrow,
+                    count: 5,
+                }),
+                20,
+            ),
+            (Some(Item::Lightning), 15),
+            (Some(Item::Confuse), 15),
+        ],
+        "Vampire" => vec![
+            (None, 40),
+            (Some(Item::Gold { amount: 25 }), 20),
+            (Some(Item::Heal), 40),
+        ],
+        "ogre" => vec![
+            (None, 30),
+            (Some(Item::Gold { amount: 30 }), 20),
+            (Some(Item::Shield), 25),
+            (Some(Item::Fireball), 25),
+        ],
+        // summoned skeletons and anything else carry nothing
+        _ => vec![],
+    };
+    LootTable::new(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monsters_that_carry_things_can_drop_gold() {
+        for name in &["orc", "goblin archer", "Vampire", "ogre"] {
+            let table = drop_table(name);
+            let gold = table
+                .entries
+                .iter()
+                .any(|&(item, weight)| matches!(item, Some(Item::Gold { .. })) && weight > 0);
+            assert!(gold, "{} never drops gold", name);
+        }
+    }
+}
@@ -1,715 +1,273 @@
-use rand::Rng;
-use std::cmp;
-use tcod::colors::*;
+mod activity;
+mod ai;
+mod camera;
+mod class;
+mod combat;
+mod companion;
+mod config;
+mod describe;
+mod game;
+mod hunger;
+mod identify;
+mod items;
+mod loot;
+mod map;
+mod noise;
+mod object;
+mod pathfinding;
+mod shop;
+mod spells;
+mod status;
+mod traps;
+mod ui;
+mod vaults;
+
+use crate::camera::Camera;
+use crate::config::{load_keymap, Keymap};
+use crate::game::main_menu;
+use crate::map::MapStyle;
+use crate::ui::{Tcod, PANEL_HEIGHT};
+use crate::vaults::{load_vaults, Vault};
+use std::env;
+use std::path::PathBuf;
+use std::process;
 use tcod::console::*;
-use tcod::map::{FovAlgorithm, Map as FovMap};
-
+use tcod::map::Map as FovMap;
+
+const FONT_FILE: &str = "arial10x10.png";
+// hand-made rooms for the level generator, see `vaults`
+const VAULT_DIR: &str = "vaults";
+// key bindings, see `config`
+const KEYMAP_FILE: &str = "keys.toml";
+// default window size, can be changed with `--size WIDTHxHEIGHT`
 const SCREEN_WIDTH: i32 = 80;
 const SCREEN_HEIGHT: i32 = 50;
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 43;
-const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
-const COLOR_LIGHT_WALL: Color = Color {
-    r: 130,
-    g: 110,
-    b: 50,
-};
-const COLOR_DARK_GROUND: Color = Color {
-    r: 50,
-    g: 50,
-    b: 150,
-};
-const COLOR_LIGHT_GROUND: Color = Color {
-    r: 200,
-    g: 180,
-    b: 50,
-};
-const ROOM_MAX_SIZE: i32 = 10;
-const ROOM_MIN_SIZE: i32 = 6;
-const MAX_ROOMS: i32 = 30;
-const MAX_ROOM_MONSTERS: i32 = 3;
+const MIN_SCREEN_WIDTH: i32 = 40;
+const MIN_SCREEN_HEIGHT: i32 = 30;
+// default map size, can be changed with `--map-size WIDTHxHEIGHT`; the
+// camera scrolls around whatever doesn't fit on screen
+const MAP_WIDTH: i32 = 100;
+const MAP_HEIGHT: i32 = 60;
+const MIN_MAP_WIDTH: i32 = 40;
+const MIN_MAP_HEIGHT: i32 = 30;
+const MAX_MAP_WIDTH: i32 = 200;
+const MAX_MAP_HEIGHT: i32 = 200;
 const LIMIT_FPS: i32 = 20;
-const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
-const FOV_LIGHT_WALLS: bool = true;
-const TORCH_RADIUS: i32 = 10;
-const PLAYER: usize = 0;
-const BAR_WIDTH: i32 = 20;
-const PANEL_HEIGHT: i32 = 7;
-const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
-const MSX_X: i32 = BAR_WIDTH + 2;
-const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
-const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
-
-struct Tcod {
-    root: Root,
-    con: Offscreen,
-    panel: Offscreen,
-    fov: FovMap,
-}
-
-/// This is a generic object: the player, a monster, an item, the stairs...
-/// It's always represented by a character on screen.
-#[derive(Debug)]
-struct Object {
-    x: i32,
-    y: i32,
-    char: char,
-    color: Color,
-    name: String,
-    blocks: bool,
-    alive: bool,
-    fighter: Option<Fighter>,
-    ai: Option<Ai>,
-}
-
-impl Object {
-    pub fn new(x: i32, y: i32, char: char, name: &str, color: Color, blocks: bool) -> Self {
-        Object {
-            x,
-            y,
-            char,
-            name: name.into(),
-            color,
-            alive: false,
-            blocks,
-            fighter: None,
-            ai: None,
-        }
-    }
-
-    /// set the color and then draw the character that represents
-    /// this object at its position
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
-    }
-
-    pub fn pos(&self) -> (i32, i32) {
-        (self.x, self.y)
-    }
-
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
-    }
-
-    pub fn distance_to(&self, other: &Object) -> f32 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
-    }
-
-    pub fn take_damage(&mut self, damage: i32) {
-        if let Some(fighter) = self.fighter.as_mut() {
-            if damage > 0 {
-                fighter.hp -= damage;
-            }
-            if fighter.hp <= 0 {
-                self.alive = false;
-                fighter.on_death.callback(self);
-            }
-        }
-        // check for death and call death function
-        if let Some(fighter) = self.fighter {
-            if fighter.hp <= 0 {
-                self.alive = false;
-                fighter.on_death.callback(self);
-            }
-        }
-    }
-
-    pub fn attack(&mut self, target: &mut Object) {
-        let damage = self.fighter.map_or(0, |f| f.power) - target.fighter.map_or(0, |f| f.defense);
-        if damage > 0 {
-            println!(
-                "{} attacks {} for {} hit points.",
-                self.name, target.name, damage
-            );
-
-            target.take_damage(damage);
-        } else {
-            println!(
-                "{} attacks {} but it has no effect!",
-                self.name, target.name
-            )
-        }
-    }
-}
-
-// combat-related properties and methods
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Fighter {
-    max_hp: i32,
-    hp: i32,
-    defense: i32,
-    power: i32,
-    on_death: DeathCallback,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum DeathCallback {
-    Player,
-    Monster,
-}
-
-impl DeathCallback {
-    fn callback(self, object: &mut Object) {
-        use DeathCallback::*;
-        let callback: fn(&mut Object) = match self {
-            Player => player_death,
-            Monster => monster_death,
-        };
-        callback(object);
-    }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum Ai {
-    Basic,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum PlayerAction {
-    TookTurn,
-    DidntTakeTurn,
-    Exit,
-}
-
-/// Tile for map and it's properties
-#[derive(Clone, Copy, Debug)]
-struct Tile {
-    blocked: bool,
-    block_site: bool,
-    explored: bool,
-}
-
-impl Tile {
-    pub fn empty() -> Self {
-        Tile {
-            blocked: false,
-            block_site: false,
-            explored: false,
-        }
-    }
-
-    pub fn wall() -> Self {
-        Tile {
-            blocked: true,
-            block_site: true,
-            explored: false,
-        }
-    }
-}
-
-struct Messages {
-    messages: Vec<(String, Color)>,
-}
-
-impl Messages {
-    pub fn new() -> Self {
-        Self { messages: vec![] }
-    }
-
-    pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
-        self.messages.push((message.into(), color))
-    }
 
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
-        self.messages.iter()
-    }
+/// Settings read from the command line
+struct Options {
+    screen_width: i32,
+    screen_height: i32,
+    map_width: i32,
+    map_height: i32,
+    // `--no-animations` turns off screen shake and flashes
+    animations: bool,
+    // `--no-autosave` stops saving the game on the way down
+    autosave: bool,
+    // `--generator NAME` builds every level the same way
+    map_style: Option<MapStyle>,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Rect {
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
-}
+fn main() {
+    let options = parse_args();
+    let (map_width, map_height) = (options.map_width, options.map_height);
 
-impl Rect {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
-        Rect {
-            x1: x,
-            y1: y,
-            x2: x + w,
-            y2: y + h,
+    let font_path = find_font().unwrap_or_else(|| {
+        eprintln!("Could not find the font file {}. Looked in:", FONT_FILE);
+        for path in asset_candidates(FONT_FILE) {
+            eprintln!("    {}", path.display());
         }
-    }
-
-    pub fn center(&self) -> (i32, i32) {
-        let center_x = (self.x1 + self.x2) / 2;
-        let center_y = (self.y1 + self.y2) / 2;
-        (center_x, center_y)
-    }
-
-    pub fn intersects_with(&self, other: &Rect) -> bool {
-        // If either of these conditions is false, then the rectangles cannot intersect.
-        // If both of these conditons are true, then the rectangles can intersect
-        // on the X axis.
-        let intersects_on_x_axis = (self.x1 <= other.x2) && (self.x2 >= other.x1);
-
-        // If either of these conditions is false, then the rectangles cannot intersect.
-        // If both of these conditons are true, then the rectangles can intersect
-        // on the Y axis.
-        let intersects_on_y_axis = (self.y1 <= other.y2) && (self.y2 >= other.y1);
-
-        // If both of these conditions are true, then the rectangles intersect.
-        intersects_on_x_axis && intersects_on_y_axis
-    }
-}
-
-/// NOTE:
-/// There’s a ton of different ways to create the map.
-/// One common alternative is one continuous Vec with MAP_HEIGHT * MAP_WIDTH items.
-/// To access a tile on (x, y), you would do map[y * MAP_WIDTH + x].
-/// The advantage is that you only do one array lookup instead of two and iterating
-/// over every object in the map is faster because they’re all in the same region of memory.
-type Map = Vec<Vec<Tile>>;
-
-struct Game {
-    map: Map,
-}
+        eprintln!(
+            "It ships in the root of the game's repository; copy it next to the \
+             executable or run the game from that directory."
+        );
+        process::exit(1);
+    });
+    let vaults = find_vaults();
+    let keymap = find_keymap();
 
-fn main() {
     // Set up Tcod fields
     tcod::system::set_fps(LIMIT_FPS);
     let root = Root::initializer()
-        .font("arial10x10.png", FontLayout::Tcod)
+        .font(font_path, FontLayout::Tcod)
         .font_type(FontType::Greyscale)
-        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .size(options.screen_width, options.screen_height)
         .title("Rust/libtcod tutorial")
         .init();
-    let con = Offscreen::new(MAP_WIDTH, MAP_HEIGHT);
-    let fov = FovMap::new(MAP_WIDTH, MAP_HEIGHT);
-    let panel = Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT);
+    let con = Offscreen::new(map_width, map_height);
+    let map_cache = Offscreen::new(map_width, map_height);
+    let fov = FovMap::new(map_width, map_height);
+    let panel = Offscreen::new(options.screen_width, PANEL_HEIGHT);
+    // everything above the panel shows the map
+    let camera = Camera::new(options.screen_width, options.screen_height - PANEL_HEIGHT);
     let mut tcod = Tcod {
         root,
         con,
+        map_cache,
         panel,
         fov,
+        camera,
+        show_monster_health: true,
+        show_damage_numbers: true,
+        show_debug_grid: false,
+        show_minimap: false,
+        animations: options.animations,
+        autosave: options.autosave,
+        map_style: options.map_style,
+        vaults,
+        keymap,
+        screen_effects: vec![],
+        key: Default::default(),
+        mouse: Default::default(),
     };
 
-    // Set up player, npc and vector of objects (players are objects)
-    let mut player = Object::new(0, 0, '@', "player", WHITE, true);
-    player.alive = true;
-    player.fighter = Some(Fighter {
-        max_hp: 30,
-        hp: 30,
-        defense: 2,
-        power: 5,
-        on_death: DeathCallback::Player,
-    });
-
-    let mut objects = vec![player];
-
-    let mut game = Game {
-        map: make_map(&mut objects),
+    main_menu(&mut tcod);
+}
+
+/// Places a file shipped with the game may be, in order: the working
+/// directory, then next to the executable
+fn asset_candidates(name: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from(name)];
+    if let Some(beside_exe) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(name)))
+    {
+        candidates.push(beside_exe);
+    }
+    candidates
+}
+
+/// libtcod aborts the whole process on a missing font, so look for it first
+fn find_font() -> Option<PathBuf> {
+    asset_candidates(FONT_FILE)
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// The game works without vaults, so a missing directory or a broken vault
+/// file only gets a warning
+fn find_vaults() -> Vec<Vault> {
+    let Some(dir) = asset_candidates(VAULT_DIR)
+        .into_iter()
+        .find(|path| path.is_dir())
+    else {
+        eprintln!(
+            "No {} directory found, levels will have no vaults",
+            VAULT_DIR
+        );
+        return vec![];
     };
-
-    // populate the FOV map, according to the generated map
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            tcod.fov.set(
-                x,
-                y,
-                !game.map[x as usize][y as usize].block_site,
-                !game.map[x as usize][y as usize].blocked,
-            );
-        }
-    }
-
-    // force FOV "recompute" first time through the game loop
-    let mut previous_player_position = (-1, -1);
-
-    // Game loop
-    while !tcod.root.window_closed() {
-        tcod.con.clear();
-
-        let fov_recompute = previous_player_position != (objects[PLAYER].x, objects[PLAYER].y);
-        render_all(&mut tcod, &mut game, &objects, fov_recompute);
-
-        tcod.root.flush();
-
-        previous_player_position = objects[PLAYER].pos();
-        let player_action = handle_keys(&mut tcod, &game, &mut objects);
-        if player_action == PlayerAction::Exit {
-            break;
-        }
-
-        // monsters turn
-        if objects[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
-            for id in 0..objects.len() {
-                if objects[id].ai.is_some() {
-                    ai_take_turn(id, &tcod, &game, &mut objects)
-                }
+    match load_vaults(&dir) {
+        Ok((vaults, errors)) => {
+            for error in errors {
+                eprintln!("Skipping vault {}", error);
             }
+            vaults
         }
-    }
-}
-
-fn handle_keys(tcod: &mut Tcod, game: &Game, objects: &mut [Object]) -> PlayerAction {
-    use tcod::input::Key;
-    use tcod::input::KeyCode::*;
-    use PlayerAction::*;
-
-    let key = tcod.root.wait_for_keypress(true);
-    let player_alive = objects[PLAYER].alive;
-
-    return match (key, key.text(), player_alive) {
-        // movement keys
-        (Key { code: Up, .. }, _, true) => {
-            player_move_or_attack(0, -1, game, objects);
-            TookTurn
-        }
-        (Key { code: Down, .. }, _, true) => {
-            player_move_or_attack(0, 1, game, objects);
-            TookTurn
+        Err(error) => {
+            eprintln!("Could not read {}: {}", dir.display(), error);
+            vec![]
         }
-        (Key { code: Left, .. }, _, true) => {
-            player_move_or_attack(-1, 0, game, objects);
-            TookTurn
-        }
-        (Key { code: Right, .. }, _, true) => {
-            player_move_or_attack(1, 0, game, objects);
-            TookTurn
-        }
-
-        // toggle fullscreen
-        (
-            Key {
-                code: Enter,
-                alt: true,
-                ..
-            },
-            _,
-            _,
-        ) => {
-            let fullscreen = tcod.root.is_fullscreen();
-            tcod.root.set_fullscreen(!fullscreen);
-            DidntTakeTurn
-        }
-        // exit game
-        (Key { code: Escape, .. }, _, _) => Exit,
-        _ => DidntTakeTurn,
-    };
-}
-
-fn make_map(objects: &mut Vec<Object>) -> Map {
-    // fill map with "blocked" tiles
-    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-
-    let mut rooms = vec![];
-    for _ in 0..MAX_ROOMS {
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
-
-        let new_room = Rect::new(x, y, w, h);
-
-        let is_valid_location = !rooms
-            .iter()
-            .any(|other_room| new_room.intersects_with(other_room));
-
-        if is_valid_location {
-            create_room(new_room, &mut map);
-            place_objects(new_room, &map, objects);
-
-            let (new_x, new_y) = new_room.center();
-
-            if rooms.is_empty() {
-                // this is the first room, where the player starts
-                objects[PLAYER].set_pos(new_x, new_y);
-            } else {
-                // center coordinates of previous room
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
-
-                // coinflip (50/50 bool)
-                if rand::random() {
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
-                } else {
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
-                }
-            }
-        }
-
-        // add the new room to the list of rooms
-        rooms.push(new_room);
     }
-
-    map
 }
 
-fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
-    // recompute FOV if needed (the player moved or something)
-    if fov_recompute {
-        let player = &objects[PLAYER];
-
-        tcod.fov
-            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO)
-    }
-
-    let mut to_draw: Vec<&Object> = objects
-        .iter()
-        .filter(|o| tcod.fov.is_in_fov(o.x, o.y))
-        .collect();
-    // sort non-blocking objects to the beginning
-    to_draw.sort_by(|o1, o2| o1.blocks.cmp(&o2.blocks));
-
-    // draw all objects in the list
-    for object in &to_draw {
-        object.draw(&mut tcod.con);
-    }
-
-    // go through all tiles, and set their background color
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let is_visible = tcod.fov.is_in_fov(x, y);
-            let is_wall = game.map[x as usize][y as usize].block_site;
-            let color = match (is_visible, is_wall) {
-                // outside of field of view:
-                (false, true) => COLOR_DARK_WALL,
-                (false, false) => COLOR_DARK_GROUND,
-                // inside fov:
-                (true, true) => COLOR_LIGHT_WALL,
-                (true, false) => COLOR_LIGHT_GROUND,
-            };
-
-            let is_explored = &mut game.map[x as usize][y as usize].explored;
-            if is_visible {
-                *is_explored = true;
-            }
-            if *is_explored {
-                tcod.con
-                    .set_char_background(x, y, color, BackgroundFlag::Set);
+/// Like the vaults, the keymap only gets a warning when it's missing or
+/// has mistakes in it, the default keys stand in for it
+fn find_keymap() -> Keymap {
+    let Some(path) = asset_candidates(KEYMAP_FILE)
+        .into_iter()
+        .find(|path| path.is_file())
+    else {
+        eprintln!("No {} found, using the default keys", KEYMAP_FILE);
+        return Keymap::default();
+    };
+    match load_keymap(&path) {
+        Ok((keymap, warnings)) => {
+            for warning in warnings {
+                eprintln!("{}: {}", path.display(), warning);
             }
+            keymap
         }
-    }
-
-    blit(
-        &tcod.con,
-        (0, 0),
-        (SCREEN_WIDTH, SCREEN_HEIGHT),
-        &mut tcod.root,
-        (0, 0),
-        1.0,
-        1.0,
-    );
-
-    // GUI
-    tcod.panel.set_default_background(BLACK);
-    tcod.panel.clear();
-
-    //show the player's stats
-    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER].fighter.map_or(0, |f| f.max_hp);
-    render_bar(&mut tcod.panel, 1, 1, BAR_WIDTH, "HP", hp, max_hp, LIGHT_RED, DARKER_RED);
-
-    blit(&tcod.panel, (0, 0), (SCREEN_WIDTH, PANEL_HEIGHT), &mut tcod.root, (0, PANEL_Y), 1.0, 1.0);
-}
-
-fn create_room(room: Rect, map: &mut Map) {
-    for x in (room.x1 + 1)..room.x2 {
-        for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = Tile::empty();
-        }
-    }
-}
-
-fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
-    // horizontal tunnel. `min()` and `max()` are used in case `x1 > x2`
-    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
-    }
-}
-
-fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
-    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
-    }
-}
-
-fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>) {
-    // choose random number of monsters
-    let num_monsters = rand::thread_rng().gen_range(0, MAX_ROOM_MONSTERS + 1);
-
-    for _ in 0..num_monsters {
-        // choose random spot for this monster
-        // gen_range is inclusive for arg1 and exclusive for arg2,
-        // so we need to add 1 to the first argument to not put a
-        // monster in a wall.
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
-
-        if !is_blocked(x, y, map, objects) {
-            let mut monster = if rand::random::<f32>() < 0.8 {
-                // 80% chance of getting an orc
-                let mut orc = Object::new(x, y, 'o', "orc", DESATURATED_GREEN, true);
-                orc.fighter = Some(Fighter {
-                    max_hp: 10,
-                    hp: 10,
-                    defense: 0,
-                    power: 3,
-                    on_death: DeathCallback::Monster,
-                });
-                orc.ai = Some(Ai::Basic);
-
-                orc
-            } else {
-                let mut vampire = Object::new(x, y, 'V', "Vampire", DARK_RED, true);
-                vampire.fighter = Some(Fighter {
-                    max_hp: 16,
-                    hp: 16,
-                    defense: 1,
-                    power: 4,
-                    on_death: DeathCallback::Monster,
-                });
-                vampire.ai = Some(Ai::Basic);
-
-                vampire
-            };
-
-            monster.alive = true;
-            objects.push(monster);
-        }
-    }
-}
-
-fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
-    // first test the map tile
-    if map[x as usize][y as usize].blocked {
-        return true;
-    }
-
-    // now check for any blocking objects
-    objects
-        .iter()
-        .any(|object| object.blocks && object.pos() == (x, y))
-}
-
-/// move by the given amount
-fn move_by(id: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
-    let (x, y) = objects[id].pos();
-    if !is_blocked(x + dx, y + dy, map, objects) {
-        objects[id].set_pos(x + dx, y + dy);
-    }
-}
-
-fn player_move_or_attack(dx: i32, dy: i32, game: &Game, objects: &mut [Object]) {
-    let x = objects[PLAYER].x + dx;
-    let y = objects[PLAYER].y + dy;
-
-    // try to find an attackable object in the forecasted position
-    // js equivalent array.find()
-    let target_id = objects
-        .iter()
-        .position(|object| object.fighter.is_some() && object.pos() == (x, y));
-
-    // attack if target found, move otherwise
-    match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER, target_id, objects);
-            player.attack(target);
-        }
-        None => {
-            move_by(PLAYER, dx, dy, &game.map, objects);
+        Err(error) => {
+            eprintln!("Could not read {}: {}", path.display(), error);
+            Keymap::default()
         }
     }
 }
 
-fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
-    // vector from this object to the target, and distance
-    let dx = target_x - objects[id].x;
-    let dy = target_y - objects[id].y;
-    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
-
-    // normalize it to length 1 (preserving direction), then round it and
-    // convert to integer so the movement is restricted to the map grid
-    let dx = (dx as f32 / distance).round() as i32;
-    let dy = (dy as f32 / distance).round() as i32;
-    move_by(id, dx, dy, map, objects);
-}
+/// Read the command line: `--size WIDTHxHEIGHT` for the window size in
+/// cells, `--map-size WIDTHxHEIGHT` for the map's in tiles, `--no-animations`
+/// `--no-autosave` and `--generator`. Bad arguments exit with a usage message.
+fn parse_args() -> Options {
+    let mut options = Options {
+        screen_width: SCREEN_WIDTH,
+        screen_height: SCREEN_HEIGHT,
+        map_width: MAP_WIDTH,
+        map_height: MAP_HEIGHT,
+        animations: true,
+        autosave: true,
+        map_style: None,
+    };
 
-fn ai_take_turn(monster_id: usize, tcod: &Tcod, game: &Game, objects: &mut [Object]) {
-    let (monster_x, monster_y) = objects[monster_id].pos();
-    if tcod.fov.is_in_fov(monster_x, monster_y) {
-        // move towards player if not already next to them
-        if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
-            let (player_x, player_y) = objects[PLAYER].pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER].fighter.map_or(false, |f| f.hp > 0) {
-            // monster is close enough to attack if player is alive
-            let (monster, player) = mut_two(monster_id, PLAYER, objects);
-            monster.attack(player);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((width, height))
+                    if width >= MIN_SCREEN_WIDTH && height >= MIN_SCREEN_HEIGHT =>
+                {
+                    options.screen_width = width;
+                    options.screen_height = height;
+                }
+                _ => {
+                    eprintln!(
+                        "--size expects WIDTHxHEIGHT, at least {}x{}",
+                        MIN_SCREEN_WIDTH, MIN_SCREEN_HEIGHT
+                    );
+                    process::exit(2);
+                }
+            },
+            "--map-size" => match args.next().as_deref().and_then(parse_size) {
+                Some((width, height))
+                    if (MIN_MAP_WIDTH..=MAX_MAP_WIDTH).contains(&width)
+                        && (MIN_MAP_HEIGHT..=MAX_MAP_HEIGHT).contains(&height) =>
+                {
+                    options.map_width = width;
+                    options.map_height = height;
+                }
+                _ => {
+                    eprintln!(
+                        "--map-size expects WIDTHxHEIGHT, from {}x{} up to {}x{}",
+                        MIN_MAP_WIDTH, MIN_MAP_HEIGHT, MAX_MAP_WIDTH, MAX_MAP_HEIGHT
+                    );
+                    process::exit(2);
+                }
+            },
+            "--generator" => match args.next().and_then(|name| name.parse().ok()) {
+                Some(style) => options.map_style = Some(style),
+                None => {
+                    eprintln!("--generator expects one of rooms, bsp, caves, maze or drunkard");
+                    process::exit(2);
+                }
+            },
+            "--no-animations" => options.animations = false,
+            "--no-autosave" => options.autosave = false,
+            _ => {
+                eprintln!("unknown argument: {}", arg);
+                eprintln!(
+                    "usage: roguelike [--size WIDTHxHEIGHT] [--map-size WIDTHxHEIGHT] \
+                     [--no-animations] [--no-autosave] [--generator rooms|bsp|caves|maze|drunkard]"
+                );
+                process::exit(2);
+            }
         }
     }
-}
-
-fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
-    assert!(first_index != second_index);
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-
-    if first_index < second_index {
-        (&mut first_slice[first_index], &mut second_slice[0])
-    } else {
-        (&mut second_slice[0], &mut first_slice[second_index])
-    }
-}
-
-fn player_death(player: &mut Object) {
-    // game over
-    println!("You died!");
 
-    // transform player into corpse
-    player.char = '%';
-    player.color = RED;
+    options
 }
 
-fn monster_death(monster: &mut Object) {
-    // transform it into a corpse
-    println!("{} is dead!", monster.name);
-    monster.char = '%';
-    monster.color = RED;
-    monster.blocks = false;
-    monster.fighter = None;
-    monster.ai = None;
-    monster.name = format!("remains of {}", monster.name);
-}
-
-fn render_bar(
-    panel: &mut Offscreen,
-    x: i32,
-    y: i32,
-    total_width: i32,
-    name: &str,
-    value: i32,
-    maximum: i32,
-    bar_color: Color,
-    back_color: Color,
-) {
-    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
-
-    // bar background
-    panel.set_default_background(back_color);
-    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
-
-    // bar forground
-    panel.set_default_background(bar_color);
-    if bar_width > 0 {
-        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
-    }
-
-    panel.set_default_foreground(WHITE);
-    panel.print_ex(
-        x + total_width / 2,
-        y,
-        BackgroundFlag::None,
-        TextAlignment::Center,
-        &format!("{}: {}/{}", name, value, maximum),
-    )
+/// `WIDTHxHEIGHT`, as in `80x50`
+fn parse_size(size: &str) -> Option<(i32, i32)> {
+    let (width, height) = size.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
 }
@@ -0,0 +1,2418 @@
+//! Dungeon tiles and the level generator.
+
+use crate::ai::Ai;
+use crate::items::{ammo_name, AmmoKind, Item, KEY_KINDS};
+use crate::loot::LootTable;
+use crate::object::{
+    random_direction, remove_object, DeathCallback, Equipment, Faction, Fighter, Object,
+    RenderOrder, Resistances, Slot, PLAYER,
+};
+use crate::pathfinding::DistanceMap;
+use crate::shop::new_shopkeeper;
+use crate::traps::{new_trap, TrapKind};
+use crate::vaults::{Vault, VaultTile};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use tcod::colors::*;
+
+/// What each stretch of the dungeon looks like and what lives in it, from
+/// the top down. The last theme carries on all the way to the bottom.
+const LEVEL_THEMES: &[LevelTheme] = &[
+    LevelTheme {
+        name: "upper halls",
+        levels: 1..=3,
+        palette: Palette {
+            dark_wall: Color { r: 0, g: 0, b: 100 },
+            light_wall: Color {
+                r: 130,
+                g: 110,
+                b: 50,
+            },
+            dark_ground: Color {
+                r: 50,
+                g: 50,
+                b: 150,
+            },
+            light_ground: Color {
+                r: 200,
+                g: 180,
+                b: 50,
+            },
+            water: Color {
+                r: 30,
+                g: 90,
+                b: 200,
+            },
+            water_light: Color {
+                r: 70,
+                g: 140,
+                b: 230,
+            },
+            lava: Color {
+                r: 200,
+                g: 50,
+                b: 0,
+            },
+            lava_light: Color {
+                r: 255,
+                g: 150,
+                b: 20,
+            },
+        },
+        monsters: &["orc", "archer", "bat"],
+        items: &[
+            Item::Heal,
+            Item::Food,
+            Item::PlaceTrap,
+            Item::Identify,
+            Item::Blink,
+            Item::MagicMapping,
+            Item::DetectMonsters,
+            Item::Lightning,
+            Item::Confuse,
+            Item::Sword,
+            Item::Shield,
+            Item::Sling,
+            Item::Bow,
+            ARROWS,
+            STONES,
+        ],
+        styles: &[
+            (MapStyle::Rooms, 50),
+            (MapStyle::Bsp, 40),
+            (MapStyle::Drunkard, 10),
+        ],
+    },
+    LevelTheme {
+        name: "catacombs",
+        levels: 4..=6,
+        palette: Palette {
+            dark_wall: Color { r: 40, g: 0, b: 80 },
+            light_wall: Color {
+                r: 120,
+                g: 90,
+                b: 90,
+            },
+            dark_ground: Color {
+                r: 80,
+                g: 40,
+                b: 110,
+            },
+            light_ground: Color {
+                r: 190,
+                g: 150,
+                b: 110,
+            },
+            water: Color {
+                r: 50,
+                g: 70,
+                b: 120,
+            },
+            water_light: Color {
+                r: 90,
+                g: 110,
+                b: 160,
+            },
+            lava: Color {
+                r: 200,
+                g: 50,
+                b: 0,
+            },
+            lava_light: Color {
+                r: 255,
+                g: 150,
+                b: 20,
+            },
+        },
+        monsters: &["orc", "archer", "bat", "zombie", "vampire"],
+        items: &[
+            Item::Heal,
+            Item::LiquidFire,
+            Item::Speed,
+            Item::Invisibility,
+            Item::SeeInvisible,
+            Item::Food,
+            Item::PlaceTrap,
+            Item::Identify,
+            Item::Lightning,
+            Item::Confuse,
+            Item::Fireball,
+            Item::Slow,
+            Item::Blink,
+            Item::MagicMapping,
+            Item::DetectMonsters,
+            Item::Teleport,
+            Item::Charm,
+            Item::Summon,
+            Item::Sword,
+            Item::Shield,
+            Item::Pickaxe,
+            Item::Sling,
+            Item::Bow,
+            ARROWS,
+            STONES,
+        ],
+        styles: &[
+            (MapStyle::Rooms, 30),
+            (MapStyle::Bsp, 30),
+            (MapStyle::Maze, 40),
+        ],
+    },
+    LevelTheme {
+        name: "caves",
+        levels: 7..=9,
+        palette: Palette {
+            dark_wall: Color {
+                r: 40,
+                g: 30,
+                b: 10,
+            },
+            light_wall: Color {
+                r: 120,
+                g: 90,
+                b: 50,
+            },
+            dark_ground: Color {
+                r: 60,
+                g: 50,
+                b: 30,
+            },
+            light_ground: Color {
+                r: 170,
+                g: 140,
+                b: 90,
+            },
+            water: Color {
+                r: 30,
+                g: 80,
+                b: 70,
+            },
+            water_light: Color {
+                r: 60,
+                g: 130,
+                b: 110,
+            },
+            lava: Color {
+                r: 200,
+                g: 50,
+                b: 0,
+            },
+            lava_light: Color {
+                r: 255,
+                g: 150,
+                b: 20,
+            },
+        },
+        monsters: &["orc", "archer", "bat", "ogre"],
+        items: &[
+            Item::Heal,
+            Item::LiquidFire,
+            Item::Speed,
+            Item::Invisibility,
+            Item::SeeInvisible,
+            Item::Food,
+            Item::PlaceTrap,
+            Item::Identify,
+            Item::Lightning,
+            Item::Fireball,
+            Item::Slow,
+            Item::Blink,
+            Item::MagicMapping,
+            Item::DetectMonsters,
+            Item::Teleport,
+            Item::Charm,
+            Item::Summon,
+            Item::Shield,
+            Item::Pickaxe,
+            Item::Sling,
+            STONES,
+        ],
+        styles: &[(MapStyle::Caves, 60), (MapStyle::Drunkard, 40)],
+    },
+    LevelTheme {
+        name: "inferno",
+        levels: 10..=u32::MAX,
+        palette: Palette {
+            dark_wall: Color { r: 80, g: 0, b: 0 },
+            light_wall: Color {
+                r: 150,
+                g: 50,
+                b: 20,
+            },
+            dark_ground: Color {
+                r: 110,
+                g: 30,
+                b: 20,
+            },
+            light_ground: Color {
+                r: 230,
+                g: 110,
+                b: 40,
+            },
+            water: Color {
+                r: 60,
+                g: 60,
+                b: 90,
+            },
+            water_light: Color {
+                r: 110,
+                g: 100,
+                b: 140,
+            },
+            lava: Color {
+                r: 230,
+                g: 60,
+                b: 0,
+            },
+            lava_light: Color {
+                r: 255,
+                g: 200,
+                b: 40,
+            },
+        },
+        monsters: &["orc", "archer", "zombie", "vampire", "ogre"],
+        items: &[
+            Item::Heal,
+            Item::LiquidFire,
+            Item::Speed,
+            Item::Invisibility,
+            Item::SeeInvisible,
+            Item::Food,
+            Item::PlaceTrap,
+            Item::Identify,
+            Item::Lightning,
+            Item::Confuse,
+            Item::Fireball,
+            Item::Slow,
+            Item::Blink,
+            Item::MagicMapping,
+            Item::DetectMonsters,
+            Item::Teleport,
+            Item::Charm,
+            Item::Summon,
+            Item::Sword,
+            Item::Shield,
+            Item::Pickaxe,
+            Item::Sling,
+            Item::Bow,
+            ARROWS,
+            STONES,
+        ],
+        styles: &[
+            (MapStyle::Rooms, 20),
+            (MapStyle::Bsp, 20),
+            (MapStyle::Caves, 20),
+            (MapStyle::Maze, 20),
+            (MapStyle::Drunkard, 20),
+        ],
+    },
+];
+
+// the Amulet lies on the last level, which has no stairs further down
+pub const DEEPEST_LEVEL: u32 = 12;
+pub const STAIRS_DOWN: &str = "stairs down";
+pub const STAIRS_UP: &str = "stairs up";
+pub const COLOR_DOOR: Color = Color {
+    r: 160,
+    g: 100,
+    b: 40,
+};
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 30;
+const POOL_CHANCE: f32 = 0.25;
+// share of pools that are lava rather than water
+const LAVA_POOL_CHANCE: f32 = 0.3;
+const SECRET_DOOR_CHANCE: f32 = 0.15;
+// chance of a room being swapped for a vault, if one fits
+const VAULT_CHANCE: f32 = 0.15;
+const MAX_VAULTS: usize = 2;
+// chance of a room being a shop, at most one a level
+const SHOP_CHANCE: f32 = 0.1;
+const SHOP_STOCK: usize = 6;
+// chance of a room having some gold lying about, and how much per dungeon level
+const GOLD_CHANCE: f32 = 0.3;
+// chance of a room on the first few levels having a stray dog in it
+const STRAY_DOG_CHANCE: f32 = 0.04;
+const STRAY_DOG_LEVELS: RangeInclusive<u32> = 1..=3;
+const GOLD_MIN: u32 = 5;
+const GOLD_MAX: u32 = 15;
+// how much ammo lies around in one stack
+const ARROWS: Item = Item::Ammo {
+    kind: AmmoKind::Arrow,
+    count: 10,
+};
+const STONES: Item = Item::Ammo {
+    kind: AmmoKind::Stone,
+    count: 15,
+};
+// the boss's hall on the last level, walls included
+const ARENA_WIDTH: i32 = 17;
+const ARENA_HEIGHT: i32 = 13;
+// smallest piece of the map BSP will cut off, big enough for a room
+const BSP_MIN_AREA: i32 = ROOM_MIN_SIZE + 2;
+// share of a fresh cave that starts out as wall
+const CAVE_FILL_CHANCE: f32 = 0.45;
+const CAVE_SMOOTHING_STEPS: usize = 5;
+// caves with less of the map open than this get generated again
+const CAVE_MIN_FLOOR: f32 = 0.3;
+// the drunkards keep digging until this much of the map is open
+const DRUNKARD_FLOOR: f32 = 0.35;
+// steps one drunkard takes before the next one sets off
+const DRUNKARD_LIFETIME: u32 = 400;
+// chance of a doorway into a room getting a door
+const DOOR_CHANCE: f32 = 0.5;
+// chance of each kind of key getting a locked door on a level
+const LOCKED_DOOR_CHANCE: f32 = 0.3;
+
+/// What covers the ground of a walkable tile
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Terrain {
+    Floor,
+    // wading through costs WATER_MOVE_COST extra energy
+    Water,
+    // burns anything that isn't flying for LAVA_DAMAGE each turn
+    Lava,
+}
+
+/// Whether a door lets things through
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Door {
+    Open,
+    Closed,
+    // needs the key of this kind, see KEY_KINDS
+    Locked { key: usize },
+}
+
+/// Tile for map and it's properties
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Tile {
+    pub blocked: bool,
+    pub block_site: bool,
+    pub explored: bool,
+    pub secret: bool,
+    pub door: Option<Door>,
+    pub terrain: Terrain,
+    // glyph and color of the monster seen here when the tile was last in view
+    pub last_seen_monster: Option<(char, Color)>,
+}
+
+impl Tile {
+    pub fn empty() -> Self {
+        Tile {
+            blocked: false,
+            block_site: false,
+            explored: false,
+            secret: false,
+            door: None,
+            terrain: Terrain::Floor,
+            last_seen_monster: None,
+        }
+    }
+
+    pub fn wall() -> Self {
+        Tile {
+            blocked: true,
+            block_site: true,
+            explored: false,
+            secret: false,
+            door: None,
+            terrain: Terrain::Floor,
+            last_seen_monster: None,
+        }
+    }
+
+    /// looks and acts like a wall until found with `search`
+    pub fn secret() -> Self {
+        Tile {
+            secret: true,
+            ..Tile::wall()
+        }
+    }
+
+    pub fn lock_door(&mut self, key: usize) {
+        self.close_door();
+        self.door = Some(Door::Locked { key });
+    }
+
+    /// a closed door, a wall until someone walks into it
+    pub fn door() -> Self {
+        Tile {
+            door: Some(Door::Closed),
+            ..Tile::wall()
+        }
+    }
+
+    pub fn open_door(&mut self) {
+        self.door = Some(Door::Open);
+        self.blocked = false;
+        self.block_site = false;
+    }
+
+    pub fn close_door(&mut self) {
+        self.door = Some(Door::Closed);
+        self.blocked = true;
+        self.block_site = true;
+    }
+
+    /// true if it can be walked through, if need be after opening a door
+    pub fn passable(&self) -> bool {
+        !self.blocked || self.door == Some(Door::Closed)
+    }
+
+    pub fn water() -> Self {
+        Tile {
+            terrain: Terrain::Water,
+            ..Tile::empty()
+        }
+    }
+
+    pub fn lava() -> Self {
+        Tile {
+            terrain: Terrain::Lava,
+            ..Tile::empty()
+        }
+    }
+}
+
+/// The colors a dungeon level is drawn in. Walls and ground come in and out
+/// of FOV, water and lava shimmer between their two colors.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub dark_wall: Color,
+    pub light_wall: Color,
+    pub dark_ground: Color,
+    pub light_ground: Color,
+    pub water: Color,
+    pub water_light: Color,
+    pub lava: Color,
+    pub lava_light: Color,
+}
+
+impl Palette {
+    /// blend every color `t` of the way from this palette towards `to`
+    fn lerp(&self, to: &Palette, t: f32) -> Palette {
+        Palette {
+            dark_wall: lerp(self.dark_wall, to.dark_wall, t),
+            light_wall: lerp(self.light_wall, to.light_wall, t),
+            dark_ground: lerp(self.dark_ground, to.dark_ground, t),
+            light_ground: lerp(self.light_ground, to.light_ground, t),
+            water: lerp(self.water, to.water, t),
+            water_light: lerp(self.water_light, to.water_light, t),
+            lava: lerp(self.lava, to.lava, t),
+            lava_light: lerp(self.lava_light, to.lava_light, t),
+        }
+    }
+}
+
+/// A stretch of dungeon levels with its own look, monsters, items and
+/// layouts. The monsters and items are picked from the usual random tables,
+/// leaving out anything the theme doesn't allow.
+pub struct LevelTheme {
+    pub name: &'static str,
+    pub levels: RangeInclusive<u32>,
+    pub palette: Palette,
+    pub monsters: &'static [&'static str],
+    pub items: &'static [Item],
+    // generators and their weights
+    pub styles: &'static [(MapStyle, u32)],
+}
+
+/// A value that kicks in from a given dungeon level on
+struct Transition {
+    level: u32,
+    value: u32,
+}
+
+/// Returns a value that depends on level. The table specifies what
+/// value occurs after each level, default is 0.
+fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table
+        .iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rect {
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect {
+            x1: x,
+            y1: y,
+            x2: x + w,
+            y2: y + h,
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        let center_x = (self.x1 + self.x2) / 2;
+        let center_y = (self.y1 + self.y2) / 2;
+        (center_x, center_y)
+    }
+
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        // If either of these conditions is false, then the rectangles cannot intersect.
+        // If both of these conditons are true, then the rectangles can intersect
+        // on the X axis.
+        let intersects_on_x_axis = (self.x1 <= other.x2) && (self.x2 >= other.x1);
+
+        // If either of these conditions is false, then the rectangles cannot intersect.
+        // If both of these conditons are true, then the rectangles can intersect
+        // on the Y axis.
+        let intersects_on_y_axis = (self.y1 <= other.y2) && (self.y2 >= other.y1);
+
+        // If both of these conditions are true, then the rectangles intersect.
+        intersects_on_x_axis && intersects_on_y_axis
+    }
+
+    /// true if the tile is inside the room or on its walls
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x1..=self.x2).contains(&x) && (self.y1..=self.y2).contains(&y)
+    }
+
+    pub fn distance_squared_to(&self, other: &Rect) -> i32 {
+        let (x1, y1) = self.center();
+        let (x2, y2) = other.center();
+        (x2 - x1).pow(2) + (y2 - y1).pow(2)
+    }
+}
+
+/// The level's tiles, stored row by row in one continuous Vec of
+/// width * height items. The tile on (x, y) is at `y * width + x`, so a
+/// lookup is a single index and walking the whole map stays in one region
+/// of memory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameMap {
+    width: i32,
+    height: i32,
+    tiles: Vec<Tile>,
+    // doors walked open since the last `take_opened_doors`
+    #[serde(skip)]
+    doors_opened: bool,
+}
+
+impl GameMap {
+    /// A `width` by `height` map filled with `tile`
+    pub fn new(width: i32, height: i32, tile: Tile) -> Self {
+        GameMap {
+            width,
+            height,
+            tiles: vec![tile; (width * height) as usize],
+            doors_opened: false,
+        }
+    }
+
+    /// open the door at x, y, noting it for `take_opened_doors`
+    pub fn open_door(&mut self, x: i32, y: i32) {
+        self.tile_mut(x, y).open_door();
+        self.doors_opened = true;
+    }
+
+    /// true if any door was opened by walking into it since the last call
+    pub fn take_opened_doors(&mut self) -> bool {
+        std::mem::take(&mut self.doors_opened)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    /// true for a diagonal step from (x, y) that would squeeze between two
+    /// walls, which nothing can do
+    pub fn cuts_corner(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool {
+        let wall = |x, y| !self.in_bounds(x, y) || self.tile(x, y).blocked;
+        dx != 0 && dy != 0 && wall(x + dx, y) && wall(x, y + dy)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        assert!(
+            self.in_bounds(x, y),
+            "tile ({}, {}) is outside of the {}x{} map",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        (y * self.width + x) as usize
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> &Tile {
+        &self.tiles[self.index(x, y)]
+    }
+
+    pub fn tile_mut(&mut self, x: i32, y: i32) -> &mut Tile {
+        let index = self.index(x, y);
+        &mut self.tiles[index]
+    }
+
+    /// every tile along with its position, row by row
+    pub fn iter(&self) -> impl Iterator<Item = ((i32, i32), &Tile)> {
+        let width = self.width;
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(move |(index, tile)| ((index as i32 % width, index as i32 / width), tile))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = ((i32, i32), &mut Tile)> {
+        let width = self.width;
+        self.tiles
+            .iter_mut()
+            .enumerate()
+            .map(move |(index, tile)| ((index as i32 % width, index as i32 / width), tile))
+    }
+}
+
+/// One way of laying out a level. A generator carves out the map and places
+/// the player, the stairs, the monsters and the items; `make_map` adds the
+/// locked doors and traps on top. Along with the map comes the list of rooms
+/// it was built from, empty for the layouts that have none.
+trait MapGenerator {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>);
+}
+
+/// rectangular rooms joined by L-shaped tunnels, with a vault standing in
+/// for a room now and then
+struct RoomsAndCorridors<'a> {
+    vaults: &'a [Vault],
+}
+
+/// the map cut up by binary space partitioning, a room in every piece
+struct Bsp;
+
+/// open, organic caverns grown with a cellular automaton
+struct Caves;
+
+/// a perfect maze dug by a recursive backtracker: one way between any two
+/// spots and plenty of dead ends
+struct Maze;
+
+/// winding tunnels dug by drunkards staggering about at random
+struct DrunkardsWalk;
+
+/// The generators to pick from with `--generator`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapStyle {
+    Rooms,
+    Bsp,
+    Caves,
+    Maze,
+    Drunkard,
+}
+
+impl FromStr for MapStyle {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "rooms" => Ok(MapStyle::Rooms),
+            "bsp" => Ok(MapStyle::Bsp),
+            "caves" => Ok(MapStyle::Caves),
+            "maze" => Ok(MapStyle::Maze),
+            "drunkard" => Ok(MapStyle::Drunkard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Generate a level. All the randomness comes from `rng`, so a seeded rng
+/// always builds the same map. Without a `style` the generator is picked at
+/// random, see `style_chances`.
+pub fn make_map<R: Rng>(
+    width: i32,
+    height: i32,
+    level: u32,
+    style: Option<MapStyle>,
+    vaults: &[Vault],
+    objects: &mut Vec<Object>,
+    rng: &mut R,
+) -> GameMap {
+    generate_level(width, height, level, style, vaults, objects, rng).0
+}
+
+/// `make_map`, handing back the rooms as well for the tests to check
+fn generate_level<R: Rng>(
+    width: i32,
+    height: i32,
+    level: u32,
+    style: Option<MapStyle>,
+    vaults: &[Vault],
+    objects: &mut Vec<Object>,
+    rng: &mut R,
+) -> (GameMap, Vec<Rect>) {
+    let style = style.unwrap_or_else(|| style_chances(level).roll(rng).unwrap_or(MapStyle::Rooms));
+    let (mut map, rooms) = match style {
+        MapStyle::Rooms => {
+            RoomsAndCorridors { vaults }.generate(width, height, level, objects, rng)
+        }
+        MapStyle::Bsp => Bsp.generate(width, height, level, objects, rng),
+        MapStyle::Caves => Caves.generate(width, height, level, objects, rng),
+        MapStyle::Maze => Maze.generate(width, height, level, objects, rng),
+        MapStyle::Drunkard => DrunkardsWalk.generate(width, height, level, objects, rng),
+    };
+
+    // on the first level these lead out of the dungeon
+    place_up_stairs(objects);
+    if level == DEEPEST_LEVEL {
+        create_arena(&mut map, objects);
+    }
+    // after the stairs, so no key or trap ends up underneath them
+    lock_doors(&mut map, objects, rng);
+    place_traps(level, &map, objects, rng);
+
+    (map, rooms)
+}
+
+/// generator random table, straight from the level's theme
+fn style_chances(level: u32) -> LootTable<MapStyle> {
+    LootTable::new(level_theme(level).styles.to_vec())
+}
+
+/// put the stairs down at (x, y)
+fn place_stairs(x: i32, y: i32, objects: &mut Vec<Object>) {
+    let mut stairs = Object::new(x, y, '>', STAIRS_DOWN, WHITE, false);
+    stairs.always_visible = true;
+    stairs.render_order = RenderOrder::Floor;
+    objects.push(stairs);
+}
+
+/// The last level has no stairs down. Where they would have been, clear out
+/// a big pillared hall and put the boss in it, with the Amulet on it.
+fn create_arena(map: &mut GameMap, objects: &mut Vec<Object>) {
+    let Some(stairs_id) = objects.iter().position(|object| object.name == STAIRS_DOWN) else {
+        return;
+    };
+    let stairs = remove_object(stairs_id, objects);
+
+    let width = cmp::min(ARENA_WIDTH, map.width()) - 1;
+    let height = cmp::min(ARENA_HEIGHT, map.height()) - 1;
+    let x = (stairs.x - width / 2).clamp(0, map.width() - 1 - width);
+    let y = (stairs.y - height / 2).clamp(0, map.height() - 1 - height);
+    let arena = Rect::new(x, y, width, height);
+    // only ever adds floor around tiles that were already reachable, so the
+    // rest of the level stays joined up
+    create_room(arena, map);
+    // something to duck behind when the bolts start flying
+    for (pillar_x, pillar_y) in [
+        (x + width / 4, y + height / 4),
+        (x + width - width / 4, y + height / 4),
+        (x + width / 4, y + height - height / 4),
+        (x + width - width / 4, y + height - height / 4),
+    ] {
+        *map.tile_mut(pillar_x, pillar_y) = Tile::wall();
+    }
+
+    // the boss has the hall to itself
+    let in_the_way: Vec<_> = (PLAYER + 1..objects.len())
+        .filter(|&id| {
+            objects[id]
+                .tiles()
+                .any(|(tile_x, tile_y)| arena.contains(tile_x, tile_y))
+        })
+        .collect();
+    for id in in_the_way.into_iter().rev() {
+        remove_object(id, objects);
+    }
+    let (center_x, center_y) = arena.center();
+    let mut boss = new_monster("lich", center_x, center_y);
+    boss.alive = true;
+    objects.push(boss);
+}
+
+/// put the stairs back up where the player arrives
+fn place_up_stairs(objects: &mut Vec<Object>) {
+    let (x, y) = objects[PLAYER].pos();
+    let mut stairs = Object::new(x, y, '<', STAIRS_UP, WHITE, false);
+    stairs.always_visible = true;
+    stairs.render_order = RenderOrder::Floor;
+    objects.push(stairs);
+}
+
+impl MapGenerator for RoomsAndCorridors<'_> {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>) {
+        // fill map with "blocked" tiles
+        let mut map = GameMap::new(width, height, Tile::wall());
+
+        let mut rooms = vec![];
+        let mut num_vaults = 0;
+        let mut has_shop = false;
+        for _ in 0..MAX_ROOMS {
+            // never in the player's starting room
+            let vault = if !rooms.is_empty()
+                && num_vaults < MAX_VAULTS
+                && rng.gen::<f32>() < VAULT_CHANCE
+            {
+                let fitting: Vec<_> = self
+                    .vaults
+                    .iter()
+                    .filter(|vault| vault.width < width && vault.height < height)
+                    .collect();
+                rng.choose(&fitting).copied()
+            } else {
+                None
+            };
+
+            // a room's walls are its outermost tiles, which for a vault are
+            // the edges of its drawing
+            let (w, h) = match vault {
+                Some(vault) => (vault.width - 1, vault.height - 1),
+                None => (
+                    rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1),
+                    rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1),
+                ),
+            };
+
+            let x = rng.gen_range(0, width - w);
+            let y = rng.gen_range(0, height - h);
+
+            let new_room = Rect::new(x, y, w, h);
+
+            let is_valid_location = !rooms
+                .iter()
+                .any(|other_room| new_room.intersects_with(other_room));
+
+            if is_valid_location {
+                if let Some(vault) = vault {
+                    create_vault(vault, new_room, level, &mut map, objects, rng);
+                    num_vaults += 1;
+                } else if !rooms.is_empty() && !has_shop && rng.gen::<f32>() < SHOP_CHANCE {
+                    create_room(new_room, &mut map);
+                    create_shop(new_room, level, objects, rng);
+                    has_shop = true;
+                } else {
+                    create_room(new_room, &mut map);
+                    // keep the player's starting room clear of pools
+                    if !rooms.is_empty() && rng.gen::<f32>() < POOL_CHANCE {
+                        create_pool(new_room, &mut map, rng);
+                    }
+                    place_objects(new_room, level, &map, objects, rng);
+                }
+
+                let (new_x, new_y) = new_room.center();
+
+                if rooms.is_empty() {
+                    // this is the first room, where the player starts
+                    objects[PLAYER].set_pos(new_x, new_y);
+                } else {
+                    // hook up to whichever carved room is closest, so every room
+                    // stays reachable from the first one
+                    let nearest_room = rooms
+                        .iter()
+                        .min_by_key(|room| room.distance_squared_to(&new_room))
+                        .copied()
+                        .unwrap();
+                    connect_rooms(nearest_room, new_room, &rooms, &mut map, rng);
+
+                    if rng.gen::<f32>() < SECRET_DOOR_CHANCE {
+                        hide_room_entrances(new_room, &mut map);
+                    }
+                }
+
+                // add the new room to the list of rooms
+                rooms.push(new_room);
+            }
+        }
+
+        // with all the tunnels dug, put doors in the openings they left
+        for &room in &rooms {
+            place_doors(room, &mut map, rng);
+        }
+
+        // create stairs at the center of the last room
+        let (last_room_x, last_room_y) = rooms[rooms.len() - 1].center();
+        place_stairs(last_room_x, last_room_y, objects);
+
+        (map, rooms)
+    }
+}
+
+impl MapGenerator for Bsp {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>) {
+        let mut map = GameMap::new(width, height, Tile::wall());
+        let mut rooms = vec![];
+        // the outermost row and column stay wall
+        split_and_carve(
+            Rect::new(0, 0, width - 1, height - 1),
+            &mut map,
+            &mut rooms,
+            rng,
+        );
+
+        for (i, &room) in rooms.iter().enumerate() {
+            // keep the player's starting room clear of pools
+            if i > 0 && rng.gen::<f32>() < POOL_CHANCE {
+                create_pool(room, &mut map, rng);
+            }
+            place_objects(room, level, &map, objects, rng);
+        }
+
+        let (player_x, player_y) = rooms[0].center();
+        objects[PLAYER].set_pos(player_x, player_y);
+        let (stairs_x, stairs_y) = rooms[rooms.len() - 1].center();
+        place_stairs(stairs_x, stairs_y, objects);
+
+        (map, rooms)
+    }
+}
+
+/// Cut `area` in two across its longer side, and again for each half until
+/// the pieces are too small, then carve a room into every piece. On the way
+/// back up each pair of halves is joined by a tunnel between their closest
+/// rooms, so the whole map ends up connected.
+fn split_and_carve<R: Rng>(area: Rect, map: &mut GameMap, rooms: &mut Vec<Rect>, rng: &mut R) {
+    let (w, h) = (area.x2 - area.x1, area.y2 - area.y1);
+    let split_x = match (w >= 2 * BSP_MIN_AREA, h >= 2 * BSP_MIN_AREA) {
+        (false, false) => {
+            let room_w = rng.gen_range(ROOM_MIN_SIZE, cmp::min(ROOM_MAX_SIZE, w) + 1);
+            let room_h = rng.gen_range(ROOM_MIN_SIZE, cmp::min(ROOM_MAX_SIZE, h) + 1);
+            let x = rng.gen_range(area.x1, area.x2 - room_w + 1);
+            let y = rng.gen_range(area.y1, area.y2 - room_h + 1);
+            let room = Rect::new(x, y, room_w, room_h);
+            create_room(room, map);
+            rooms.push(room);
+            return;
+        }
+        (true, false) => true,
+        (false, true) => false,
+        (true, true) => w > h || (w == h && rng.gen()),
+    };
+
+    let (first, second) = if split_x {
+        let at = rng.gen_range(BSP_MIN_AREA, w - BSP_MIN_AREA + 1);
+        (
+            Rect::new(area.x1, area.y1, at, h),
+            Rect::new(area.x1 + at, area.y1, w - at, h),
+        )
+    } else {
+        let at = rng.gen_range(BSP_MIN_AREA, h - BSP_MIN_AREA + 1);
+        (
+            Rect::new(area.x1, area.y1, w, at),
+            Rect::new(area.x1, area.y1 + at, w, h - at),
+        )
+    };
+
+    let start = rooms.len();
+    split_and_carve(first, map, rooms, rng);
+    let middle = rooms.len();
+    split_and_carve(second, map, rooms, rng);
+
+    let (a, b) = rooms[start..middle]
+        .iter()
+        .flat_map(|&a| rooms[middle..].iter().map(move |&b| (a, b)))
+        .min_by_key(|(a, b)| a.distance_squared_to(b))
+        .unwrap();
+    connect_rooms(a, b, rooms, map, rng);
+}
+
+impl MapGenerator for Caves {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>) {
+        // a bad roll can leave only a few small pockets, so try again
+        let map = loop {
+            let mut map = random_fill(width, height, rng);
+            for _ in 0..CAVE_SMOOTHING_STEPS {
+                map = smooth_cave(&map);
+            }
+            let floor = keep_largest_cave(&mut map);
+            if floor as f32 >= CAVE_MIN_FLOOR * (width * height) as f32 {
+                break map;
+            }
+        };
+
+        populate_open_level(&map, level, objects, rng);
+        (map, vec![])
+    }
+}
+
+/// Stock a level without rooms: the player starts anywhere on the floor, the
+/// stairs go as far off as it gets, and monsters and items are spread out
+/// one room-sized patch at a time
+fn populate_open_level<R: Rng>(map: &GameMap, level: u32, objects: &mut Vec<Object>, rng: &mut R) {
+    let (width, height) = (map.width(), map.height());
+    let floor: Vec<_> = map
+        .iter()
+        .filter(|(_, tile)| !tile.blocked)
+        .map(|(position, _)| position)
+        .collect();
+    let &(player_x, player_y) = rng.choose(&floor).unwrap();
+    objects[PLAYER].set_pos(player_x, player_y);
+    let distances = DistanceMap::new(map, (player_x, player_y));
+    let &(stairs_x, stairs_y) = floor
+        .iter()
+        .max_by_key(|&&(x, y)| distances.distance(x, y))
+        .unwrap();
+
+    for x in (0..width).step_by(ROOM_MAX_SIZE as usize) {
+        for y in (0..height).step_by(ROOM_MAX_SIZE as usize) {
+            let w = cmp::min(ROOM_MAX_SIZE, width - 1 - x);
+            let h = cmp::min(ROOM_MAX_SIZE, height - 1 - y);
+            // a sliver along the edge has no inside to put anything in
+            if w > 1 && h > 1 {
+                place_objects(Rect::new(x, y, w, h), level, map, objects, rng);
+            }
+        }
+    }
+    place_stairs(stairs_x, stairs_y, objects);
+}
+
+impl MapGenerator for Maze {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>) {
+        let mut map = GameMap::new(width, height, Tile::wall());
+
+        // the maze's cells sit on odd coordinates, with a wall or a passage
+        // between each pair of neighbours
+        let cells_x = (width - 1) / 2;
+        let cells_y = (height - 1) / 2;
+        let mut visited = vec![false; (cells_x * cells_y) as usize];
+        let start = (rng.gen_range(0, cells_x), rng.gen_range(0, cells_y));
+        visited[(start.1 * cells_x + start.0) as usize] = true;
+        *map.tile_mut(start.0 * 2 + 1, start.1 * 2 + 1) = Tile::empty();
+
+        // walk to a random unvisited neighbour, knocking down the wall on the
+        // way, and back up to the last cell with any left once stuck
+        let mut stack = vec![start];
+        while let Some(&(x, y)) = stack.last() {
+            let mut unvisited: Vec<_> = [(0, -1), (0, 1), (-1, 0), (1, 0)]
+                .iter()
+                .map(|&(dx, dy)| (x + dx, y + dy))
+                .filter(|&(next_x, next_y)| {
+                    next_x >= 0
+                        && next_y >= 0
+                        && next_x < cells_x
+                        && next_y < cells_y
+                        && !visited[(next_y * cells_x + next_x) as usize]
+                })
+                .collect();
+            rng.shuffle(&mut unvisited);
+            match unvisited.first() {
+                Some(&(next_x, next_y)) => {
+                    visited[(next_y * cells_x + next_x) as usize] = true;
+                    *map.tile_mut(x + next_x + 1, y + next_y + 1) = Tile::empty();
+                    *map.tile_mut(next_x * 2 + 1, next_y * 2 + 1) = Tile::empty();
+                    stack.push((next_x, next_y));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        populate_open_level(&map, level, objects, rng);
+        (map, vec![])
+    }
+}
+
+impl MapGenerator for DrunkardsWalk {
+    fn generate<R: Rng>(
+        &self,
+        width: i32,
+        height: i32,
+        level: u32,
+        objects: &mut Vec<Object>,
+        rng: &mut R,
+    ) -> (GameMap, Vec<Rect>) {
+        let mut map = GameMap::new(width, height, Tile::wall());
+        let target = (DRUNKARD_FLOOR * (width * height) as f32) as usize;
+
+        let mut floor = vec![(width / 2, height / 2)];
+        *map.tile_mut(width / 2, height / 2) = Tile::empty();
+        while floor.len() < target {
+            // every drunkard sets off from somewhere already dug, so the
+            // tunnels all join up
+            let (mut x, mut y) = *rng.choose(&floor).unwrap();
+            for _ in 0..DRUNKARD_LIFETIME {
+                let (dx, dy) = *rng.choose(&[(0, -1), (0, 1), (-1, 0), (1, 0)]).unwrap();
+                // keep the outer edge solid
+                if x + dx < 1 || y + dy < 1 || x + dx >= width - 1 || y + dy >= height - 1 {
+                    continue;
+                }
+                x += dx;
+                y += dy;
+                if map.tile(x, y).blocked {
+                    *map.tile_mut(x, y) = Tile::empty();
+                    floor.push((x, y));
+                }
+            }
+        }
+
+        populate_open_level(&map, level, objects, rng);
+        (map, vec![])
+    }
+}
+
+/// Start a cave off as noise: every tile but the outer edge has an even-ish
+/// chance of being wall
+fn random_fill<R: Rng>(width: i32, height: i32, rng: &mut R) -> GameMap {
+    let mut map = GameMap::new(width, height, Tile::wall());
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            if rng.gen::<f32>() >= CAVE_FILL_CHANCE {
+                *map.tile_mut(x, y) = Tile::empty();
+            }
+        }
+    }
+    map
+}
+
+/// One cellular automaton step: tiles mostly surrounded by wall turn to wall,
+/// tiles mostly surrounded by floor open up, and ties stay as they are.
+/// Clumps of noise smooth out into caves over a few steps.
+fn smooth_cave(map: &GameMap) -> GameMap {
+    let mut smoothed = map.clone();
+    for y in 1..map.height() - 1 {
+        for x in 1..map.width() - 1 {
+            let walls = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                .filter(|&neighbour| neighbour != (x, y))
+                .filter(|&(nx, ny)| map.tile(nx, ny).blocked)
+                .count();
+            if walls > 4 {
+                *smoothed.tile_mut(x, y) = Tile::wall();
+            } else if walls < 4 {
+                *smoothed.tile_mut(x, y) = Tile::empty();
+            }
+        }
+    }
+    smoothed
+}
+
+/// Flood fill every pocket of floor and wall up all but the biggest one, so
+/// the whole cave can be reached. Returns its size in tiles.
+fn keep_largest_cave(map: &mut GameMap) -> usize {
+    let width = map.width();
+    let mut counted = vec![false; (width * map.height()) as usize];
+    let mut largest: Option<((i32, i32), usize)> = None;
+    for ((x, y), tile) in map.iter() {
+        if tile.blocked || counted[(y * width + x) as usize] {
+            continue;
+        }
+        let region = DistanceMap::new(map, (x, y));
+        let mut size = 0;
+        for ((other_x, other_y), _) in map.iter() {
+            if region.distance(other_x, other_y).is_some() {
+                counted[(other_y * width + other_x) as usize] = true;
+                size += 1;
+            }
+        }
+        if largest.is_none_or(|(_, largest_size)| size > largest_size) {
+            largest = Some(((x, y), size));
+        }
+    }
+
+    let ((cave_x, cave_y), size) = match largest {
+        Some(largest) => largest,
+        None => return 0,
+    };
+    let cave = DistanceMap::new(map, (cave_x, cave_y));
+    for ((x, y), tile) in map.iter_mut() {
+        if cave.distance(x, y).is_none() {
+            *tile = Tile::wall();
+        }
+    }
+    size
+}
+
+/// Percentage of the walkable tiles on the map the player has seen
+pub fn explored_percent(map: &GameMap) -> f32 {
+    let floor = map
+        .iter()
+        .map(|(_, tile)| tile)
+        .filter(|tile| !tile.blocked);
+    let (explored, total) = floor.fold((0, 0), |(explored, total), tile| {
+        (explored + tile.explored as i32, total + 1)
+    });
+
+    if total == 0 {
+        return 0.0;
+    }
+    explored as f32 / total as f32 * 100.0
+}
+
+/// the theme covering a dungeon level
+pub fn level_theme(level: u32) -> &'static LevelTheme {
+    LEVEL_THEMES
+        .iter()
+        .find(|theme| theme.levels.contains(&level))
+        .unwrap_or(&LEVEL_THEMES[LEVEL_THEMES.len() - 1])
+}
+
+/// Find the palette for a dungeon level. Each theme's palette is where its
+/// first level starts, and the levels after it drift towards the next theme's
+/// colors. Levels in the last theme keep its colors.
+pub fn level_palette(level: u32) -> Palette {
+    match palette_blend(level) {
+        (theme, Some((next, t))) => theme.palette.lerp(&next.palette, t),
+        (theme, None) => theme.palette,
+    }
+}
+
+/// The theme a level's palette starts from, and the theme it drifts
+/// towards with how far along the way it is, if there is one after it
+fn palette_blend(level: u32) -> (&'static LevelTheme, Option<(&'static LevelTheme, f32)>) {
+    let index = LEVEL_THEMES
+        .iter()
+        .position(|theme| theme.levels.contains(&level))
+        .unwrap_or(LEVEL_THEMES.len() - 1);
+    let theme = &LEVEL_THEMES[index];
+    let next = LEVEL_THEMES.get(index + 1).map(|next| {
+        let from_level = *theme.levels.start();
+        let to_level = *next.levels.start();
+        let t = level.saturating_sub(from_level) as f32 / (to_level - from_level) as f32;
+        (next, t)
+    });
+    (theme, next)
+}
+
+fn create_room(room: Rect, map: &mut GameMap) {
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            *map.tile_mut(x, y) = Tile::empty();
+        }
+    }
+}
+
+/// Stamp `vault` onto the map over `room`, then fill its monster and item
+/// markers from the level's usual random tables
+fn create_vault<R: Rng>(
+    vault: &Vault,
+    room: Rect,
+    level: u32,
+    map: &mut GameMap,
+    objects: &mut Vec<Object>,
+    rng: &mut R,
+) {
+    let monster_chances = monster_chances(level);
+    let item_chances = item_chances(level);
+
+    // the whole floor goes down before anything stands on it, so big
+    // monsters see all the room they have
+    let mut markers = vec![];
+    for vault_y in 0..vault.height {
+        for vault_x in 0..vault.width {
+            let (x, y) = (room.x1 + vault_x, room.y1 + vault_y);
+            let tile = vault.tile(vault_x, vault_y);
+            match tile {
+                // the map starts out solid, and walling over a tunnel some
+                // earlier rooms were joined by would cut them off
+                VaultTile::Wall => (),
+                VaultTile::Water => *map.tile_mut(x, y) = Tile::water(),
+                VaultTile::Floor | VaultTile::Monster | VaultTile::Item => {
+                    *map.tile_mut(x, y) = Tile::empty()
+                }
+            }
+            if tile == VaultTile::Monster || tile == VaultTile::Item {
+                markers.push((tile, x, y));
+            }
+        }
+    }
+
+    for (tile, x, y) in markers {
+        if tile == VaultTile::Monster {
+            if let Some(name) = monster_chances.roll(rng) {
+                add_monster(new_monster(name, x, y), room, map, objects, rng);
+            }
+        } else if !is_blocked(x, y, map, objects) {
+            if let Some(item) = item_chances.roll(rng) {
+                objects.push(new_item(item, x, y));
+            }
+        }
+    }
+}
+
+/// Put a shopkeeper in the room, with a few things from the level's item
+/// table for sale. Shops get no monsters or loot of their own.
+fn create_shop<R: Rng>(room: Rect, level: u32, objects: &mut Vec<Object>, rng: &mut R) {
+    let item_chances = item_chances(level);
+    let stock = (0..SHOP_STOCK)
+        .filter_map(|_| item_chances.roll(rng))
+        .collect();
+    // in a corner, out of the way of the tunnels that meet in the middle
+    objects.push(new_shopkeeper(room.x1 + 1, room.y1 + 1, stock));
+}
+
+/// Carve an L-shaped tunnel between the centers of two rooms. Of the two
+/// possible bends, pick the one that cuts through fewer of the other rooms.
+fn connect_rooms<R: Rng>(a: Rect, b: Rect, rooms: &[Rect], map: &mut GameMap, rng: &mut R) {
+    let (a_x, a_y) = a.center();
+    let (b_x, b_y) = b.center();
+
+    // horizontal first bends at (b_x, a_y), vertical first at (a_x, b_y)
+    let horizontal_overlap = tunnel_overlap(a, b, (b_x, a_y), rooms);
+    let vertical_overlap = tunnel_overlap(a, b, (a_x, b_y), rooms);
+    let horizontal_first = match horizontal_overlap.cmp(&vertical_overlap) {
+        cmp::Ordering::Less => true,
+        cmp::Ordering::Greater => false,
+        // coinflip (50/50 bool)
+        cmp::Ordering::Equal => rng.gen(),
+    };
+
+    if horizontal_first {
+        create_h_tunnel(a_x, b_x, a_y, map);
+        create_v_tunnel(a_y, b_y, b_x, map);
+    } else {
+        create_v_tunnel(a_y, b_y, a_x, map);
+        create_h_tunnel(a_x, b_x, b_y, map);
+    }
+}
+
+/// Count the tiles of an L-shaped tunnel from `a` to `b` bending at `corner`
+/// which land inside (or on the walls of) any room other than `a` and `b`.
+fn tunnel_overlap(a: Rect, b: Rect, corner: (i32, i32), rooms: &[Rect]) -> usize {
+    let (corner_x, corner_y) = corner;
+    let legs = [a.center(), b.center()];
+
+    let mut tiles = vec![];
+    for &(x, y) in &legs {
+        // each leg is a straight line, so one of these ranges is a single tile
+        for tile_x in cmp::min(x, corner_x)..=cmp::max(x, corner_x) {
+            for tile_y in cmp::min(y, corner_y)..=cmp::max(y, corner_y) {
+                tiles.push((tile_x, tile_y));
+            }
+        }
+    }
+
+    tiles
+        .iter()
+        .filter(|&&(x, y)| {
+            rooms
+                .iter()
+                .any(|room| *room != a && *room != b && room.contains(x, y))
+        })
+        .count()
+}
+
+/// Turn every tunnel opening in the room's walls back into a (secret) wall,
+/// so the room can only be reached once the player searches for it
+fn hide_room_entrances(room: Rect, map: &mut GameMap) {
+    for x in room.x1..=room.x2 {
+        for y in room.y1..=room.y2 {
+            let on_wall = x == room.x1 || x == room.x2 || y == room.y1 || y == room.y2;
+            if on_wall && !map.tile(x, y).blocked {
+                *map.tile_mut(x, y) = Tile::secret();
+            }
+        }
+    }
+}
+
+/// Put doors in some of the room's doorways: single tile openings in its
+/// walls with wall on either side, where a tunnel comes in
+fn place_doors<R: Rng>(room: Rect, map: &mut GameMap, rng: &mut R) {
+    for x in room.x1..=room.x2 {
+        for y in room.y1..=room.y2 {
+            let in_side_wall = (x == room.x1 || x == room.x2) && y != room.y1 && y != room.y2;
+            let in_end_wall = (y == room.y1 || y == room.y2) && x != room.x1 && x != room.x2;
+            let doorway = !map.tile(x, y).blocked
+                && if in_side_wall {
+                    map.tile(x, y - 1).blocked && map.tile(x, y + 1).blocked
+                } else if in_end_wall {
+                    map.tile(x - 1, y).blocked && map.tile(x + 1, y).blocked
+                } else {
+                    false
+                };
+            if doorway && rng.gen::<f32>() < DOOR_CHANCE {
+                *map.tile_mut(x, y) = Tile::door();
+            }
+        }
+    }
+}
+
+/// Lock some of the doors and leave each one's key lying somewhere the
+/// player can get to from where they start without any keys
+fn lock_doors<R: Rng>(map: &mut GameMap, objects: &mut Vec<Object>, rng: &mut R) {
+    let mut doors: Vec<_> = map
+        .iter()
+        .filter(|(_, tile)| tile.door == Some(Door::Closed))
+        .map(|(position, _)| position)
+        .collect();
+    rng.shuffle(&mut doors);
+
+    for (kind, &(name, color)) in KEY_KINDS.iter().enumerate() {
+        if rng.gen::<f32>() >= LOCKED_DOOR_CHANCE {
+            continue;
+        }
+        let (door_x, door_y) = match doors.pop() {
+            Some(door) => door,
+            None => break,
+        };
+        map.tile_mut(door_x, door_y).lock_door(kind);
+
+        // locked doors don't count as passable, so this is everywhere the
+        // player can go with the doors locked so far
+        let reachable = DistanceMap::new(map, objects[PLAYER].pos());
+        let spots: Vec<_> = map
+            .iter()
+            .filter(|&((x, y), tile)| {
+                reachable.distance(x, y).is_some()
+                    && tile.door.is_none()
+                    && tile.terrain == Terrain::Floor
+                    && (x, y) != objects[PLAYER].pos()
+                    && !objects.iter().any(|object| object.occupies(x, y))
+            })
+            .map(|(position, _)| position)
+            .collect();
+        match rng.choose(&spots) {
+            Some(&(x, y)) => {
+                let mut key = Object::new(x, y, '-', name, color, false);
+                key.item = Some(Item::Key { kind });
+                objects.push(key);
+            }
+            // nowhere to leave the key, so leave the door unlocked instead
+            None => map.tile_mut(door_x, door_y).close_door(),
+        }
+    }
+}
+
+/// Flood a small, roughly round patch of the room with water or lava
+fn create_pool<R: Rng>(room: Rect, map: &mut GameMap, rng: &mut R) {
+    let tile = if rng.gen::<f32>() < LAVA_POOL_CHANCE {
+        Tile::lava()
+    } else {
+        Tile::water()
+    };
+    let radius = rng.gen_range(1, 3);
+    let center_x = rng.gen_range(room.x1 + 1, room.x2);
+    let center_y = rng.gen_range(room.y1 + 1, room.y2);
+
+    // stay inside the room so the pool never eats into a wall
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            if (x - center_x).pow(2) + (y - center_y).pow(2) <= radius * radius {
+                *map.tile_mut(x, y) = tile;
+            }
+        }
+    }
+}
+
+fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut GameMap) {
+    // horizontal tunnel. `min()` and `max()` are used in case `x1 > x2`
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        *map.tile_mut(x, y) = Tile::empty();
+    }
+}
+
+fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut GameMap) {
+    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
+        *map.tile_mut(x, y) = Tile::empty();
+    }
+}
+
+/// Hide traps on open floor anywhere on the level, in rooms and corridors
+/// alike, but never where the player starts
+fn place_traps<R: Rng>(level: u32, map: &GameMap, objects: &mut Vec<Object>, rng: &mut R) {
+    let num_traps = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 2 },
+            Transition { level: 3, value: 4 },
+            Transition { level: 6, value: 6 },
+        ],
+        level,
+    );
+
+    // trap random table
+    let trap_chances = LootTable::new(vec![
+        (TrapKind::Spikes, 50),
+        (TrapKind::Dart, 30),
+        (
+            TrapKind::Teleport,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 20,
+                }],
+                level,
+            ),
+        ),
+    ]);
+
+    let mut spots: Vec<_> = map
+        .iter()
+        .filter(|&((x, y), tile)| {
+            !tile.blocked
+                && tile.door.is_none()
+                && tile.terrain == Terrain::Floor
+                && !objects.iter().any(|object| object.occupies(x, y))
+        })
+        .map(|(position, _)| position)
+        .collect();
+    rng.shuffle(&mut spots);
+    for &(x, y) in spots.iter().take(num_traps as usize) {
+        if let Some(kind) = trap_chances.roll(rng) {
+            objects.push(new_trap(x, y, kind));
+        }
+    }
+}
+
+fn place_objects<R: Rng>(
+    room: Rect,
+    level: u32,
+    map: &GameMap,
+    objects: &mut Vec<Object>,
+    rng: &mut R,
+) {
+    // maximum number of monsters per room
+    let max_monsters = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 2 },
+            Transition { level: 4, value: 3 },
+            Transition { level: 6, value: 5 },
+        ],
+        level,
+    );
+
+    let monster_chances = monster_chances(level);
+
+    // choose random number of monsters
+    let num_monsters = rng.gen_range(0, max_monsters + 1);
+
+    for _ in 0..num_monsters {
+        // choose random spot for this monster
+        // gen_range is inclusive for arg1 and exclusive for arg2,
+        // so we need to add 1 to the first argument to not put a
+        // monster in a wall.
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
+
+        if let Some(name) = monster_chances.roll(rng) {
+            add_monster(new_monster(name, x, y), room, map, objects, rng);
+        }
+    }
+
+    if STRAY_DOG_LEVELS.contains(&level) && rng.gen::<f32>() < STRAY_DOG_CHANCE {
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
+        add_monster(new_monster("stray dog", x, y), room, map, objects, rng);
+    }
+
+    // maximum number of items per room
+    let max_items = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 1 },
+            Transition { level: 4, value: 2 },
+        ],
+        level,
+    );
+
+    let item_chances = item_chances(level);
+
+    // choose random number of items
+    let num_items = rng.gen_range(0, max_items + 1);
+
+    for _ in 0..num_items {
+        // choose random spot for this item
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
+
+        // only place it if the tile is dry, open floor
+        if map.tile(x, y).terrain == Terrain::Floor && !is_blocked(x, y, map, objects) {
+            if let Some(item) = item_chances.roll(rng) {
+                objects.push(new_item(item, x, y));
+            }
+        }
+    }
+
+    // sometimes a pile of gold, bigger the deeper it lies
+    if rng.gen::<f32>() < GOLD_CHANCE {
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
+        if map.tile(x, y).terrain == Terrain::Floor && !is_blocked(x, y, map, objects) {
+            let amount = rng.gen_range(GOLD_MIN, GOLD_MAX + 1) * level;
+            objects.push(new_item(Item::Gold { amount }, x, y));
+        }
+    }
+}
+
+/// monster random table, how likely each monster is on the given level,
+/// zero for any the level's theme leaves out
+fn monster_chances(level: u32) -> LootTable<&'static str> {
+    let archer_chance = from_dungeon_level(
+        &[
+            Transition {
+                level: 1,
+                value: 10,
+            },
+            Transition {
+                level: 4,
+                value: 20,
+            },
+        ],
+        level,
+    );
+    // vampires only come out on the deeper floors
+    let vampire_chance = from_dungeon_level(
+        &[
+            Transition {
+                level: 3,
+                value: 15,
+            },
+            Transition {
+                level: 5,
+                value: 30,
+            },
+            Transition {
+                level: 7,
+                value: 60,
+            },
+        ],
+        level,
+    );
+    let ogre_chance = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 5 },
+            Transition {
+                level: 5,
+                value: 15,
+            },
+        ],
+        level,
+    );
+    let zombie_chance = from_dungeon_level(
+        &[Transition {
+            level: 4,
+            value: 20,
+        }],
+        level,
+    );
+    let mut chances = LootTable::new(vec![
+        ("orc", 80),
+        ("archer", archer_chance),
+        ("bat", 15),
+        ("zombie", zombie_chance),
+        ("vampire", vampire_chance),
+        ("ogre", ogre_chance),
+    ]);
+    let allowed = level_theme(level).monsters;
+    chances.retain(|name| allowed.contains(name));
+    chances
+}
+
+/// Bring a monster from the level's random table onto a free floor tile
+/// where `unseen` says the player can't see it, awake and already hunting
+pub fn spawn_hunter<R: Rng>(
+    level: u32,
+    map: &GameMap,
+    objects: &mut Vec<Object>,
+    unseen: impl Fn(i32, i32) -> bool,
+    rng: &mut R,
+) {
+    let Some(name) = monster_chances(level).roll(rng) else {
+        return;
+    };
+    let (x, y) = (
+        rng.gen_range(0, map.width()),
+        rng.gen_range(0, map.height()),
+    );
+    let mut monster = new_monster(name, x, y);
+    let fits = monster.tiles().all(|(tile_x, tile_y)| {
+        map.in_bounds(tile_x, tile_y)
+            && map.tile(tile_x, tile_y).terrain == Terrain::Floor
+            && !is_blocked(tile_x, tile_y, map, objects)
+            && unseen(tile_x, tile_y)
+    });
+    // no luck this time, there will be others
+    if !fits {
+        return;
+    }
+    if let Some(Ai::Sleeping { .. }) = monster.ai {
+        monster.ai = Some(Ai::Basic);
+    }
+    monster.alive = true;
+    monster.facing = random_direction(rng);
+    objects.push(monster);
+}
+
+/// A monster of the given kind from the monster table, placed at (x, y)
+pub fn new_monster(name: &str, x: i32, y: i32) -> Object {
+    match name {
+        "orc" => {
+            let mut orc = Object::new(x, y, 'o', "orc", DESATURATED_GREEN, true);
+            orc.fighter = Some(Fighter {
+                base_max_hp: 10,
+                hp: 10,
+                base_defense: 0,
+                base_power: 3,
+                base_accuracy: 0,
+                crit_chance: 0.05,
+                flee_below: 0.3,
+                xp: 35,
+                on_death: DeathCallback::Monster,
+                resistances: Resistances::NONE,
+            });
+            orc.ai = Some(Ai::Sleeping { sight_radius: 5.0 });
+
+            orc
+        }
+        "archer" => {
+            let mut archer = Object::new(x, y, 'a', "goblin archer", LIGHT_GREEN, true);
+            archer.fighter = Some(Fighter {
+                base_max_hp: 8,
+                hp: 8,
+                base_defense: 0,
+                base_power: 3,
+                base_accuracy: 2,
+                crit_chance: 0.1,
+                flee_below: 0.4,
+                xp: 40,
+                on_death: DeathCallback::Monster,
+                resistances: Resistances::NONE,
+            });
+            // archers stand watch instead of sleeping
+            archer.ai = Some(Ai::Ranged { range: 6.0 });
+
+            archer
+        }
+        "bat" => {
+            let mut bat = Object::new(x, y, 'b', "bat", LIGHT_SEPIA, true);
+            bat.fighter = Some(Fighter {
+                base_max_hp: 4,
+                hp: 4,
+                base_defense: 2,
+                base_power: 1,
+                base_accuracy: 1,
+                crit_chance: 0.05,
+                flee_below: 0.0,
+                xp: 15,
+                on_death: DeathCallback::Monster,
+                resistances: Resistances::NONE,
+            });
+            bat.ai = Some(Ai::Basic);
+            // two turns for every one of the player's
+            bat.speed = 20;
+            bat.flying = true;
+            // hears its way around, seen or not
+            bat.senses_invisible = true;
+
+            bat
+        }
+        "zombie" => {
+            let mut zombie = Object::new(x, y, 'z', "zombie", DESATURATED_GREEN, true);
+            zombie.fighter = Some(Fighter {
+                base_max_hp: 20,
+                hp: 20,
+                base_defense: 0,
+                base_power: 5,
+                base_accuracy: 0,
+                crit_chance: 0.05,
+                flee_below: 0.0,
+                xp: 50,
+                on_death: DeathCallback::Monster,
+                // rotten through already, and dry enough to burn
+                resistances: Resistances {
+                    fire: 1.5,
+                    poison: 0.0,
+                    ..Resistances::NONE
+                },
+            });
+            zombie.ai = Some(Ai::Sleeping { sight_radius: 5.0 });
+            zombie.faction = Faction::Undead;
+            // shambles along at one turn for every two of the player's
+            zombie.speed = 5;
+
+            zombie
+        }
+        "vampire" => {
+            let mut vampire = Object::new(x, y, 'V', "Vampire", DARK_RED, true);
+            vampire.fighter = Some(Fighter {
+                base_max_hp: 16,
+                hp: 16,
+                base_defense: 1,
+                base_power: 4,
+                base_accuracy: 2,
+                crit_chance: 0.1,
+                // the undead don't know fear
+                flee_below: 0.0,
+                xp: 100,
+                on_death: DeathCallback::Monster,
+                // no blood to poison, and it goes up like dry paper
+                resistances: Resistances {
+                    fire: 2.0,
+                    poison: 0.0,
+                    ..Resistances::NONE
+                },
+            });
+            vampire.ai = Some(Ai::Sleeping { sight_radius: 8.0 });
+            vampire.faction = Faction::Undead;
+            // vampires get three turns for every two of the player's
+            vampire.speed = 15;
+            vampire.flying = true;
+
+            vampire
+        }
+        "ogre" => {
+            // ogres take up a 2x2 square
+            let mut ogre = Object::new(x, y, 'O', "ogre", DARKER_GREEN, true);
+            ogre.size = (2, 2);
+            ogre.fighter = Some(Fighter {
+                base_max_hp: 25,
+                hp: 25,
+                base_defense: 2,
+                base_power: 6,
+                base_accuracy: 0,
+                crit_chance: 0.05,
+                flee_below: 0.2,
+                xp: 100,
+                on_death: DeathCallback::Monster,
+                // thick hide, but it hates fire
+                resistances: Resistances {
+                    physical: 0.75,
+                    fire: 1.5,
+                    ..Resistances::NONE
+                },
+            });
+            ogre.ai = Some(Ai::Sleeping { sight_radius: 4.0 });
+
+            ogre
+        }
+        // only ever raised by the boss
+        "skeleton" => {
+            let mut skeleton = Object::new(x, y, 's', "skeleton", LIGHTEST_GREY, true);
+            skeleton.fighter = Some(Fighter {
+                base_max_hp: 8,
+                hp: 8,
+                base_defense: 1,
+                base_power: 4,
+                base_accuracy: 1,
+                crit_chance: 0.05,
+                flee_below: 0.0,
+                xp: 20,
+                on_death: DeathCallback::Monster,
+                // bones don't mind poison, and shatter under lightning
+                resistances: Resistances {
+                    lightning: 1.5,
+                    poison: 0.0,
+                    ..Resistances::NONE
+                },
+            });
+            skeleton.ai = Some(Ai::Basic);
+            skeleton.faction = Faction::Undead;
+
+            skeleton
+        }
+        // the boss waiting at the bottom of the dungeon
+        "lich" => {
+            let mut lich = Object::new(x, y, 'L', "Lich King", LIGHT_CYAN, true);
+            lich.fighter = Some(Fighter {
+                base_max_hp: 60,
+                hp: 60,
+                base_defense: 3,
+                base_power: 8,
+                base_accuracy: 3,
+                crit_chance: 0.1,
+                flee_below: 0.0,
+                xp: 1000,
+                on_death: DeathCallback::Boss,
+                resistances: Resistances {
+                    fire: 0.5,
+                    lightning: 0.5,
+                    poison: 0.0,
+                    ..Resistances::NONE
+                },
+            });
+            lich.ai = Some(Ai::Boss {
+                turn: 0,
+                enraged: false,
+            });
+            lich.faction = Faction::Undead;
+            lich.senses_invisible = true;
+
+            lich
+        }
+        // won't hurt a fly, and can be won over with a ration
+        "stray dog" => {
+            let mut dog = Object::new(x, y, 'd', "stray dog", SEPIA, true);
+            dog.fighter = Some(Fighter {
+                base_max_hp: 15,
+                hp: 15,
+                base_defense: 1,
+                base_power: 3,
+                base_accuracy: 1,
+                crit_chance: 0.05,
+                flee_below: 0.0,
+                xp: 0,
+                on_death: DeathCallback::Monster,
+                resistances: Resistances::NONE,
+            });
+            dog.faction = Faction::Neutral;
+
+            dog
+        }
+        // called up by a scroll of summoning to fight for the player
+        "spirit wolf" => {
+            let mut wolf = Object::new(x, y, 'w', "spirit wolf", LIGHT_CYAN, true);
+            wolf.fighter = Some(Fighter {
+                base_max_hp: 12,
+                hp: 12,
+                base_defense: 1,
+                base_power: 4,
+                base_accuracy: 2,
+                crit_chance: 0.1,
+                flee_below: 0.0,
+                xp: 0,
+                on_death: DeathCallback::Summon,
+                resistances: Resistances {
+                    poison: 0.0,
+                    ..Resistances::NONE
+                },
+            });
+            wolf.ai = Some(Ai::Basic);
+            wolf.faction = Faction::Player;
+
+            wolf
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Put the monster down if its whole footprint is inside `room` and free
+fn add_monster<R: Rng>(
+    mut monster: Object,
+    room: Rect,
+    map: &GameMap,
+    objects: &mut Vec<Object>,
+    rng: &mut R,
+) {
+    let fits = monster.tiles().all(|(tile_x, tile_y)| {
+        tile_x < room.x2
+            && tile_y < room.y2
+            && map.tile(tile_x, tile_y).terrain == Terrain::Floor
+            && !is_blocked(tile_x, tile_y, map, objects)
+    });
+    if fits {
+        monster.alive = true;
+        monster.facing = random_direction(rng);
+        objects.push(monster);
+    }
+}
+
+/// item random table, how likely each item is on the given level, zero for
+/// any the level's theme leaves out
+fn item_chances(level: u32) -> LootTable<Item> {
+    let mut chances = LootTable::new(vec![
+        (Item::Heal, 70),
+        (Item::Food, 20),
+        (
+            Item::PlaceTrap,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (Item::Identify, 15),
+        (
+            Item::Lightning,
+            from_dungeon_level(
+                &[
+                    Transition {
+                        level: 1,
+                        value: 10,
+                    },
+                    Transition {
+                        level: 4,
+                        value: 25,
+                    },
+                ],
+                level,
+            ),
+        ),
+        (
+            Item::Fireball,
+            from_dungeon_level(
+                &[
+                    Transition {
+                        level: 1,
+                        value: 10,
+                    },
+                    Transition {
+                        level: 6,
+                        value: 25,
+                    },
+                ],
+                level,
+            ),
+        ),
+        (
+            Item::Confuse,
+            from_dungeon_level(
+                &[
+                    Transition {
+                        level: 1,
+                        value: 10,
+                    },
+                    Transition {
+                        level: 2,
+                        value: 15,
+                    },
+                ],
+                level,
+            ),
+        ),
+        (
+            Item::Sword,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (
+            Item::Shield,
+            from_dungeon_level(
+                &[Transition {
+                    level: 3,
+                    value: 15,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Pickaxe,
+            from_dungeon_level(&[Transition { level: 4, value: 5 }], level),
+        ),
+        (
+            Item::LiquidFire,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Charm,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Summon,
+            from_dungeon_level(&[Transition { level: 3, value: 5 }], level),
+        ),
+        (Item::Speed, 5),
+        (
+            Item::Invisibility,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (Item::SeeInvisible, 5),
+        (Item::MagicMapping, 5),
+        (Item::DetectMonsters, 10),
+        (Item::Blink, 10),
+        (
+            Item::Slow,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (
+            Item::Teleport,
+            from_dungeon_level(&[Transition { level: 3, value: 5 }], level),
+        ),
+        (Item::Sling, 5),
+        (
+            Item::Bow,
+            from_dungeon_level(&[Transition { level: 3, value: 5 }], level),
+        ),
+        (ARROWS, 10),
+        (STONES, 10),
+    ]);
+    let allowed = level_theme(level).items;
+    chances.retain(|item| allowed.contains(item));
+    chances
+}
+
+/// An item of the given kind, lying at (x, y)
+pub fn new_item(item: Item, x: i32, y: i32) -> Object {
+    let mut object = match item {
+        // create a healing potion
+        Item::Heal => Object::new(x, y, '!', "healing potion", VIOLET, false),
+        Item::LiquidFire => Object::new(x, y, '!', "potion of liquid fire", VIOLET, false),
+        Item::Speed => Object::new(x, y, '!', "potion of speed", VIOLET, false),
+        Item::Invisibility => Object::new(x, y, '!', "potion of invisibility", VIOLET, false),
+        Item::SeeInvisible => Object::new(x, y, '!', "potion of see invisible", VIOLET, false),
+        // create a lightning bolt scroll
+        Item::Lightning => Object::new(x, y, '#', "scroll of lightning bolt", LIGHT_YELLOW, false),
+        // create a fireball scroll
+        Item::Fireball => Object::new(x, y, '#', "scroll of fireball", LIGHT_YELLOW, false),
+        // create a confuse scroll
+        Item::Confuse => Object::new(x, y, '#', "scroll of confusion", LIGHT_YELLOW, false),
+        Item::Identify => Object::new(x, y, '#', "scroll of identify", LIGHT_YELLOW, false),
+        Item::Charm => Object::new(x, y, '#', "scroll of charm monster", LIGHT_YELLOW, false),
+        Item::Summon => Object::new(x, y, '#', "scroll of summoning", LIGHT_YELLOW, false),
+        Item::Slow => Object::new(x, y, '#', "scroll of slow monster", LIGHT_YELLOW, false),
+        Item::Blink => Object::new(x, y, '#', "scroll of blinking", LIGHT_YELLOW, false),
+        Item::Teleport => Object::new(x, y, '#', "scroll of teleportation", LIGHT_YELLOW, false),
+        Item::MagicMapping => {
+            Object::new(x, y, '#', "scroll of magic mapping", LIGHT_YELLOW, false)
+        }
+        Item::DetectMonsters => {
+            Object::new(x, y, '#', "scroll of detect monsters", LIGHT_YELLOW, false)
+        }
+        // create a sword
+        Item::Sword => {
+            let mut object = Object::new(x, y, '/', "sword", SKY, false);
+            object.equipment = Some(Equipment {
+                slot: Slot::RightHand,
+                equipped: false,
+                power_bonus: 0,
+                defense_bonus: 0,
+                max_hp_bonus: 0,
+                damage: Some((1, 5)),
+            });
+            object
+        }
+        // create a shield
+        Item::Shield => {
+            let mut object = Object::new(x, y, '[', "shield", DARKER_ORANGE, false);
+            object.equipment = Some(Equipment {
+                slot: Slot::LeftHand,
+                equipped: false,
+                power_bonus: 0,
+                defense_bonus: 1,
+                max_hp_bonus: 0,
+                damage: None,
+            });
+            object
+        }
+        Item::Pickaxe => {
+            let mut object = Object::new(x, y, '\\', "pickaxe", LIGHT_GREY, false);
+            object.equipment = Some(Equipment {
+                slot: Slot::RightHand,
+                equipped: false,
+                power_bonus: 0,
+                defense_bonus: 0,
+                max_hp_bonus: 0,
+                damage: Some((0, 2)),
+            });
+            object
+        }
+        Item::Bow | Item::Sling => {
+            let name = if item == Item::Bow { "bow" } else { "sling" };
+            let mut object = Object::new(x, y, '}', name, SEPIA, false);
+            object.equipment = Some(Equipment {
+                slot: Slot::Back,
+                equipped: false,
+                power_bonus: 0,
+                defense_bonus: 0,
+                max_hp_bonus: 0,
+                damage: None,
+            });
+            object
+        }
+        Item::Ammo { kind, count } => {
+            Object::new(x, y, '(', &ammo_name(kind, count), kind.color(), false)
+        }
+        Item::Amulet => {
+            let mut object = Object::new(x, y, '"', "Amulet of the Ancient Kings", GOLD, false);
+            object.always_visible = true;
+            object
+        }
+        Item::Food => Object::new(x, y, '%', "ration", LIGHT_SEPIA, false),
+        Item::PlaceTrap => Object::new(x, y, '&', "spike trap kit", SEPIA, false),
+        Item::Gold { amount } => Object::new(x, y, '$', &format!("{} gold", amount), GOLD, false),
+        Item::Key { .. } => unreachable!(),
+    };
+    object.item = Some(item);
+    object
+}
+
+pub fn is_blocked(x: i32, y: i32, map: &GameMap, objects: &[Object]) -> bool {
+    // first test the map tile
+    if map.tile(x, y).blocked {
+        return true;
+    }
+
+    // now check for any blocking objects
+    objects
+        .iter()
+        .any(|object| object.blocks && object.occupies(x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MAP_HEIGHT, MAP_WIDTH};
+    use rand::{SeedableRng, XorShiftRng};
+
+    const SEEDS: u32 = 20;
+    const STYLES: &[MapStyle] = &[
+        MapStyle::Rooms,
+        MapStyle::Bsp,
+        MapStyle::Caves,
+        MapStyle::Maze,
+        MapStyle::Drunkard,
+    ];
+
+    /// a level built from `seed`, with only the player on it beforehand
+    fn generate(seed: u32, level: u32, style: MapStyle) -> (GameMap, Vec<Rect>, Vec<Object>) {
+        let mut rng = XorShiftRng::from_seed([seed + 1, 2, 3, 4]);
+        let mut objects = vec![Object::new(0, 0, '@', "player", WHITE, true)];
+        let (map, rooms) = generate_level(
+            MAP_WIDTH,
+            MAP_HEIGHT,
+            level,
+            Some(style),
+            &[],
+            &mut objects,
+            &mut rng,
+        );
+        (map, rooms, objects)
+    }
+
+    #[test]
+    fn player_starts_on_walkable_floor() {
+        for seed in 0..SEEDS {
+            for &style in STYLES {
+                let (map, _, objects) = generate(seed, 1, style);
+                let (x, y) = objects[PLAYER].pos();
+                assert!(
+                    !map.tile(x, y).blocked,
+                    "seed {} {:?}: player starts in a wall at ({}, {})",
+                    seed,
+                    style,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rooms_keep_to_their_sizes_and_count() {
+        for seed in 0..SEEDS {
+            let (_, rooms, _) = generate(seed, 1, MapStyle::Rooms);
+            assert!(!rooms.is_empty());
+            assert!(rooms.len() <= MAX_ROOMS as usize, "seed {}", seed);
+            for room in &rooms {
+                let (w, h) = (room.x2 - room.x1, room.y2 - room.y1);
+                assert!(
+                    (ROOM_MIN_SIZE..=ROOM_MAX_SIZE).contains(&w)
+                        && (ROOM_MIN_SIZE..=ROOM_MAX_SIZE).contains(&h),
+                    "seed {}: room {:?} is {}x{}",
+                    seed,
+                    room,
+                    w,
+                    h
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rooms_dont_intersect() {
+        for seed in 0..SEEDS {
+            let (_, rooms, _) = generate(seed, 1, MapStyle::Rooms);
+            for (i, a) in rooms.iter().enumerate() {
+                for b in &rooms[i + 1..] {
+                    assert!(
+                        !a.intersects_with(b),
+                        "seed {}: {:?} and {:?} intersect",
+                        seed,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all_floor_is_reachable_from_the_start() {
+        for seed in 0..SEEDS {
+            for &style in STYLES {
+                for &level in &[1, DEEPEST_LEVEL] {
+                    let (mut map, _, objects) = generate(seed, level, style);
+                    // secret passages and locked doors only hold the player
+                    // up, they don't wall anything off
+                    for (_, tile) in map.iter_mut() {
+                        if tile.secret {
+                            *tile = Tile::empty();
+                        } else if let Some(Door::Locked { .. }) = tile.door {
+                            tile.open_door();
+                        }
+                    }
+                    let distances = DistanceMap::new(&map, objects[PLAYER].pos());
+                    for ((x, y), tile) in map.iter() {
+                        assert!(
+                            tile.blocked || distances.distance(x, y).is_some(),
+                            "seed {} {:?} level {}: ({}, {}) can't be reached",
+                            seed,
+                            style,
+                            level,
+                            x,
+                            y
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn palettes_drift_between_themes() {
+        for pair in LEVEL_THEMES.windows(2) {
+            let (theme, next) = (&pair[0], &pair[1]);
+            // a theme starts on its own colors and ends part way to the next
+            for level in theme.levels.clone() {
+                let (from, to) = palette_blend(level);
+                assert_eq!(from.name, theme.name);
+                let (to, t) = to.unwrap();
+                assert_eq!(to.name, next.name);
+                assert!((0.0..1.0).contains(&t));
+            }
+            assert_eq!(palette_blend(*theme.levels.start()).1.unwrap().1, 0.0);
+            assert!(palette_blend(*theme.levels.end()).1.unwrap().1 > 0.0);
+        }
+        // the deepest theme has nothing after it to drift towards
+        let deepest = &LEVEL_THEMES[LEVEL_THEMES.len() - 1];
+        let (from, to) = palette_blend(DEEPEST_LEVEL);
+        assert_eq!(from.name, deepest.name);
+        assert!(to.is_none());
+    }
+}
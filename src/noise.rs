@@ -0,0 +1,31 @@
+//! Noise from loud actions. It carries through open tiles, growing fainter
+//! with every step, and monsters out of sight that hear it come to look.
+
+use crate::game::Game;
+use crate::object::Object;
+use crate::pathfinding::DistanceMap;
+use crate::ui::Tcod;
+use std::mem;
+
+/// A sound made at (x, y), heard up to `loudness` steps away
+#[derive(Clone, Copy, Debug)]
+pub struct Noise {
+    pub x: i32,
+    pub y: i32,
+    pub loudness: u32,
+}
+
+/// Let the monsters react to the noises made since the last call. Monsters
+/// the player can see already know where they are and don't need to look.
+pub fn hear_noises(tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+    for noise in mem::take(&mut game.noises) {
+        let heard = DistanceMap::within(&game.map, (noise.x, noise.y), noise.loudness);
+        for monster in objects.iter_mut() {
+            let hears = monster.tiles().any(|(x, y)| heard.distance(x, y).is_some());
+            if !hears || tcod.fov.is_in_fov(monster.x, monster.y) {
+                continue;
+            }
+            monster.ai = monster.ai.take().map(|ai| ai.hear((noise.x, noise.y)));
+        }
+    }
+}
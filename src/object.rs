@@ -0,0 +1,974 @@
+//! Objects on the map, their combat stats, equipment and movement.
+
+use crate::ai::Ai;
+use crate::combat::{attack_roll, damage_range, roll_damage, AttackRoll};
+use crate::game::{Game, Messages};
+use crate::items::Item;
+use crate::loot::drop_table;
+use crate::map::{new_item, Door, GameMap, Terrain};
+use crate::noise::Noise;
+use crate::pathfinding::Path;
+use crate::shop::Shop;
+use crate::spells::Spellcaster;
+use crate::status::{StatusEffects, StatusKind};
+use crate::traps::Trap;
+use crate::ui::Hit;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use tcod::colors::{self, *};
+use tcod::console::*;
+
+const BACKSTAB_MULTIPLIER: i32 = 3;
+// the log tells misses, hits and critical hits apart at a glance
+const MISS_COLOR: Color = LIGHT_GREY;
+const HIT_COLOR: Color = WHITE;
+const CRIT_COLOR: Color = LIGHT_YELLOW;
+// how many steps away a fight can be heard; backstabs are silent
+const COMBAT_LOUDNESS: u32 = 8;
+// energy gained per tick at normal speed, and the energy one action costs
+const NORMAL_SPEED: i32 = 10;
+pub const ACTION_COST: i32 = 10;
+const WATER_MOVE_COST: i32 = ACTION_COST / 2;
+// how far status effects tint an object's color towards their own
+const STATUS_TINT: f32 = 0.6;
+pub const PLAYER: usize = 0;
+
+/// This is a generic object: the player, a monster, an item, the stairs...
+/// It's always represented by a character on screen.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Object {
+    pub x: i32,
+    pub y: i32,
+    pub char: char,
+    pub color: Color,
+    pub name: String,
+    pub blocks: bool,
+    pub alive: bool,
+    pub fighter: Option<Fighter>,
+    pub ai: Option<Ai>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
+    pub trap: Option<Trap>,
+    pub shop: Option<Shop>,
+    pub caster: Option<Spellcaster>,
+    pub status: StatusEffects,
+    pub faction: Faction,
+    // sees invisible objects without needing the status for it
+    pub senses_invisible: bool,
+    // turns a summoned ally has left before it fades away
+    pub summon_turns: Option<i32>,
+    // drawn on explored tiles even when out of view, like the stairs
+    pub always_visible: bool,
+    // what's drawn over what when several objects share a tile
+    pub render_order: RenderOrder,
+    pub level: i32,
+    // footprint in tiles, (x, y) is its top-left corner
+    pub size: (i32, i32),
+    // flying objects aren't hurt by lava
+    pub flying: bool,
+    // energy gained each tick; the object can act once it has ACTION_COST
+    pub speed: i32,
+    pub energy: i32,
+    // direction of the last step; unaware monsters only see what's in front
+    pub facing: (i32, i32),
+    // the monster's route to its target, worked out again after loading
+    #[serde(skip)]
+    pub path: Option<Path>,
+}
+
+impl Object {
+    pub fn new(x: i32, y: i32, char: char, name: &str, color: Color, blocks: bool) -> Self {
+        Object {
+            x,
+            y,
+            char,
+            name: name.into(),
+            color,
+            alive: false,
+            blocks,
+            fighter: None,
+            ai: None,
+            item: None,
+            equipment: None,
+            trap: None,
+            shop: None,
+            caster: None,
+            status: StatusEffects::default(),
+            faction: Faction::Monster,
+            senses_invisible: false,
+            summon_turns: None,
+            always_visible: false,
+            render_order: if blocks {
+                RenderOrder::Actor
+            } else {
+                RenderOrder::Item
+            },
+            level: 1,
+            size: (1, 1),
+            flying: false,
+            speed: NORMAL_SPEED,
+            energy: 0,
+            facing: (0, 1),
+            path: None,
+        }
+    }
+
+    /// set the color and then draw the character that represents
+    /// this object at its position
+    /// `show_health` tints monsters by how hurt they are instead
+    pub fn draw(&self, con: &mut dyn Console, show_health: bool) {
+        let color = match (&self.ai, self.fighter) {
+            (Some(_), Some(fighter)) if show_health => health_color(&fighter),
+            // sleeping monsters are drawn dimmer
+            (Some(Ai::Sleeping { .. }), _) => colors::lerp(self.color, BLACK, 0.5),
+            _ => self.color,
+        };
+        // tinted by whatever it's suffering from, the first effect if several
+        let color = match self.status.iter().next() {
+            Some(effect) => colors::lerp(color, effect.kind.color(), STATUS_TINT),
+            None => color,
+        };
+        con.set_default_foreground(color);
+        for (x, y) in self.tiles() {
+            con.put_char(x, y, self.char, BackgroundFlag::None);
+        }
+    }
+
+    /// true for traps the player hasn't found yet, which aren't shown
+    pub fn hidden(&self) -> bool {
+        self.trap.is_some_and(|trap| !trap.revealed)
+    }
+
+    pub fn pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    pub fn set_pos(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    /// every tile covered by the object's footprint
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (x, y) = self.pos();
+        let (width, height) = self.size;
+        (x..x + width).flat_map(move |tile_x| (y..y + height).map(move |tile_y| (tile_x, tile_y)))
+    }
+
+    pub fn occupies(&self, x: i32, y: i32) -> bool {
+        let (width, height) = self.size;
+        (self.x..self.x + width).contains(&x) && (self.y..self.y + height).contains(&y)
+    }
+
+    /// distance from the closest tile of the footprint to a tile
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        let (width, height) = self.size;
+        let dx = x - x.clamp(self.x, self.x + width - 1);
+        let dy = y - y.clamp(self.y, self.y + height - 1);
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+
+    /// distance between the closest tiles of the two footprints
+    pub fn distance_to(&self, other: &Object) -> f32 {
+        // gap between the two spans along one axis, 0 if they overlap
+        let gap = |start: i32, len: i32, other_start: i32, other_len: i32| {
+            if other_start >= start + len {
+                other_start - (start + len - 1)
+            } else if start >= other_start + other_len {
+                start - (other_start + other_len - 1)
+            } else {
+                0
+            }
+        };
+        let dx = gap(self.x, self.size.0, other.x, other.size.0);
+        let dy = gap(self.y, self.size.1, other.y, other.size.1);
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+
+    /// energy gained each tick, with haste and slow taken into account
+    pub fn current_speed(&self) -> i32 {
+        let mut speed = self.speed;
+        if self.status.has(StatusKind::Hasted) {
+            speed *= 2;
+        }
+        if self.status.has(StatusKind::Slowed) {
+            speed /= 2;
+        }
+        speed
+    }
+
+    pub fn sees_invisible(&self) -> bool {
+        self.senses_invisible || self.status.has(StatusKind::SeeInvisible)
+    }
+
+    /// true unless the object is invisible and `observer` can't see that
+    pub fn visible_to(&self, observer: &Object) -> bool {
+        !self.status.has(StatusKind::Invisible) || observer.sees_invisible()
+    }
+
+    /// the side the object fights on for now, charmed monsters fight for
+    /// the player
+    pub fn allegiance(&self) -> Faction {
+        if self.status.has(StatusKind::Charmed) {
+            Faction::Player
+        } else {
+            self.faction
+        }
+    }
+
+    pub fn is_hostile_to(&self, other: &Object) -> bool {
+        let (ours, theirs) = (self.allegiance(), other.allegiance());
+        ours != theirs && ours != Faction::Neutral && theirs != Faction::Neutral
+    }
+
+    /// how much of `damage` gets through the object's resistances
+    pub fn resist(&self, damage: Damage) -> i32 {
+        let multiplier = self
+            .fighter
+            .map_or(1.0, |f| f.resistances.multiplier(damage.kind));
+        (damage.amount as f32 * multiplier).round() as i32
+    }
+
+    /// Returns the xp the object was worth if this was the killing blow
+    pub fn take_damage(&mut self, damage: Damage, game: &mut Game) -> Option<i32> {
+        let damage = self.resist(damage);
+        // apply damage if possible
+        if let Some(fighter) = self.fighter.as_mut() {
+            if damage > 0 {
+                fighter.hp -= damage;
+                game.hits.push(Hit {
+                    x: self.x,
+                    y: self.y,
+                    damage,
+                });
+            }
+        }
+        // check for death and call death function, only on the killing blow
+        if let Some(fighter) = self.fighter {
+            if fighter.hp <= 0 && self.alive {
+                self.alive = false;
+                self.status = StatusEffects::default();
+                fighter.on_death.callback(self, game);
+                return Some(fighter.xp);
+            }
+        }
+        None
+    }
+
+    /// heal by the given amount, without going over the maximum
+    pub fn heal(&mut self, amount: i32, game: &Game) {
+        let max_hp = self.max_hp(game);
+        if let Some(fighter) = self.fighter.as_mut() {
+            fighter.hp = cmp::min(fighter.hp + amount, max_hp);
+        }
+    }
+
+    pub fn power(&self, game: &Game) -> i32 {
+        let base_power = self.fighter.map_or(0, |f| f.base_power);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.power_bonus)
+            .sum();
+        base_power + bonus - self.hunger_penalty(game).0
+    }
+
+    /// lowest and highest damage of a blow, from the power and the weapon
+    /// in hand if there is one
+    pub fn damage_range(&self, game: &Game) -> (i32, i32) {
+        let weapon = self
+            .get_all_equipped(game)
+            .iter()
+            .find_map(|equipment| equipment.damage);
+        damage_range(self.power(game), weapon)
+    }
+
+    pub fn accuracy(&self) -> i32 {
+        self.fighter.map_or(0, |f| f.base_accuracy)
+    }
+
+    pub fn defense(&self, game: &Game) -> i32 {
+        let base_defense = self.fighter.map_or(0, |f| f.base_defense);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.defense_bonus)
+            .sum();
+        base_defense + bonus - self.hunger_penalty(game).1
+    }
+
+    pub fn max_hp(&self, game: &Game) -> i32 {
+        let base_max_hp = self.fighter.map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|e| e.max_hp_bonus)
+            .sum();
+        base_max_hp + bonus
+    }
+
+    /// what going hungry takes off the player's (power, defense)
+    fn hunger_penalty(&self, game: &Game) -> (i32, i32) {
+        if self.name == "player" {
+            let hunger = game.hunger();
+            (hunger.power_penalty(), hunger.defense_penalty())
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// returns a list of equipped items
+    pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
+        // only the player carries an inventory, so monsters get no bonuses
+        if self.name == "player" {
+            game.inventory
+                .iter()
+                .filter_map(|item| item.equipment)
+                .filter(|equipment| equipment.equipped)
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Equip object and show a message about it
+    pub fn equip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(
+                format!("Can't equip {:?} because it's not an Item.", self),
+                RED,
+            );
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if !equipment.equipped {
+                equipment.equipped = true;
+                messages.add(
+                    format!("Equipped {} on {}.", self.name, equipment.slot),
+                    LIGHT_GREEN,
+                );
+            }
+        } else {
+            messages.add(
+                format!("Can't equip {:?} because it's not an Equipment.", self),
+                RED,
+            );
+        }
+    }
+
+    /// Dequip object and show a message about it
+    pub fn dequip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(
+                format!("Can't dequip {:?} because it's not an Item.", self),
+                RED,
+            );
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if equipment.equipped {
+                equipment.equipped = false;
+                messages.add(
+                    format!("Dequipped {} from {}.", self.name, equipment.slot),
+                    LIGHT_YELLOW,
+                );
+            }
+        } else {
+            messages.add(
+                format!("Can't dequip {:?} because it's not an Equipment.", self),
+                RED,
+            );
+        }
+    }
+
+    pub fn gain_xp(&mut self, xp: i32) {
+        if let Some(fighter) = self.fighter.as_mut() {
+            fighter.xp += xp;
+        }
+    }
+
+    /// roll to hit `target` with a blow doing `damage`, see `combat::attack_roll`
+    fn roll_attack<R: Rng>(
+        &self,
+        target: &Object,
+        damage: (i32, i32),
+        rng: &mut R,
+        game: &Game,
+    ) -> AttackRoll {
+        let crit_chance = self.fighter.map_or(0.0, |f| f.crit_chance);
+        attack_roll(
+            self.accuracy(),
+            target.defense(game),
+            damage,
+            crit_chance,
+            rng,
+        )
+    }
+
+    /// a sneak attack on a target that hasn't noticed the attacker yet,
+    /// never misses and always does BACKSTAB_MULTIPLIER times the damage
+    pub fn backstab<R: Rng>(&mut self, target: &mut Object, rng: &mut R, game: &mut Game) {
+        let damage =
+            Damage::physical(roll_damage(self.damage_range(game), rng) * BACKSTAB_MULTIPLIER);
+        let dealt = target.resist(damage);
+        if dealt <= 0 {
+            game.messages.add(
+                format!(
+                    "{} sneaks up on {} but the attack bounces off!",
+                    self.name, target.name
+                ),
+                HIT_COLOR,
+            );
+        } else {
+            game.messages.add(
+                format!(
+                    "{} backstabs {} for {} hit points!",
+                    self.name, target.name, dealt
+                ),
+                CRIT_COLOR,
+            );
+        }
+        if let Some(xp) = target.take_damage(damage, game) {
+            self.gain_xp(xp);
+        }
+    }
+
+    pub fn attack<R: Rng>(&mut self, target: &mut Object, rng: &mut R, game: &mut Game) {
+        game.noises.push(Noise {
+            x: target.x,
+            y: target.y,
+            loudness: COMBAT_LOUDNESS,
+        });
+        let (damage, is_crit) = match self.roll_attack(target, self.damage_range(game), rng, game) {
+            AttackRoll::Miss => {
+                game.messages.add(
+                    format!("{} attacks {} but misses.", self.name, target.name),
+                    MISS_COLOR,
+                );
+                return;
+            }
+            AttackRoll::Hit(damage) => (Damage::physical(damage), false),
+            AttackRoll::Crit(damage) => (Damage::physical(damage), true),
+        };
+        let dealt = target.resist(damage);
+        if dealt <= 0 {
+            game.messages.add(
+                format!(
+                    "{} attacks {} but the attack bounces off!",
+                    self.name, target.name
+                ),
+                HIT_COLOR,
+            );
+        } else if is_crit {
+            game.messages.add(
+                format!(
+                    "{} lands a critical hit on {} for {} hit points!",
+                    self.name, target.name, dealt
+                ),
+                CRIT_COLOR,
+            );
+        } else {
+            game.messages.add(
+                format!(
+                    "{} attacks {} for {} hit points.",
+                    self.name, target.name, dealt
+                ),
+                HIT_COLOR,
+            );
+        }
+        if let Some(xp) = target.take_damage(damage, game) {
+            // yield experience to the attacker
+            self.gain_xp(xp);
+        }
+    }
+
+    /// same attack roll as `attack`, but from a distance: a `missile` hitting
+    /// with `power` instead of the shooter's own
+    pub fn shoot<R: Rng>(
+        &mut self,
+        target: &mut Object,
+        power: i32,
+        missile: &str,
+        rng: &mut R,
+        game: &mut Game,
+    ) {
+        let (damage, is_crit) = match self.roll_attack(target, damage_range(power, None), rng, game)
+        {
+            AttackRoll::Miss => {
+                game.messages.add(
+                    format!("{}'s {} misses {}.", self.name, missile, target.name),
+                    MISS_COLOR,
+                );
+                return;
+            }
+            AttackRoll::Hit(damage) => (Damage::physical(damage), false),
+            AttackRoll::Crit(damage) => (Damage::physical(damage), true),
+        };
+        let dealt = target.resist(damage);
+        if dealt <= 0 {
+            game.messages.add(
+                format!(
+                    "{} shoots at {} but the {} glances off!",
+                    self.name, target.name, missile
+                ),
+                HIT_COLOR,
+            );
+        } else if is_crit {
+            game.messages.add(
+                format!(
+                    "{}'s {} strikes {} true for {} hit points!",
+                    self.name, missile, target.name, dealt
+                ),
+                CRIT_COLOR,
+            );
+        } else {
+            game.messages.add(
+                format!(
+                    "{} shoots {} for {} hit points.",
+                    self.name, target.name, dealt
+                ),
+                HIT_COLOR,
+            );
+        }
+        if let Some(xp) = target.take_damage(damage, game) {
+            // yield experience to the attacker
+            self.gain_xp(xp);
+        }
+    }
+}
+
+// combat-related properties and methods
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    // stats before equipment, see `Object::max_hp` and friends
+    pub base_max_hp: i32,
+    pub hp: i32,
+    // defense is also how hard the fighter is to hit, against the
+    // attacker's accuracy
+    pub base_defense: i32,
+    pub base_power: i32,
+    pub base_accuracy: i32,
+    // chance in [0, 1] of a hit doing CRIT_MULTIPLIER times the damage
+    pub crit_chance: f32,
+    // monsters run away once their hp drops below this share of their max hp,
+    // 0.0 for the ones that never do
+    pub flee_below: f32,
+    // for monsters what killing them is worth, for the player what they've earned
+    pub xp: i32,
+    pub on_death: DeathCallback,
+    pub resistances: Resistances,
+}
+
+/// Layers objects are drawn in, later ones over earlier ones, so a corpse or
+/// an item never hides whoever is standing on it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RenderOrder {
+    // part of the floor: stairs and traps
+    Floor,
+    Corpse,
+    Item,
+    // the player and monsters
+    Actor,
+}
+
+/// Who fights whom: objects attack anything of another faction, except
+/// for the neutral ones
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Faction {
+    // the player and their allies
+    Player,
+    // the living: orcs, goblins and ogres
+    Monster,
+    // the restless dead, who hate the living just as much as they hate the player
+    Undead,
+    // fights nobody and nobody fights it
+    Neutral,
+}
+
+/// What a blow, spell or trap hurts with, which some fighters take better
+/// than others
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DamageKind {
+    Physical,
+    Fire,
+    Lightning,
+    Poison,
+}
+
+/// hit points of damage of one kind, before the target's resistances
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub amount: i32,
+    pub kind: DamageKind,
+}
+
+impl Damage {
+    pub fn physical(amount: i32) -> Self {
+        Damage {
+            amount,
+            kind: DamageKind::Physical,
+        }
+    }
+}
+
+/// Multipliers on the damage a fighter takes of each kind: 1.0 as normal,
+/// less for resistant, 0.0 for immune and more for vulnerable
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Resistances {
+    pub physical: f32,
+    pub fire: f32,
+    pub lightning: f32,
+    pub poison: f32,
+}
+
+impl Resistances {
+    pub const NONE: Resistances = Resistances {
+        physical: 1.0,
+        fire: 1.0,
+        lightning: 1.0,
+        poison: 1.0,
+    };
+
+    pub fn multiplier(self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Physical => self.physical,
+            DamageKind::Fire => self.fire,
+            DamageKind::Lightning => self.lightning,
+            DamageKind::Poison => self.poison,
+        }
+    }
+}
+
+/// An object that can be equipped, yielding bonuses
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub max_hp_bonus: i32,
+    // a weapon's damage roll, added to the wielder's power
+    pub damage: Option<(i32, i32)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    LeftHand,
+    RightHand,
+    // bows and slings, slung on the back until they're fired
+    Back,
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Slot::LeftHand => write!(f, "left hand"),
+            Slot::RightHand => write!(f, "right hand"),
+            Slot::Back => write!(f, "back"),
+        }
+    }
+}
+
+/// Green at full health, fading to red as the fighter nears death
+fn health_color(fighter: &Fighter) -> Color {
+    let fraction = fighter.hp as f32 / fighter.base_max_hp as f32;
+    colors::lerp(RED, GREEN, fraction.clamp(0.0, 1.0))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+    // drops the Amulet
+    Boss,
+    // fades away without a trace
+    Summon,
+}
+
+impl DeathCallback {
+    fn callback(self, object: &mut Object, game: &mut Game) {
+        use DeathCallback::*;
+        let callback: fn(&mut Object, &mut Game) = match self {
+            Player => player_death,
+            Monster => monster_death,
+            Boss => boss_death,
+            Summon => summon_death,
+        };
+        callback(object, game);
+    }
+}
+
+/// true if the object's whole footprint would be free with its top-left
+/// corner at (x, y). The object itself doesn't count as in the way.
+fn fits_at(id: usize, x: i32, y: i32, map: &GameMap, objects: &[Object]) -> bool {
+    let (width, height) = objects[id].size;
+    (x..x + width).all(|tile_x| {
+        (y..y + height).all(|tile_y| {
+            !map.tile(tile_x, tile_y).blocked
+                && !objects.iter().enumerate().any(|(other_id, other)| {
+                    other_id != id && other.blocks && other.occupies(tile_x, tile_y)
+                })
+        })
+    })
+}
+
+/// move by the given amount, returns false if the way was blocked
+/// walking into a closed door opens it instead, which uses up the move
+pub fn move_by(id: usize, dx: i32, dy: i32, map: &mut GameMap, objects: &mut [Object]) -> bool {
+    let (x, y) = objects[id].pos();
+    let doors: Vec<_> = objects[id]
+        .tiles()
+        .map(|(tile_x, tile_y)| (tile_x + dx, tile_y + dy))
+        .filter(|&(door_x, door_y)| {
+            map.in_bounds(door_x, door_y) && map.tile(door_x, door_y).door == Some(Door::Closed)
+        })
+        .collect();
+    if !doors.is_empty() {
+        for (door_x, door_y) in doors {
+            map.open_door(door_x, door_y);
+        }
+        objects[id].facing = (dx, dy);
+        return true;
+    }
+
+    if !fits_at(id, x + dx, y + dy, map, objects) || map.cuts_corner(x, y, dx, dy) {
+        return false;
+    }
+
+    // monsters won't walk into lava that would burn them, the player may
+    let object = &objects[id];
+    let footprint = || {
+        object
+            .tiles()
+            .map(|(tile_x, tile_y)| map.tile(tile_x + dx, tile_y + dy).terrain)
+    };
+    if id != PLAYER && !object.flying && footprint().any(|terrain| terrain == Terrain::Lava) {
+        return false;
+    }
+    let into_water = footprint().any(|terrain| terrain == Terrain::Water);
+
+    objects[id].set_pos(x + dx, y + dy);
+    objects[id].facing = (dx, dy);
+    if into_water {
+        objects[id].energy -= WATER_MOVE_COST;
+    }
+    true
+}
+
+pub fn move_towards(
+    id: usize,
+    target_x: i32,
+    target_y: i32,
+    map: &mut GameMap,
+    objects: &mut [Object],
+) {
+    // vector from this object to the target, and distance
+    let dx = target_x - objects[id].x;
+    let dy = target_y - objects[id].y;
+    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+
+    // normalize it to length 1 (preserving direction), then round it and
+    // convert to integer so the movement is restricted to the map grid
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    if move_by(id, dx, dy, map, objects) {
+        return;
+    }
+
+    // something is in the way (often another monster), so try to sidestep
+    // it, starting with whichever perpendicular step ends up closer
+    let (x, y) = objects[id].pos();
+    let mut sidesteps = [(-dy, dx), (dy, -dx)];
+    sidesteps.sort_by_key(|&(step_x, step_y)| {
+        (target_x - (x + step_x)).pow(2) + (target_y - (y + step_y)).pow(2)
+    });
+    for &(step_x, step_y) in &sidesteps {
+        if move_by(id, step_x, step_y, map, objects) {
+            return;
+        }
+    }
+}
+
+/// Step directly away from the target, or failing that along just one of
+/// the axes. Returns false if there was nowhere to go.
+pub fn move_away_from(
+    id: usize,
+    target_x: i32,
+    target_y: i32,
+    map: &mut GameMap,
+    objects: &mut [Object],
+) -> bool {
+    let dx = (objects[id].x - target_x).signum();
+    let dy = (objects[id].y - target_y).signum();
+
+    let steps = [(dx, dy), (dx, 0), (0, dy)];
+    steps
+        .iter()
+        .filter(|&&step| step != (0, 0))
+        .any(|&(step_x, step_y)| move_by(id, step_x, step_y, map, objects))
+}
+
+/// one of the eight directions, at random
+pub fn random_direction<R: Rng>(rng: &mut R) -> (i32, i32) {
+    loop {
+        let direction = (rng.gen_range(-1, 2), rng.gen_range(-1, 2));
+        if direction != (0, 0) {
+            return direction;
+        }
+    }
+}
+
+/// Take object `id` off the level and hand it back. Everything after it
+/// moves down one place, so the player stays at PLAYER and the objects keep
+/// their order, but any index past `id` held from before is off by one. To
+/// remove several objects, collect their ids first and remove them
+/// afterwards, the last one first.
+pub fn remove_object(id: usize, objects: &mut Vec<Object>) -> Object {
+    assert!(id != PLAYER, "the player can't be removed from the level");
+    objects.remove(id)
+}
+
+pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
+    assert!(first_index != second_index);
+    let split_at_index = cmp::max(first_index, second_index);
+    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
+
+    if first_index < second_index {
+        (&mut first_slice[first_index], &mut second_slice[0])
+    } else {
+        (&mut second_slice[0], &mut first_slice[second_index])
+    }
+}
+
+fn player_death(player: &mut Object, game: &mut Game) {
+    // game over
+    game.messages.add("You died!", RED);
+
+    // transform player into corpse
+    player.char = '%';
+    player.color = DARK_RED;
+    player.render_order = RenderOrder::Corpse;
+}
+
+fn boss_death(boss: &mut Object, game: &mut Game) {
+    game.messages.add(
+        format!("The {} crumbles to dust! The Amulet is yours.", boss.name),
+        LIGHT_YELLOW,
+    );
+    game.spawned.push(new_item(Item::Amulet, boss.x, boss.y));
+    monster_death(boss, game);
+}
+
+fn summon_death(summon: &mut Object, game: &mut Game) {
+    game.messages
+        .add(format!("{} fades away!", summon.name), LIGHT_GREY);
+    vanish(summon);
+}
+
+/// Leave nothing behind, the object is taken off the level at the end of
+/// the turn
+pub fn vanish(object: &mut Object) {
+    object.alive = false;
+    object.blocks = false;
+    object.fighter = None;
+    object.ai = None;
+    object.summon_turns = Some(0);
+}
+
+fn monster_death(monster: &mut Object, game: &mut Game) {
+    // transform it into a corpse
+    game.messages
+        .add(format!("{} is dead!", monster.name), ORANGE);
+    if let Some(item) = drop_table(&monster.name)
+        .roll(&mut rand::thread_rng())
+        .flatten()
+    {
+        game.spawned.push(new_item(item, monster.x, monster.y));
+    }
+    monster.char = '%';
+    monster.color = DARK_RED;
+    monster.render_order = RenderOrder::Corpse;
+    monster.blocks = false;
+    monster.fighter = None;
+    monster.ai = None;
+    monster.name = format!("remains of {}", monster.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    const CORRIDOR_LENGTH: i32 = 20;
+
+    #[test]
+    fn removing_an_object_keeps_the_player_and_the_order() {
+        let mut objects: Vec<_> = ["player", "orc", "sword", "troll", "stairs"]
+            .iter()
+            .map(|&name| Object::new(0, 0, '?', name, WHITE, false))
+            .collect();
+        let removed = remove_object(2, &mut objects);
+        assert_eq!(removed.name, "sword");
+        let names: Vec<_> = objects.iter().map(|object| object.name.as_str()).collect();
+        assert_eq!(names, ["player", "orc", "troll", "stairs"]);
+        assert_eq!(objects[PLAYER].name, "player");
+    }
+
+    #[test]
+    #[should_panic]
+    fn the_player_cant_be_removed() {
+        let mut objects = vec![Object::new(0, 0, '@', "player", WHITE, true)];
+        remove_object(PLAYER, &mut objects);
+    }
+
+    #[test]
+    fn monsters_chasing_down_a_corridor_never_stack_or_swap() {
+        // a one tile wide corridor along y = 1, walls all around it
+        let mut map = GameMap::new(CORRIDOR_LENGTH + 2, 3, Tile::wall());
+        for x in 1..=CORRIDOR_LENGTH {
+            *map.tile_mut(x, 1) = Tile::empty();
+        }
+        let mut objects = vec![
+            Object::new(4, 1, '@', "player", WHITE, true),
+            Object::new(1, 1, 'o', "orc", WHITE, true),
+            Object::new(2, 1, 'o', "orc", WHITE, true),
+        ];
+
+        // the player runs for the far end and stays there, the monsters
+        // keep chasing until they've piled up behind them
+        for _ in 0..2 * CORRIDOR_LENGTH {
+            move_by(PLAYER, 1, 0, &mut map, &mut objects);
+            for id in 1..objects.len() {
+                let (player_x, player_y) = objects[PLAYER].pos();
+                move_towards(id, player_x, player_y, &mut map, &mut objects);
+            }
+
+            let (back, front, player) = (objects[1].pos(), objects[2].pos(), objects[PLAYER].pos());
+            assert!(back != front && front != player && back != player);
+            assert!(back.0 < front.0 && front.0 < player.0);
+            assert!(objects.iter().all(|object| object.y == 1));
+        }
+        assert_eq!(objects[PLAYER].x, CORRIDOR_LENGTH);
+        assert_eq!(objects[2].x, CORRIDOR_LENGTH - 1);
+        assert_eq!(objects[1].x, CORRIDOR_LENGTH - 2);
+    }
+
+    #[test]
+    fn walking_a_door_open_is_noted_on_the_map() {
+        let mut map = GameMap::new(5, 3, Tile::empty());
+        map.tile_mut(2, 1).close_door();
+        let mut objects = vec![Object::new(1, 1, '@', "player", WHITE, true)];
+
+        move_by(PLAYER, 1, 0, &mut map, &mut objects);
+        assert_eq!(objects[PLAYER].pos(), (1, 1));
+        assert_eq!(map.tile(2, 1).door, Some(Door::Open));
+        assert!(map.take_opened_doors());
+        // taken, so the next turn starts clean
+        assert!(!map.take_opened_doors());
+
+        move_by(PLAYER, 1, 0, &mut map, &mut objects);
+        assert_eq!(objects[PLAYER].pos(), (2, 1));
+        assert!(!map.take_opened_doors());
+    }
+}
@@ -0,0 +1,288 @@
+//! How monsters find their way: A* routes for single monsters, so they walk
+//! around walls instead of getting stuck on corners, and a distance map any
+//! number of monsters can follow to the player. A route is kept on the
+//! monster and only worked out again once its target moves or the next step
+//! is blocked. The player's travel and exploring commands find their way
+//! with the same tools.
+
+use crate::map::{GameMap, Terrain};
+use crate::object::{move_by, move_towards, Object, PLAYER};
+use std::collections::VecDeque;
+use tcod::pathfinding::AStar;
+
+// routes longer than this send the monster the long way round the level,
+// so it just heads straight for the target instead
+const MAX_PATH_LENGTH: usize = 25;
+const DIAGONAL_COST: f32 = 1.41;
+// other monsters aren't walls, they'll likely move, but prefer going around them
+const CROWDED_STEP_COST: f32 = 10.0;
+
+/// The steps left to walk to `target`
+#[derive(Debug)]
+pub struct Path {
+    target: (i32, i32),
+    steps: VecDeque<(i32, i32)>,
+}
+
+/// cost of moving object `id` with its top-left corner onto (x, y),
+/// 0.0 meaning it can't go there
+fn step_cost(
+    id: usize,
+    x: i32,
+    y: i32,
+    target: (i32, i32),
+    map: &GameMap,
+    objects: &[Object],
+) -> f32 {
+    // the target is usually someone standing there, it still has to be reachable
+    if (x, y) == target {
+        return 1.0;
+    }
+
+    let object = &objects[id];
+    let (width, height) = object.size;
+    let footprint = || {
+        (x..x + width).flat_map(move |tile_x| (y..y + height).map(move |tile_y| (tile_x, tile_y)))
+    };
+    let walkable = footprint().all(|(tile_x, tile_y)| {
+        map.in_bounds(tile_x, tile_y) && {
+            let tile = map.tile(tile_x, tile_y);
+            tile.passable() && (object.flying || tile.terrain != Terrain::Lava)
+        }
+    });
+    if !walkable {
+        return 0.0;
+    }
+
+    let crowded = footprint().any(|(tile_x, tile_y)| {
+        objects.iter().enumerate().any(|(other_id, other)| {
+            other_id != id && other.blocks && other.occupies(tile_x, tile_y)
+        })
+    });
+    if crowded {
+        CROWDED_STEP_COST
+    } else {
+        1.0
+    }
+}
+
+/// A* route for object `id` to `target`, `None` if there's no way through
+fn find_path(id: usize, target: (i32, i32), map: &GameMap, objects: &[Object]) -> Option<Path> {
+    let mut astar = AStar::new_from_callback(
+        map.width(),
+        map.height(),
+        |(from_x, from_y), (x, y)| {
+            if map.cuts_corner(from_x, from_y, x - from_x, y - from_y) {
+                0.0
+            } else {
+                step_cost(id, x, y, target, map, objects)
+            }
+        },
+        DIAGONAL_COST,
+    );
+    if !astar.find(objects[id].pos(), target) {
+        return None;
+    }
+    Some(Path {
+        target,
+        steps: astar.walk().collect(),
+    })
+}
+
+/// A* route for the player to `target` over tiles they've already explored,
+/// `None` if they don't know of a way there
+pub fn player_route(target: (i32, i32), map: &GameMap, objects: &[Object]) -> Option<Path> {
+    let (target_x, target_y) = target;
+    if !map.in_bounds(target_x, target_y) {
+        return None;
+    }
+    let tile = map.tile(target_x, target_y);
+    if !tile.explored || !tile.passable() {
+        return None;
+    }
+    let mut astar = AStar::new_from_callback(
+        map.width(),
+        map.height(),
+        |(from_x, from_y), (x, y)| {
+            if !map.tile(x, y).explored || map.cuts_corner(from_x, from_y, x - from_x, y - from_y) {
+                0.0
+            } else {
+                step_cost(PLAYER, x, y, target, map, objects)
+            }
+        },
+        DIAGONAL_COST,
+    );
+    if !astar.find(objects[PLAYER].pos(), target) {
+        return None;
+    }
+    Some(Path {
+        target,
+        steps: astar.walk().collect(),
+    })
+}
+
+impl Path {
+    /// the next tile along the route, if there's any of it left
+    pub fn next_step(&self) -> Option<(i32, i32)> {
+        self.steps.front().copied()
+    }
+}
+
+/// Take one step along the route to (target_x, target_y). Falls back to
+/// `move_towards` when there's no usable route.
+pub fn move_along_path(
+    id: usize,
+    target_x: i32,
+    target_y: i32,
+    map: &mut GameMap,
+    objects: &mut [Object],
+) {
+    let target = (target_x, target_y);
+    let cached = objects[id].path.take().filter(|path| path.target == target);
+    let path = cached.or_else(|| find_path(id, target, map, objects));
+
+    match path {
+        Some(mut path) if path.steps.len() <= MAX_PATH_LENGTH => {
+            let (x, y) = objects[id].pos();
+            let Some((step_x, step_y)) = path.steps.pop_front() else {
+                return;
+            };
+            if move_by(id, step_x - x, step_y - y, map, objects) {
+                objects[id].path = Some(path);
+            } else {
+                // someone stepped into the way, route again next turn
+                move_towards(id, target_x, target_y, map, objects);
+            }
+        }
+        _ => move_towards(id, target_x, target_y, map, objects),
+    }
+}
+
+/// How many steps every tile is from one spot, usually the player. Worked
+/// out once a turn for the whole map, so any number of monsters can head
+/// for that spot by rolling downhill without a search of their own.
+#[derive(Debug, Default)]
+pub struct DistanceMap {
+    width: i32,
+    height: i32,
+    // `None` for walls and anything walled off from the root
+    distances: Vec<Option<u32>>,
+}
+
+impl DistanceMap {
+    pub fn new(map: &GameMap, root: (i32, i32)) -> Self {
+        DistanceMap::within(map, root, u32::MAX)
+    }
+
+    /// Only fill in the tiles up to `max_distance` steps from the root
+    pub fn within(map: &GameMap, root: (i32, i32), max_distance: u32) -> Self {
+        DistanceMap::fill(map, vec![root], max_distance)
+    }
+
+    /// Steps to whichever of the roots is closest, from everywhere
+    pub fn to_nearest(map: &GameMap, roots: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        DistanceMap::fill(map, roots, u32::MAX)
+    }
+
+    fn fill(map: &GameMap, roots: impl IntoIterator<Item = (i32, i32)>, max_distance: u32) -> Self {
+        let (width, height) = (map.width(), map.height());
+        let mut distances = vec![None; (width * height) as usize];
+
+        // every step costs the same, so a breadth-first flood fill visits
+        // the tiles in order of distance
+        let mut frontier = VecDeque::new();
+        for root in roots {
+            distances[(root.1 * width + root.0) as usize] = Some(0);
+            frontier.push_back(root);
+        }
+        while let Some((x, y)) = frontier.pop_front() {
+            let distance = distances[(y * width + x) as usize].unwrap_or(0);
+            if distance >= max_distance {
+                continue;
+            }
+            for (next_x, next_y) in neighbours(x, y) {
+                if !map.in_bounds(next_x, next_y)
+                    || !map.tile(next_x, next_y).passable()
+                    || map.cuts_corner(x, y, next_x - x, next_y - y)
+                {
+                    continue;
+                }
+                let next = &mut distances[(next_y * width + next_x) as usize];
+                if next.is_none() {
+                    *next = Some(distance + 1);
+                    frontier.push_back((next_x, next_y));
+                }
+            }
+        }
+
+        DistanceMap {
+            width,
+            height,
+            distances,
+        }
+    }
+
+    pub fn distance(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        self.distances[(y * self.width + x) as usize]
+    }
+}
+
+/// the eight tiles around (x, y)
+fn neighbours(x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+    (-1..=1)
+        .flat_map(move |dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&step| step != (0, 0))
+        .map(move |(dx, dy)| (x + dx, y + dy))
+}
+
+/// Step object `id` onto the neighbouring tile closest to the distance map's
+/// root, trying the next best ones if that's taken. Returns false if none of
+/// them gets it any closer.
+pub fn move_downhill(
+    id: usize,
+    distances: &DistanceMap,
+    map: &mut GameMap,
+    objects: &mut [Object],
+) -> bool {
+    roll(id, distances, map, objects, |distance| distance as i64)
+}
+
+/// Like `move_downhill`, but away from the root. Returns false when the
+/// object is cornered.
+pub fn move_uphill(
+    id: usize,
+    distances: &DistanceMap,
+    map: &mut GameMap,
+    objects: &mut [Object],
+) -> bool {
+    roll(id, distances, map, objects, |distance| -(distance as i64))
+}
+
+/// step to whichever free neighbouring tile is lowest by `height`, as long as
+/// it's lower than where the object stands now
+fn roll(
+    id: usize,
+    distances: &DistanceMap,
+    map: &mut GameMap,
+    objects: &mut [Object],
+    height: impl Fn(u32) -> i64,
+) -> bool {
+    let (x, y) = objects[id].pos();
+    let Some(here) = distances.distance(x, y).map(&height) else {
+        return false;
+    };
+
+    let mut lower: Vec<_> = neighbours(x, y)
+        .filter_map(|(next_x, next_y)| {
+            let next = height(distances.distance(next_x, next_y)?);
+            (next < here).then_some((next, next_x - x, next_y - y))
+        })
+        .collect();
+    lower.sort_by_key(|&(next, _, _)| next);
+    lower
+        .into_iter()
+        .any(|(_, dx, dy)| move_by(id, dx, dy, map, objects))
+}
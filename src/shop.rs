@@ -0,0 +1,138 @@
+//! Shops: a shopkeeper standing in some rooms who buys and sells items for
+//! gold. Bumping into them opens their menu.
+
+use crate::game::Game;
+use crate::identify::item_name;
+use crate::items::Item;
+use crate::map::new_item;
+use crate::object::Object;
+use crate::ui::{menu, Tcod, MAX_MENU_OPTIONS};
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+
+const SHOP_WIDTH: i32 = 40;
+
+/// What a shopkeeper has for sale
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Shop {
+    pub stock: Vec<Item>,
+}
+
+/// A shopkeeper at (x, y) selling `stock`
+pub fn new_shopkeeper(x: i32, y: i32, stock: Vec<Item>) -> Object {
+    let mut shopkeeper = Object::new(x, y, '@', "shopkeeper", LIGHT_AMBER, true);
+    shopkeeper.shop = Some(Shop { stock });
+    shopkeeper
+}
+
+/// what the shopkeeper pays for an item, half what they sell it for
+fn sell_price(item: Item) -> Option<u32> {
+    item.price().map(|price| price / 2)
+}
+
+/// Buy and sell with the shopkeeper until the player walks away
+pub fn trade(shopkeeper_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    loop {
+        let header = format!(
+            "\"Welcome, traveller! Buying or selling?\"\n\nYou have {} gold.\n",
+            game.gold
+        );
+        match menu(&header, &["Buy", "Sell"], SHOP_WIDTH, &mut tcod.root) {
+            Some(0) => buy(shopkeeper_id, tcod, game, objects),
+            Some(1) => sell(shopkeeper_id, tcod, game, objects),
+            _ => break,
+        }
+    }
+}
+
+fn buy(shopkeeper_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    let Some(shop) = objects[shopkeeper_id].shop.as_mut() else {
+        return;
+    };
+    if shop.stock.is_empty() {
+        game.messages
+            .add("\"Sorry, I'm all sold out.\"", LIGHT_AMBER);
+        return;
+    }
+
+    let options: Vec<String> = shop
+        .stock
+        .iter()
+        .map(|&item| {
+            let name = item_name(&new_item(item, 0, 0), game);
+            format!("{} ({} gold)", name, item.price().unwrap_or(0))
+        })
+        .collect();
+    let header = format!("What would you like? You have {} gold.\n", game.gold);
+    let Some(choice) = menu(&header, &options, SHOP_WIDTH, &mut tcod.root) else {
+        return;
+    };
+
+    let item = shop.stock[choice];
+    let price = item.price().unwrap_or(0);
+    if game.gold < price {
+        game.messages.add("You can't afford that.", LIGHT_AMBER);
+    } else if game.inventory.len() >= MAX_MENU_OPTIONS {
+        game.messages.add("Your inventory is full.", RED);
+    } else {
+        shop.stock.remove(choice);
+        game.gold -= price;
+        let bought = new_item(item, 0, 0);
+        game.messages.add(
+            format!("You buy a {} for {} gold.", item_name(&bought, game), price),
+            GREEN,
+        );
+        game.inventory.push(bought);
+    }
+}
+
+fn sell(shopkeeper_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    let Some(shop) = objects[shopkeeper_id].shop.as_mut() else {
+        return;
+    };
+    if game.inventory.is_empty() {
+        game.messages.add("You have nothing to sell.", LIGHT_GREY);
+        return;
+    }
+
+    let options: Vec<String> = game
+        .inventory
+        .iter()
+        .map(|object| match object.item.and_then(sell_price) {
+            Some(price) => format!("{} ({} gold)", item_name(object, game), price),
+            None => format!("{} (not for sale)", item_name(object, game)),
+        })
+        .collect();
+    let header = format!("What are you selling? You have {} gold.\n", game.gold);
+    let Some(choice) = menu(&header, &options, SHOP_WIDTH, &mut tcod.root) else {
+        return;
+    };
+
+    let item = game.inventory[choice].item;
+    match item.and_then(|item| sell_price(item).map(|price| (item, price))) {
+        Some(_) if shop.stock.len() >= MAX_MENU_OPTIONS => {
+            game.messages
+                .add("\"I've no room for any more, sorry.\"", LIGHT_AMBER);
+        }
+        Some((item, price)) => {
+            let mut sold = game.inventory.remove(choice);
+            if sold.equipment.is_some() {
+                sold.dequip(&mut game.messages);
+            }
+            game.gold += price;
+            game.messages.add(
+                format!(
+                    "You sell the {} for {} gold.",
+                    item_name(&sold, game),
+                    price
+                ),
+                GREEN,
+            );
+            shop.stock.push(item);
+        }
+        None => {
+            game.messages
+                .add("\"I wouldn't know what to do with that.\"", LIGHT_AMBER);
+        }
+    }
+}
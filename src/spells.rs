@@ -0,0 +1,588 @@
+//! Spells: the effects scrolls and potions have, shared with the spells the
+//! player casts from their own mana.
+
+use crate::ai::Ai;
+use crate::game::Game;
+use crate::map::{is_blocked, new_monster};
+use crate::noise::Noise;
+use crate::object::{vanish, Damage, DamageKind, DeathCallback, Object, PLAYER};
+use crate::status::StatusKind;
+use crate::traps::spring_traps;
+use crate::ui::{
+    menu, play_effect, projectile_glyph, target_tile, ScreenEffect, Tcod, FOV_ALGO,
+    FOV_LIGHT_WALLS, INVENTORY_WIDTH, TORCH_RADIUS,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+use tcod::line::Line;
+
+pub const HEAL_AMOUNT: i32 = 4;
+// keeps working for a while after the first rush
+const HEAL_REGEN_TURNS: i32 = 8;
+const HEAL_REGEN_AMOUNT: i32 = 1;
+const LIGHTNING_DAMAGE: Damage = Damage {
+    amount: 20,
+    kind: DamageKind::Lightning,
+};
+const LIGHTNING_RANGE: i32 = 5;
+const LIGHTNING_STUN_TURNS: i32 = 2;
+const CONFUSE_RANGE: i32 = 8;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: Damage = Damage {
+    amount: 12,
+    kind: DamageKind::Fire,
+};
+// anyone who survives the blast keeps burning
+const FIREBALL_BURN_TURNS: i32 = 3;
+const FIREBALL_BURN_DAMAGE: i32 = 2;
+// how many steps away the thunder and the explosion can be heard
+const LIGHTNING_LOUDNESS: u32 = 12;
+const FIREBALL_LOUDNESS: u32 = 15;
+const CHARM_RANGE: i32 = 6;
+const CHARM_TURNS: i32 = 15;
+const SUMMON_COUNT: usize = 2;
+const SUMMON_TURNS: i32 = 20;
+pub const HASTE_TURNS: i32 = 10;
+const SLOW_RANGE: i32 = 6;
+const SLOW_TURNS: i32 = 10;
+const BLINK_RANGE: f32 = 5.0;
+const DETECT_MONSTERS_TURNS: i32 = 30;
+pub const INVISIBILITY_TURNS: i32 = 20;
+pub const SEE_INVISIBLE_TURNS: i32 = 40;
+// a caster gets a point of mana back every this many turns
+const MANA_REGEN_EVERY: i32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Spell {
+    Heal,
+    Lightning,
+    Confuse,
+    Fireball,
+    Charm,
+    Summon,
+    Haste,
+    Slow,
+    Blink,
+    Teleport,
+    Invisibility,
+    SeeInvisible,
+    MagicMapping,
+    DetectMonsters,
+}
+
+impl Spell {
+    pub fn name(self) -> &'static str {
+        match self {
+            Spell::Heal => "heal",
+            Spell::Lightning => "lightning bolt",
+            Spell::Confuse => "confusion",
+            Spell::Fireball => "fireball",
+            Spell::Charm => "charm monster",
+            Spell::Summon => "summon spirits",
+            Spell::Haste => "haste",
+            Spell::Slow => "slow monster",
+            Spell::Blink => "blink",
+            Spell::Teleport => "teleport",
+            Spell::Invisibility => "invisibility",
+            Spell::SeeInvisible => "see invisible",
+            Spell::MagicMapping => "magic mapping",
+            Spell::DetectMonsters => "detect monsters",
+        }
+    }
+
+    pub fn mana_cost(self) -> i32 {
+        match self {
+            Spell::Heal => 4,
+            Spell::Lightning => 6,
+            Spell::Confuse => 5,
+            Spell::Fireball => 10,
+            Spell::Charm => 7,
+            Spell::Summon => 8,
+            Spell::Haste => 6,
+            Spell::Slow => 4,
+            Spell::Blink => 3,
+            Spell::Teleport => 8,
+            Spell::Invisibility => 9,
+            Spell::SeeInvisible => 4,
+            Spell::MagicMapping => 10,
+            Spell::DetectMonsters => 5,
+        }
+    }
+
+    /// Do what the spell does for the player, whether it comes from a
+    /// scroll, a potion or their own mana. Returns false if it had nothing
+    /// to work on or the player called it off.
+    pub fn cast(self, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+        match self {
+            Spell::Heal => cast_heal(tcod, game, objects),
+            Spell::Lightning => cast_lightning(tcod, game, objects),
+            Spell::Confuse => cast_confuse(tcod, game, objects),
+            Spell::Fireball => cast_fireball(tcod, game, objects),
+            Spell::Charm => cast_charm(tcod, game, objects),
+            Spell::Summon => cast_summon(tcod, game, objects),
+            Spell::Haste => cast_haste(tcod, game, objects),
+            Spell::Slow => cast_slow(tcod, game, objects),
+            Spell::Blink => cast_blink(tcod, game, objects),
+            Spell::Teleport => cast_teleport(tcod, game, objects),
+            Spell::Invisibility => cast_invisibility(tcod, game, objects),
+            Spell::SeeInvisible => cast_see_invisible(tcod, game, objects),
+            Spell::MagicMapping => cast_magic_mapping(tcod, game, objects),
+            Spell::DetectMonsters => cast_detect_monsters(tcod, game, objects),
+        }
+    }
+}
+
+/// Mana, and the spells it can be spent on
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spellcaster {
+    pub mana: i32,
+    pub max_mana: i32,
+    pub spells: Vec<Spell>,
+    // turns until the next point of mana comes back
+    regen_in: i32,
+}
+
+impl Spellcaster {
+    pub fn new(max_mana: i32, spells: Vec<Spell>) -> Self {
+        Spellcaster {
+            mana: max_mana,
+            max_mana,
+            spells,
+            regen_in: MANA_REGEN_EVERY,
+        }
+    }
+}
+
+/// One turn off a summoned ally's time, sending it back once it's up
+pub fn tick_summon(object: &mut Object, game: &mut Game) {
+    let Some(turns) = object.summon_turns.as_mut() else {
+        return;
+    };
+    *turns -= 1;
+    if *turns <= 0 {
+        game.messages
+            .add(format!("The {} fades away.", object.name), LIGHT_GREY);
+        vanish(object);
+    }
+}
+
+/// one turn's worth of mana coming back
+pub fn regen_mana(object: &mut Object) {
+    let Some(caster) = object.caster.as_mut() else {
+        return;
+    };
+    caster.regen_in -= 1;
+    if caster.regen_in <= 0 {
+        caster.regen_in = MANA_REGEN_EVERY;
+        caster.mana = (caster.mana + 1).min(caster.max_mana);
+    }
+}
+
+/// Ask which known spell to cast and pay for it if it goes off. Returns
+/// whether the player's turn was spent.
+pub fn cast_menu(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let Some(caster) = objects[PLAYER].caster.clone() else {
+        game.messages.add("You don't know any spells.", LIGHT_GREY);
+        return false;
+    };
+    let options: Vec<String> = caster
+        .spells
+        .iter()
+        .map(|spell| format!("{} ({} mana)", spell.name(), spell.mana_cost()))
+        .collect();
+    let header = format!(
+        "Press the key next to a spell to cast it. Mana: {}/{}\n",
+        caster.mana, caster.max_mana
+    );
+    let Some(choice) = menu(&header, &options, INVENTORY_WIDTH, &mut tcod.root) else {
+        return false;
+    };
+    let spell = caster.spells[choice];
+    if caster.mana < spell.mana_cost() {
+        game.messages.add(
+            format!("You don't have enough mana to cast {}.", spell.name()),
+            LIGHT_GREY,
+        );
+        return false;
+    }
+    if !spell.cast(tcod, game, objects) {
+        return false;
+    }
+    if let Some(caster) = objects[PLAYER].caster.as_mut() {
+        caster.mana -= spell.mana_cost();
+    }
+    true
+}
+
+/// Heal the player, doing nothing if they're already at full health
+fn cast_heal(_tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    if let Some(fighter) = objects[PLAYER].fighter {
+        if fighter.hp == objects[PLAYER].max_hp(game) {
+            game.messages.add("You are already at full health.", RED);
+            return false;
+        }
+        game.messages
+            .add("Your wounds start to feel better!", LIGHT_VIOLET);
+        objects[PLAYER].heal(HEAL_AMOUNT, game);
+        objects[PLAYER]
+            .status
+            .add(StatusKind::Regen, HEAL_REGEN_TURNS, HEAL_REGEN_AMOUNT);
+        return true;
+    }
+    false
+}
+
+/// Strike the closest visible monster, wasting nothing if there is none
+fn cast_lightning(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let monster_id = match closest_monster(tcod, objects, LIGHTNING_RANGE) {
+        Some(monster_id) => monster_id,
+        None => {
+            game.messages
+                .add("No enemy is close enough to strike.", RED);
+            return false;
+        }
+    };
+
+    let (from, to) = (objects[PLAYER].pos(), objects[monster_id].pos());
+    let bolt = ScreenEffect::Bolt {
+        path: Line::new(from, to).collect(),
+        glyph: projectile_glyph(from, to),
+        frame: 0,
+    };
+    play_effect(tcod, game, objects, bolt);
+
+    game.messages.add(
+        format!(
+            "A lightning bolt strikes the {} with a loud thunder! \
+             The damage is {} hit points.",
+            objects[monster_id].name,
+            objects[monster_id].resist(LIGHTNING_DAMAGE)
+        ),
+        LIGHT_BLUE,
+    );
+    objects[monster_id]
+        .status
+        .add(StatusKind::Stun, LIGHTNING_STUN_TURNS, 0);
+    game.noises.push(Noise {
+        x: objects[monster_id].x,
+        y: objects[monster_id].y,
+        loudness: LIGHTNING_LOUDNESS,
+    });
+    if let Some(xp) = objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
+        objects[PLAYER].gain_xp(xp);
+    }
+    true
+}
+
+/// Confuse the closest visible monster for a few turns
+fn cast_confuse(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let monster_id = match closest_monster(tcod, objects, CONFUSE_RANGE) {
+        Some(monster_id) => monster_id,
+        None => {
+            game.messages
+                .add("No enemy is close enough to confuse.", RED);
+            return false;
+        }
+    };
+
+    // replace the monster's AI with a "confused" one; after some turns it
+    // will restore the old AI
+    let old_ai = objects[monster_id].ai.take().unwrap_or(Ai::Basic);
+    objects[monster_id].ai = Some(Ai::Confused {
+        previous_ai: Box::new(old_ai),
+        num_turns: CONFUSE_NUM_TURNS,
+    });
+    game.messages.add(
+        format!(
+            "The eyes of the {} look vacant, as it starts to stumble around!",
+            objects[monster_id].name
+        ),
+        LIGHT_GREEN,
+    );
+    true
+}
+
+/// Ask the player for a target tile and burn every fighter around it,
+/// the player included
+fn cast_fireball(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    game.messages.add(
+        "Left-click a target tile for the fireball, or right-click to cancel.",
+        LIGHT_CYAN,
+    );
+    let (x, y) = match target_tile(tcod, game, objects, None) {
+        Some(tile_pos) => tile_pos,
+        None => return false,
+    };
+
+    let burst = ScreenEffect::Burst {
+        center: (x, y),
+        radius: FIREBALL_RADIUS as usize,
+        color: ORANGE,
+        frame: 0,
+    };
+    play_effect(tcod, game, objects, burst);
+
+    game.messages.add(
+        format!(
+            "The fireball explodes, burning everything within {} tiles!",
+            FIREBALL_RADIUS
+        ),
+        ORANGE,
+    );
+    game.noises.push(Noise {
+        x,
+        y,
+        loudness: FIREBALL_LOUDNESS,
+    });
+    let mut xp_to_gain = 0;
+    for object in objects.iter_mut() {
+        if object.distance(x, y) <= FIREBALL_RADIUS as f32 && object.fighter.is_some() {
+            game.messages.add(
+                format!(
+                    "The {} gets burned for {} hit points.",
+                    object.name,
+                    object.resist(FIREBALL_DAMAGE)
+                ),
+                ORANGE,
+            );
+            if let Some(xp) = object.take_damage(FIREBALL_DAMAGE, game) {
+                xp_to_gain += xp;
+            } else if object.alive {
+                object.status.add(
+                    StatusKind::Burning,
+                    FIREBALL_BURN_TURNS,
+                    FIREBALL_BURN_DAMAGE,
+                );
+            }
+        }
+    }
+    // the player can't collect on burning themselves
+    if objects[PLAYER].alive {
+        objects[PLAYER].gain_xp(xp_to_gain);
+    }
+    true
+}
+
+/// Turn the closest visible monster to the player's side for a while.
+/// The master of the dungeon won't be swayed.
+fn cast_charm(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let Some(monster_id) = closest_monster(tcod, objects, CHARM_RANGE) else {
+        game.messages.add("No enemy is close enough to charm.", RED);
+        return false;
+    };
+
+    let monster = &mut objects[monster_id];
+    if monster
+        .fighter
+        .is_some_and(|f| f.on_death == DeathCallback::Boss)
+    {
+        game.messages
+            .add(format!("The {} laughs off your charm!", monster.name), RED);
+        return true;
+    }
+    // whatever it was doing, it's now out to fight for the player
+    if matches!(
+        monster.ai,
+        Some(Ai::Sleeping { .. }) | Some(Ai::Investigating { .. }) | Some(Ai::Fleeing { .. })
+    ) {
+        monster.ai = Some(Ai::Basic);
+    }
+    monster.status.add(StatusKind::Charmed, CHARM_TURNS, 0);
+    game.messages.add(
+        format!("The {} looks at you adoringly!", monster.name),
+        LIGHT_MAGENTA,
+    );
+    true
+}
+
+/// Call up spirit wolves on the free tiles around the player to fight by
+/// their side until they fade away. They join the level once the turn is
+/// over.
+fn cast_summon(_tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let (x, y) = objects[PLAYER].pos();
+    let mut spots: Vec<_> = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+        .filter(|&(spot_x, spot_y)| {
+            game.map.in_bounds(spot_x, spot_y)
+                && !game.map.tile(spot_x, spot_y).blocked
+                && !objects
+                    .iter()
+                    .any(|object| object.blocks && object.occupies(spot_x, spot_y))
+        })
+        .collect();
+    if spots.is_empty() {
+        game.messages
+            .add("There's no room for anything to answer your call.", RED);
+        return false;
+    }
+    rand::thread_rng().shuffle(&mut spots);
+
+    for &(spot_x, spot_y) in spots.iter().take(SUMMON_COUNT) {
+        let mut wolf = new_monster("spirit wolf", spot_x, spot_y);
+        wolf.alive = true;
+        wolf.summon_turns = Some(SUMMON_TURNS);
+        game.spawned.push(wolf);
+    }
+    game.messages.add(
+        "Ghostly wolves step out of thin air to fight by your side!",
+        LIGHT_CYAN,
+    );
+    true
+}
+
+/// Speed the player up for a while
+fn cast_haste(_tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    objects[PLAYER]
+        .status
+        .add(StatusKind::Hasted, HASTE_TURNS, 0);
+    game.messages
+        .add("The world around you slows down!", LIGHT_AZURE);
+    true
+}
+
+/// Slow the closest visible monster down for a while
+fn cast_slow(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let Some(monster_id) = closest_monster(tcod, objects, SLOW_RANGE) else {
+        game.messages.add("No enemy is close enough to slow.", RED);
+        return false;
+    };
+
+    objects[monster_id]
+        .status
+        .add(StatusKind::Slowed, SLOW_TURNS, 0);
+    game.messages.add(
+        format!("The {} slows to a crawl!", objects[monster_id].name),
+        DARK_ORANGE,
+    );
+    true
+}
+
+/// Ask the player for a free tile in view close by and hop over to it
+fn cast_blink(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    game.messages.add(
+        "Left-click a tile to blink to, or right-click to cancel.",
+        LIGHT_CYAN,
+    );
+    let Some((x, y)) = target_tile(tcod, game, objects, Some(BLINK_RANGE)) else {
+        return false;
+    };
+    if is_blocked(x, y, &game.map, objects) {
+        game.messages
+            .add("There's no room to blink to there.", LIGHT_GREY);
+        return false;
+    }
+
+    game.messages.add("You blink across the room!", LIGHT_CYAN);
+    move_player_to(x, y, tcod, game, objects);
+    true
+}
+
+/// Whisk the player away to a random free tile they've explored before
+fn cast_teleport(tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    let player_pos = objects[PLAYER].pos();
+    let spots: Vec<(i32, i32)> = (0..game.map.width())
+        .flat_map(|x| (0..game.map.height()).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            (x, y) != player_pos
+                && game.map.tile(x, y).explored
+                && !is_blocked(x, y, &game.map, objects)
+        })
+        .collect();
+    let Some(&(x, y)) = rand::thread_rng().choose(&spots) else {
+        game.messages
+            .add("You feel a tug, but nothing happens.", LIGHT_GREY);
+        return true;
+    };
+
+    game.messages
+        .add("The world twists around you!", LIGHT_CYAN);
+    move_player_to(x, y, tcod, game, objects);
+    true
+}
+
+/// Hide the player from every monster that can't see invisible. Only a
+/// confused one stumbling into them can still land a blow.
+fn cast_invisibility(_tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    objects[PLAYER]
+        .status
+        .add(StatusKind::Invisible, INVISIBILITY_TURNS, 0);
+    game.messages.add("You fade from sight!", LIGHT_GREY);
+    true
+}
+
+/// Let the player see invisible monsters for a while
+fn cast_see_invisible(_tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> bool {
+    objects[PLAYER]
+        .status
+        .add(StatusKind::SeeInvisible, SEE_INVISIBLE_TURNS, 0);
+    game.messages.add(
+        "Your eyes tingle as the unseen comes into view!",
+        LIGHTER_YELLOW,
+    );
+    true
+}
+
+/// Mark every floor tile on the level explored, along with the walls around
+/// them. Secret doors stay secret.
+fn cast_magic_mapping(_tcod: &mut Tcod, game: &mut Game, _objects: &mut [Object]) -> bool {
+    let map = &game.map;
+    let open_nearby = |x: i32, y: i32| {
+        (-1..=1).any(|dx| {
+            (-1..=1).any(|dy| map.in_bounds(x + dx, y + dy) && !map.tile(x + dx, y + dy).blocked)
+        })
+    };
+    let mapped: Vec<(i32, i32)> = map
+        .iter()
+        .filter(|&((x, y), _)| open_nearby(x, y))
+        .map(|(pos, _)| pos)
+        .collect();
+    for (x, y) in mapped {
+        game.map.tile_mut(x, y).explored = true;
+    }
+    game.dirty = true;
+    game.messages
+        .add("A map of the level forms in your mind!", LIGHT_CYAN);
+    true
+}
+
+/// Let the player sense every monster on the level for a while, see
+/// `render_all`
+fn cast_detect_monsters(_tcod: &mut Tcod, game: &mut Game, _objects: &mut [Object]) -> bool {
+    game.detect_monsters = game.detect_monsters.max(DETECT_MONSTERS_TURNS);
+    game.messages
+        .add("You sense the presence of monsters!", LIGHT_CYAN);
+    true
+}
+
+/// Put the player down at (x, y) without walking there. The monsters are
+/// about to act, so what the player sees has to be worked out right away.
+fn move_player_to(x: i32, y: i32, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
+    objects[PLAYER].set_pos(x, y);
+    objects[PLAYER].path = None;
+    tcod.fov
+        .compute_fov(x, y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
+    game.dirty = true;
+    spring_traps(PLAYER, tcod, game, objects);
+}
+
+/// The monster in view nearest to the player, if any is within `max_range`
+fn closest_monster(tcod: &Tcod, objects: &[Object], max_range: i32) -> Option<usize> {
+    let player = &objects[PLAYER];
+    objects
+        .iter()
+        .enumerate()
+        .filter(|&(id, object)| {
+            id != PLAYER
+                && object.fighter.is_some()
+                && object.ai.is_some()
+                && object.is_hostile_to(player)
+                && object.visible_to(player)
+                && object.tiles().any(|(x, y)| tcod.fov.is_in_fov(x, y))
+        })
+        .map(|(id, object)| (id, player.distance_to(object)))
+        .filter(|&(_, distance)| distance <= max_range as f32)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(id, _)| id)
+}
@@ -0,0 +1,186 @@
+//! Status effects: poison, burning, stun, regeneration, charm, haste, slow and
+//! invisibility, which last a number of turns on whoever has them and act
+//! once every turn.
+
+use crate::game::Game;
+use crate::map::Terrain;
+use crate::object::{Damage, DamageKind, Object};
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StatusKind {
+    // loses `magnitude` hp every turn
+    Poison,
+    // the same, but water puts it out
+    Burning,
+    // gains no energy, so loses its turns
+    Stun,
+    // heals `magnitude` hp every turn
+    Regen,
+    // fights for the player, see `Object::allegiance`
+    Charmed,
+    // gains energy twice as fast, or half as fast, see `Object::current_speed`
+    Hasted,
+    Slowed,
+    // unseen by anyone who can't see invisible, see `Object::visible_to`
+    Invisible,
+    SeeInvisible,
+}
+
+impl StatusKind {
+    /// short enough for a few of them to fit on the status row of the panel
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "Pois",
+            StatusKind::Burning => "Burn",
+            StatusKind::Stun => "Stun",
+            StatusKind::Regen => "Regen",
+            StatusKind::Charmed => "Charm",
+            StatusKind::Hasted => "Fast",
+            StatusKind::Slowed => "Slow",
+            StatusKind::Invisible => "Invis",
+            StatusKind::SeeInvisible => "Sight",
+        }
+    }
+
+    /// what the effect tints whoever has it
+    pub fn color(self) -> Color {
+        match self {
+            StatusKind::Poison => LIME,
+            StatusKind::Burning => ORANGE,
+            StatusKind::Stun => LIGHT_BLUE,
+            StatusKind::Regen => PINK,
+            StatusKind::Charmed => LIGHT_MAGENTA,
+            StatusKind::Hasted => LIGHT_AZURE,
+            StatusKind::Slowed => DARK_ORANGE,
+            StatusKind::Invisible => DARK_GREY,
+            StatusKind::SeeInvisible => LIGHTER_YELLOW,
+        }
+    }
+
+    fn wear_off_message(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "is no longer poisoned",
+            StatusKind::Burning => "stops burning",
+            StatusKind::Stun => "is no longer stunned",
+            StatusKind::Regen => "stops regenerating",
+            StatusKind::Charmed => "is no longer charmed",
+            StatusKind::Hasted => "slows back down",
+            StatusKind::Slowed => "speeds back up",
+            StatusKind::Invisible => "fades back into view",
+            StatusKind::SeeInvisible => "can no longer see the unseen",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+    pub magnitude: i32,
+}
+
+/// Every effect an object is under, at most one of each kind
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Start an effect, or top it up if it's already going: the longer
+    /// duration and the stronger magnitude win
+    pub fn add(&mut self, kind: StatusKind, turns: i32, magnitude: i32) {
+        match self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => {
+                effect.turns = effect.turns.max(turns);
+                effect.magnitude = effect.magnitude.max(magnitude);
+            }
+            None => self.effects.push(StatusEffect {
+                kind,
+                turns,
+                magnitude,
+            }),
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter()
+    }
+
+    fn remove(&mut self, kind: StatusKind) {
+        self.effects.retain(|effect| effect.kind != kind);
+    }
+}
+
+/// One turn of every effect on the object, dropping the ones that have run
+/// out. Stun, haste and slow are handled by the energy loop, they only count
+/// down here.
+pub fn tick_status(object: &mut Object, game: &mut Game) {
+    if object.fighter.is_none() {
+        return;
+    }
+
+    let in_water = object
+        .tiles()
+        .any(|(x, y)| game.map.tile(x, y).terrain == Terrain::Water);
+    if in_water && object.status.has(StatusKind::Burning) {
+        object.status.remove(StatusKind::Burning);
+        game.messages.add(
+            format!("The water puts out the fire on the {}.", object.name),
+            LIGHT_BLUE,
+        );
+    }
+
+    let effects: Vec<StatusEffect> = object.status.iter().copied().collect();
+    for effect in &effects {
+        match effect.kind {
+            StatusKind::Poison => {
+                object.take_damage(
+                    Damage {
+                        amount: effect.magnitude,
+                        kind: DamageKind::Poison,
+                    },
+                    game,
+                );
+            }
+            StatusKind::Burning => {
+                object.take_damage(
+                    Damage {
+                        amount: effect.magnitude,
+                        kind: DamageKind::Fire,
+                    },
+                    game,
+                );
+            }
+            StatusKind::Regen => object.heal(effect.magnitude, game),
+            StatusKind::Stun
+            | StatusKind::Charmed
+            | StatusKind::Hasted
+            | StatusKind::Slowed
+            | StatusKind::Invisible
+            | StatusKind::SeeInvisible => {}
+        }
+        // dying wiped its effects, see `Object::take_damage`
+        if !object.alive {
+            return;
+        }
+    }
+
+    object.status.effects.retain_mut(|effect| {
+        effect.turns -= 1;
+        effect.turns > 0
+    });
+    for effect in effects {
+        if !object.status.has(effect.kind) {
+            game.messages.add(
+                format!("The {} {}.", object.name, effect.kind.wear_off_message()),
+                LIGHT_GREY,
+            );
+        }
+    }
+}
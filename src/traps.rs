@@ -0,0 +1,227 @@
+//! Hidden traps: what they do when stepped on, and how they're found.
+
+use crate::game::Game;
+use crate::map::Terrain;
+use crate::object::{Damage, DamageKind, Object, RenderOrder, PLAYER};
+use crate::status::StatusKind;
+use crate::ui::Tcod;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tcod::colors::*;
+
+const SPIKE_DAMAGE: Damage = Damage {
+    amount: 5,
+    kind: DamageKind::Physical,
+};
+const DART_DAMAGE: Damage = Damage {
+    amount: 3,
+    kind: DamageKind::Physical,
+};
+// the darts are poisoned
+const DART_POISON_TURNS: i32 = 6;
+const DART_POISON_DAMAGE: i32 = 1;
+// chance each turn of noticing a hidden trap next to the player without searching
+const PERCEPTION_CHANCE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TrapKind {
+    Spikes,
+    Dart,
+    // sends whoever steps on it to a random spot on the level
+    Teleport,
+}
+
+/// A trap lying on the floor, invisible until it's found or goes off
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub revealed: bool,
+    // set by the player, so only the player's enemies set it off
+    #[serde(default)]
+    pub placed: bool,
+}
+
+impl TrapKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            TrapKind::Spikes => "spike trap",
+            TrapKind::Dart => "dart trap",
+            TrapKind::Teleport => "teleport trap",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            TrapKind::Spikes => "Spikes spring up to hurt whoever steps on it.",
+            TrapKind::Dart => "Shoots a dart at whoever steps on it.",
+            TrapKind::Teleport => "Sends whoever steps on it somewhere else on the level.",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            TrapKind::Spikes => LIGHT_GREY,
+            TrapKind::Dart => LIGHT_RED,
+            TrapKind::Teleport => LIGHT_MAGENTA,
+        }
+    }
+}
+
+/// A trap object at (x, y), hidden to begin with
+pub fn new_trap(x: i32, y: i32, kind: TrapKind) -> Object {
+    let mut trap = Object::new(x, y, '^', kind.name(), kind.color(), false);
+    trap.trap = Some(Trap {
+        kind,
+        revealed: false,
+        placed: false,
+    });
+    trap.render_order = RenderOrder::Floor;
+    trap
+}
+
+/// Set a spike trap on the player's tile from a trap kit, out in the open
+/// for the player but no more noticed by monsters than any other trap.
+/// Returns false if there's a trap there already.
+pub fn place_trap(game: &mut Game, objects: &[Object]) -> bool {
+    let (x, y) = objects[PLAYER].pos();
+    let taken = objects
+        .iter()
+        .chain(&game.spawned)
+        .any(|object| object.trap.is_some() && object.pos() == (x, y));
+    if taken {
+        game.messages
+            .add("There's already a trap here.", LIGHT_GREY);
+        return false;
+    }
+
+    let mut trap = new_trap(x, y, TrapKind::Spikes);
+    if let Some(trap) = trap.trap.as_mut() {
+        trap.placed = true;
+    }
+    reveal(&mut trap);
+    game.messages
+        .add("You set a spike trap at your feet.", LIGHT_BLUE);
+    // joins the level's objects once the turn is over
+    game.spawned.push(trap);
+    true
+}
+
+/// make a trap visible for good
+fn reveal(trap: &mut Object) {
+    if let Some(trap) = trap.trap.as_mut() {
+        trap.revealed = true;
+    }
+    // like the stairs, it stays on the map once the player knows about it
+    trap.always_visible = true;
+}
+
+/// Set off any trap under object `id`, after it stepped onto a new tile.
+/// Flying creatures pass over them.
+pub fn spring_traps(id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
+    if objects[id].flying || objects[id].fighter.is_none() {
+        return;
+    }
+    let trap_ids: Vec<_> = (0..objects.len())
+        .filter(|&trap_id| {
+            objects[trap_id].trap.is_some()
+                && objects[id]
+                    .tiles()
+                    .any(|(x, y)| objects[trap_id].occupies(x, y))
+        })
+        .collect();
+
+    for trap_id in trap_ids {
+        let kind = match objects[trap_id].trap {
+            // the player's own traps are only for their enemies
+            Some(trap) if trap.placed && !objects[id].is_hostile_to(&objects[PLAYER]) => continue,
+            Some(trap) => trap.kind,
+            None => continue,
+        };
+        // nobody notices what happens out of the player's sight
+        let seen = tcod.fov.is_in_fov(objects[id].x, objects[id].y);
+        if seen {
+            reveal(&mut objects[trap_id]);
+            let name = &objects[id].name;
+            let message = match kind {
+                TrapKind::Spikes => format!("Spikes shoot up under the {}!", name),
+                TrapKind::Dart => format!("A dart flies out of the wall at the {}!", name),
+                TrapKind::Teleport => format!("The {} vanishes in a flash of light!", name),
+            };
+            game.messages.add(message, ORANGE);
+        }
+
+        match kind {
+            TrapKind::Spikes => {
+                objects[id].take_damage(SPIKE_DAMAGE, game);
+            }
+            TrapKind::Dart => {
+                objects[id]
+                    .status
+                    .add(StatusKind::Poison, DART_POISON_TURNS, DART_POISON_DAMAGE);
+                objects[id].take_damage(DART_DAMAGE, game);
+            }
+            TrapKind::Teleport => teleport(id, game, objects),
+        }
+    }
+}
+
+/// move object `id` to a random free floor tile
+fn teleport(id: usize, game: &mut Game, objects: &mut [Object]) {
+    let spots: Vec<_> = game
+        .map
+        .iter()
+        .filter(|&((x, y), tile)| {
+            !tile.blocked
+                && tile.terrain == Terrain::Floor
+                && !objects.iter().any(|object| object.occupies(x, y))
+        })
+        .map(|(position, _)| position)
+        .collect();
+    if let Some(&(x, y)) = rand::thread_rng().choose(&spots) {
+        objects[id].set_pos(x, y);
+        objects[id].path = None;
+        game.dirty = true;
+    }
+}
+
+/// Try to find the hidden traps next to the player, each with the given
+/// chance. Returns how many turned up.
+pub fn find_traps(chance: f32, game: &mut Game, objects: &mut [Object]) -> usize {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let mut found = 0;
+    for object in objects.iter_mut() {
+        let hidden = object.trap.is_some_and(|trap| !trap.revealed);
+        let adjacent = (object.x - player_x).abs() <= 1 && (object.y - player_y).abs() <= 1;
+        if hidden && adjacent && rand::random::<f32>() < chance {
+            reveal(object);
+            game.messages
+                .add(format!("You spot a {}!", object.name), LIGHT_BLUE);
+            found += 1;
+        }
+    }
+    found
+}
+
+/// the player keeps an eye out for traps every turn, even when not searching
+pub fn notice_traps(game: &mut Game, objects: &mut [Object]) {
+    find_traps(PERCEPTION_CHANCE, game, objects);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{GameMap, Tile};
+
+    #[test]
+    fn a_placed_trap_is_revealed_and_one_to_a_tile() {
+        let mut game = Game::new(GameMap::new(10, 10, Tile::empty()));
+        let objects = vec![Object::new(3, 4, '@', "player", WHITE, true)];
+
+        assert!(place_trap(&mut game, &objects));
+        assert!(!place_trap(&mut game, &objects));
+        assert_eq!(game.spawned.len(), 1);
+        let trap = &game.spawned[0];
+        assert_eq!(trap.pos(), (3, 4));
+        assert!(trap.trap.is_some_and(|trap| trap.placed && trap.revealed));
+    }
+}
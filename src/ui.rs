@@ -0,0 +1,1258 @@
+//! Everything drawn on screen: the map, the panel, menus and targeting.
+
+use crate::camera::Camera;
+use crate::config::{Action, Keymap};
+use crate::describe::describe;
+use crate::game::{Game, Messages};
+use crate::identify::item_name;
+use crate::items::KEY_KINDS;
+use crate::map::{
+    explored_percent, Door, GameMap, MapStyle, Terrain, COLOR_DOOR, STAIRS_DOWN, STAIRS_UP,
+};
+use crate::object::{DeathCallback, Object, PLAYER};
+use crate::status::{StatusEffect, StatusEffects};
+use crate::vaults::Vault;
+use rand::Rng;
+use std::cmp;
+use tcod::colors::{self, *};
+use tcod::console::*;
+use tcod::image;
+use tcod::input::{self, Event, Key, KeyCode, Mouse};
+use tcod::map::{FovAlgorithm, Map as FovMap};
+
+// share of max hp lost in one turn that shakes the screen, and the share
+// of max hp left that counts as low health
+const BIG_HIT_FRACTION: f32 = 0.25;
+const LOW_HP_FRACTION: f32 = 0.3;
+pub const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
+pub const FOV_LIGHT_WALLS: bool = true;
+pub const TORCH_RADIUS: i32 = 10;
+const BAR_WIDTH: i32 = 20;
+pub const PANEL_HEIGHT: i32 = 9;
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
+// the player's status effects go on the bottom row of the panel, under the bars
+const STATUS_Y: i32 = PANEL_HEIGHT - 1;
+pub const INVENTORY_WIDTH: i32 = 50;
+pub const LEVEL_SCREEN_WIDTH: i32 = 40;
+pub const CHARACTER_SCREEN_WIDTH: i32 = 30;
+pub const MAIN_MENU_WIDTH: i32 = 24;
+// options are picked with the letters a to z
+pub const MAX_MENU_OPTIONS: usize = 26;
+
+// frames a lightning bolt or a fireball's flash stays up once it's all drawn
+const EFFECT_LINGER_FRAMES: usize = 2;
+const HIT_FLASH_FRAMES: i32 = 2;
+// a damage number rises a row every frame for this many frames
+const DAMAGE_NUMBER_FRAMES: usize = 3;
+
+/// Damage something took at (x, y), for `render_all` to show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub x: i32,
+    pub y: i32,
+    pub damage: i32,
+}
+
+/// Short, purely visual effects played over a few frames by `render_all`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScreenEffect {
+    // jolt the map around after a heavy hit
+    Shake {
+        frames_left: i32,
+    },
+    // wash the screen red when the player drops to low health
+    Flash {
+        frames_left: i32,
+    },
+    // light up the tile of whatever just got hurt
+    HitFlash {
+        pos: (i32, i32),
+        frames_left: i32,
+    },
+    // how much damage it was, floating up off the tile
+    DamageNumber {
+        pos: (i32, i32),
+        damage: i32,
+        frame: usize,
+    },
+    // something shot or thrown, a tile further along `path` every frame
+    Projectile {
+        path: Vec<(i32, i32)>,
+        glyph: char,
+        color: Color,
+        frame: usize,
+    },
+    // a lightning bolt, its line to the target a tile longer every frame
+    Bolt {
+        path: Vec<(i32, i32)>,
+        glyph: char,
+        frame: usize,
+    },
+    // an explosion, its flash spreading a tile further out every frame
+    Burst {
+        center: (i32, i32),
+        radius: usize,
+        color: Color,
+        frame: usize,
+    },
+}
+
+impl ScreenEffect {
+    /// true once it has played out
+    fn done(&self) -> bool {
+        match self {
+            ScreenEffect::Shake { frames_left }
+            | ScreenEffect::Flash { frames_left }
+            | ScreenEffect::HitFlash { frames_left, .. } => *frames_left <= 0,
+            ScreenEffect::DamageNumber { frame, .. } => *frame >= DAMAGE_NUMBER_FRAMES,
+            ScreenEffect::Projectile { path, frame, .. } => *frame >= path.len(),
+            ScreenEffect::Bolt { path, frame, .. } => *frame >= path.len() + EFFECT_LINGER_FRAMES,
+            ScreenEffect::Burst { radius, frame, .. } => *frame > radius + EFFECT_LINGER_FRAMES,
+        }
+    }
+
+    fn advance(&mut self) {
+        match self {
+            ScreenEffect::Shake { frames_left }
+            | ScreenEffect::Flash { frames_left }
+            | ScreenEffect::HitFlash { frames_left, .. } => *frames_left -= 1,
+            ScreenEffect::DamageNumber { frame, .. }
+            | ScreenEffect::Projectile { frame, .. }
+            | ScreenEffect::Bolt { frame, .. }
+            | ScreenEffect::Burst { frame, .. } => *frame += 1,
+        }
+    }
+}
+
+pub struct Tcod {
+    pub root: Root,
+    pub con: Offscreen,
+    // map backgrounds, only redrawn when the map is dirty or the FOV moved
+    pub map_cache: Offscreen,
+    pub panel: Offscreen,
+    pub fov: FovMap,
+    // the part of the map shown above the panel
+    pub camera: Camera,
+    // tint monsters next to the player by their remaining hp
+    pub show_monster_health: bool,
+    // float the damage from every hit up off whatever took it
+    pub show_damage_numbers: bool,
+    // overlay coordinate guides on the map (F2)
+    pub show_debug_grid: bool,
+    // overview of the explored map in the top-right corner (Tab)
+    pub show_minimap: bool,
+    pub animations: bool,
+    // save every time the player goes down the stairs
+    pub autosave: bool,
+    // `--generator`, `None` leaves the choice to the dungeon level
+    pub map_style: Option<MapStyle>,
+    // hand-made rooms read at startup, stamped into some levels
+    pub vaults: Vec<Vault>,
+    // what the keys do, read at startup
+    pub keymap: Keymap,
+    pub screen_effects: Vec<ScreenEffect>,
+    // input from the last `check_for_event`, the key is reset every frame
+    pub key: Key,
+    pub mouse: Mouse,
+}
+
+/// A torch-lit gradient behind the main menu, twice the console's size so
+/// `blit_2x` can use the subcell resolution
+pub fn menu_background(width: i32, height: i32) -> image::Image {
+    let (width, height) = (width * 2, height * 2);
+    let mut background = image::Image::new(width, height);
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 * 0.6);
+    let max_distance = (center_x.powi(2) + center_y.powi(2)).sqrt();
+    for y in 0..height {
+        for x in 0..width {
+            let distance = ((x as f32 - center_x).powi(2) + (y as f32 - center_y).powi(2)).sqrt();
+            let glow = 1.0 - distance / max_distance;
+            let color = colors::lerp(BLACK, DARKER_ORANGE, glow.powi(3));
+            background.put_pixel(x, y, color);
+        }
+    }
+    background
+}
+
+/// A menu with no options, just a message
+pub fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
+
+/// Populate the FOV map according to the generated map
+pub fn initialise_fov(tcod: &mut Tcod, game: &Game) {
+    for ((x, y), tile) in game.map.iter() {
+        tcod.fov.set(x, y, !tile.block_site, !tile.blocked);
+    }
+}
+
+/// Shake the screen if the player just lost a big chunk of health, and
+/// flash it red when they first fall below the low health mark
+pub fn queue_hit_effects(tcod: &mut Tcod, hp_before: i32, player: &Object, game: &Game) {
+    let fighter = match player.fighter {
+        Some(fighter) if tcod.animations => fighter,
+        _ => return,
+    };
+
+    let max_hp = player.max_hp(game) as f32;
+    if (hp_before - fighter.hp) as f32 >= max_hp * BIG_HIT_FRACTION {
+        tcod.screen_effects
+            .push(ScreenEffect::Shake { frames_left: 4 });
+    }
+    let low_hp = max_hp * LOW_HP_FRACTION;
+    if hp_before as f32 >= low_hp && (fighter.hp as f32) < low_hp {
+        tcod.screen_effects
+            .push(ScreenEffect::Flash { frames_left: 3 });
+    }
+}
+
+pub fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
+    // the hits since the last frame turn into flashes and numbers
+    for hit in game.hits.drain(..) {
+        let pos = (hit.x, hit.y);
+        // hitting something invisible shouldn't give away where it is
+        let unseen = objects
+            .iter()
+            .any(|object| object.pos() == pos && !object.visible_to(&objects[PLAYER]));
+        if !tcod.animations || unseen {
+            continue;
+        }
+        tcod.screen_effects.push(ScreenEffect::HitFlash {
+            pos,
+            frames_left: HIT_FLASH_FRAMES,
+        });
+        if tcod.show_damage_numbers {
+            tcod.screen_effects.push(ScreenEffect::DamageNumber {
+                pos,
+                damage: hit.damage,
+                frame: 0,
+            });
+        }
+    }
+
+    // the map changed, doors may have opened or closed, so the FOV map has
+    // to catch up before it's recomputed
+    if game.dirty {
+        initialise_fov(tcod, game);
+    }
+    // recompute FOV if needed (the player moved or something)
+    if fov_recompute || game.dirty {
+        let player = &objects[PLAYER];
+
+        tcod.fov
+            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO)
+    }
+
+    tcod.camera
+        .center_on(objects[PLAYER].pos(), game.map.width(), game.map.height());
+
+    // the player always knows where they are, invisible or not
+    let player = &objects[PLAYER];
+    let mut to_draw: Vec<&Object> = objects
+        .iter()
+        .enumerate()
+        .filter(|&(id, o)| id == PLAYER || o.visible_to(player))
+        .map(|(_, o)| o)
+        .filter(|o| !o.hidden())
+        .filter(|o| {
+            o.tiles().any(|(x, y)| {
+                tcod.fov.is_in_fov(x, y) || (o.always_visible && game.map.tile(x, y).explored)
+            }) || (game.detect_monsters > 0 && o.alive && o.ai.is_some())
+        })
+        .collect();
+    // floor first and actors last, so they're drawn on top
+    to_draw.sort_by_key(|o| o.render_order);
+
+    if fov_recompute || game.dirty {
+        render_map(tcod, game, objects);
+        game.explored = explored_percent(&game.map);
+        game.dirty = false;
+    }
+    blit(
+        &tcod.map_cache,
+        (0, 0),
+        (game.map.width(), game.map.height()),
+        &mut tcod.con,
+        (0, 0),
+        1.0,
+        1.0,
+    );
+
+    // draw all objects in the list
+    for object in &to_draw {
+        let show_health = tcod.show_monster_health && object.distance_to(&objects[PLAYER]) < 2.0;
+        object.draw(&mut tcod.con, show_health);
+    }
+
+    // a shake nudges the whole map a cell in a random direction
+    let shaking = tcod
+        .screen_effects
+        .iter()
+        .any(|effect| matches!(effect, ScreenEffect::Shake { .. }));
+    let map_offset = if shaking {
+        let mut rng = rand::thread_rng();
+        (rng.gen_range(-1, 2), rng.gen_range(-1, 2))
+    } else {
+        (0, 0)
+    };
+    // only the part of the map the camera looks at; a map smaller than the
+    // screen leaves the rest blank
+    tcod.root.clear();
+    let camera = tcod.camera;
+    blit(
+        &tcod.con,
+        (camera.x, camera.y),
+        (
+            cmp::min(camera.width, game.map.width()),
+            cmp::min(camera.height, game.map.height()),
+        ),
+        &mut tcod.root,
+        map_offset,
+        1.0,
+        1.0,
+    );
+
+    if tcod.show_debug_grid {
+        render_debug_grid(tcod, game);
+    }
+    if tcod.show_minimap {
+        render_minimap(tcod, game, objects);
+    }
+
+    // GUI
+    tcod.panel.set_default_background(BLACK);
+    tcod.panel.clear();
+
+    //show the player's stats
+    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
+    let max_hp = objects[PLAYER].max_hp(game);
+    render_bar(
+        &mut tcod.panel,
+        1,
+        1,
+        BAR_WIDTH,
+        "HP",
+        hp,
+        max_hp,
+        LIGHT_RED,
+        DARKER_RED,
+    );
+    if let Some(caster) = &objects[PLAYER].caster {
+        render_bar(
+            &mut tcod.panel,
+            1,
+            2,
+            BAR_WIDTH,
+            "Mana",
+            caster.mana,
+            caster.max_mana,
+            LIGHT_BLUE,
+            DARKER_BLUE,
+        );
+    }
+
+    // the boss's health, while the player can see it
+    let boss = objects.iter().find(|object| {
+        object.alive
+            && object
+                .fighter
+                .is_some_and(|f| f.on_death == DeathCallback::Boss)
+            && tcod.fov.is_in_fov(object.x, object.y)
+    });
+    if let Some(boss) = boss {
+        render_bar(
+            &mut tcod.panel,
+            1,
+            7,
+            BAR_WIDTH,
+            &boss.name,
+            boss.fighter.map_or(0, |f| f.hp),
+            boss.max_hp(game),
+            LIGHT_CYAN,
+            DARKER_CYAN,
+        );
+    }
+
+    render_status_effects(&mut tcod.panel, objects);
+
+    // display names of objects under the mouse
+    tcod.panel.set_default_foreground(LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        get_names_under_mouse(tcod.mouse, tcod.camera, game, objects, &tcod.fov),
+    );
+
+    // print the game messages, newest at the bottom, going up until the
+    // panel is full
+    let msg_width = tcod.panel.width() - MSG_X;
+    let mut y = MSG_HEIGHT as i32;
+    for (message, color) in game.messages.iter().rev() {
+        let message_height = tcod.panel.get_height_rect(MSG_X, y, msg_width, 0, message);
+        y -= message_height;
+        if y < 0 {
+            break;
+        }
+        tcod.panel.set_default_foreground(*color);
+        tcod.panel.print_rect(MSG_X, y, msg_width, 0, message);
+    }
+
+    tcod.panel.set_default_foreground(LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        4,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Explored: {:.0}%", game.explored),
+    );
+    if game.sneaking {
+        tcod.panel
+            .print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left, "Sneaking");
+    }
+    let hunger = game.hunger();
+    tcod.panel.set_default_foreground(hunger.color());
+    tcod.panel.print_ex(
+        BAR_WIDTH,
+        3,
+        BackgroundFlag::None,
+        TextAlignment::Right,
+        hunger.name(),
+    );
+    tcod.panel.set_default_foreground(LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        5,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Dungeon level: {}", game.dungeon_level),
+    );
+    tcod.panel.print_ex(
+        1,
+        6,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Level: {}", objects[PLAYER].level),
+    );
+    tcod.panel.set_default_foreground(GOLD);
+    tcod.panel.print_ex(
+        BAR_WIDTH,
+        6,
+        BackgroundFlag::None,
+        TextAlignment::Right,
+        format!("Gold: {}", game.gold),
+    );
+
+    let panel_y = tcod.root.height() - PANEL_HEIGHT;
+    blit(
+        &tcod.panel,
+        (0, 0),
+        (tcod.panel.width(), PANEL_HEIGHT),
+        &mut tcod.root,
+        (0, panel_y),
+        1.0,
+        1.0,
+    );
+
+    render_screen_effects(tcod);
+}
+
+/// Comma separated names of everything under the mouse the player knows
+/// about, the tile itself last
+fn get_names_under_mouse(
+    mouse: Mouse,
+    camera: Camera,
+    game: &Game,
+    objects: &[Object],
+    fov_map: &FovMap,
+) -> String {
+    let Some((x, y)) = camera.to_map(mouse.cx as i32, mouse.cy as i32) else {
+        return String::new();
+    };
+    describe(x, y, game, objects, fov_map)
+        .into_iter()
+        .map(|description| description.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Draw the effects on the map, then the red flash on top of everything,
+/// and move every effect on a frame, dropping the ones that are done
+fn render_screen_effects(tcod: &mut Tcod) {
+    let (camera, fov) = (tcod.camera, &tcod.fov);
+    for effect in &tcod.screen_effects {
+        match effect {
+            ScreenEffect::Projectile {
+                path,
+                glyph,
+                color,
+                frame,
+            } => {
+                if let Some(&(x, y)) = path.get(*frame) {
+                    put_effect_char(&mut tcod.root, camera, (x, y), *glyph, *color);
+                }
+            }
+            ScreenEffect::Bolt { path, glyph, frame } => {
+                for &(x, y) in path.iter().take(frame + 1) {
+                    put_effect_char(&mut tcod.root, camera, (x, y), *glyph, LIGHT_CYAN);
+                }
+            }
+            ScreenEffect::Burst {
+                center,
+                radius,
+                color,
+                frame,
+            } => {
+                let reach = cmp::min(*frame, *radius) as i32;
+                for dx in -reach..=reach {
+                    for dy in -reach..=reach {
+                        let (x, y) = (center.0 + dx, center.1 + dy);
+                        let in_reach = ((dx * dx + dy * dy) as f32).sqrt() <= reach as f32;
+                        let Some((screen_x, screen_y)) = camera.to_screen(x, y) else {
+                            continue;
+                        };
+                        if in_reach && fov.is_in_fov(x, y) {
+                            let background = tcod.root.get_char_background(screen_x, screen_y);
+                            tcod.root.set_char_background(
+                                screen_x,
+                                screen_y,
+                                colors::lerp(background, *color, 0.7),
+                                BackgroundFlag::Set,
+                            );
+                        }
+                    }
+                }
+            }
+            ScreenEffect::HitFlash { pos, .. } => {
+                if let Some((screen_x, screen_y)) = camera.to_screen(pos.0, pos.1) {
+                    if fov.is_in_fov(pos.0, pos.1) {
+                        tcod.root.set_char_background(
+                            screen_x,
+                            screen_y,
+                            WHITE,
+                            BackgroundFlag::Set,
+                        );
+                    }
+                }
+            }
+            ScreenEffect::DamageNumber { pos, damage, frame } => {
+                let (x, y) = (pos.0, pos.1 - 1 - *frame as i32);
+                if let Some((screen_x, screen_y)) = camera.to_screen(x, y) {
+                    if fov.is_in_fov(pos.0, pos.1) {
+                        tcod.root.set_default_foreground(YELLOW);
+                        tcod.root.print_ex(
+                            screen_x,
+                            screen_y,
+                            BackgroundFlag::None,
+                            TextAlignment::Center,
+                            damage.to_string(),
+                        );
+                    }
+                }
+            }
+            ScreenEffect::Shake { .. } | ScreenEffect::Flash { .. } => (),
+        }
+    }
+
+    let flashing = tcod
+        .screen_effects
+        .iter()
+        .any(|effect| matches!(effect, ScreenEffect::Flash { .. }));
+    if flashing {
+        let (width, height) = (tcod.root.width(), tcod.root.height());
+        let mut flash = Offscreen::new(width, height);
+        flash.set_default_background(RED);
+        flash.clear();
+        blit(
+            &flash,
+            (0, 0),
+            (width, height),
+            &mut tcod.root,
+            (0, 0),
+            0.0,
+            0.4,
+        );
+    }
+
+    for effect in tcod.screen_effects.iter_mut() {
+        effect.advance();
+    }
+    tcod.screen_effects.retain(|effect| !effect.done());
+}
+
+/// draw an effect's glyph over map tile (x, y), if it's on screen
+fn put_effect_char(root: &mut Root, camera: Camera, (x, y): (i32, i32), glyph: char, color: Color) {
+    if let Some((screen_x, screen_y)) = camera.to_screen(x, y) {
+        root.set_default_foreground(color);
+        root.put_char(screen_x, screen_y, glyph, BackgroundFlag::None);
+    }
+}
+
+/// Queue `effect` and play it out before the game carries on. Does nothing
+/// when animations are off.
+pub fn play_effect(tcod: &mut Tcod, game: &mut Game, objects: &[Object], effect: ScreenEffect) {
+    if !tcod.animations {
+        return;
+    }
+    tcod.screen_effects.push(effect);
+    play_screen_effects(tcod, game, objects);
+}
+
+/// Keep drawing frames until every queued effect has played out. Any key
+/// skips whatever is left.
+pub fn play_screen_effects(tcod: &mut Tcod, game: &mut Game, objects: &[Object]) {
+    while !tcod.screen_effects.is_empty() {
+        if let Some((_, Event::Key(_))) = input::check_for_event(input::KEY_PRESS) {
+            tcod.screen_effects.clear();
+            break;
+        }
+        render_all(tcod, game, objects, false);
+        tcod.root.flush();
+    }
+}
+
+/// Lighten every 10th row and column of the map and label them with their
+/// coordinate, to help place things while debugging
+fn render_debug_grid(tcod: &mut Tcod, game: &Game) {
+    let camera = tcod.camera;
+    let on_screen = |(x, y)| camera.to_screen(x, y);
+    for ((x, y), _) in game.map.iter() {
+        if x % 10 == 0 || y % 10 == 0 {
+            if let Some((screen_x, screen_y)) = on_screen((x, y)) {
+                tcod.root.set_char_background(
+                    screen_x,
+                    screen_y,
+                    DARKEST_GREY,
+                    BackgroundFlag::Add,
+                );
+            }
+        }
+    }
+
+    tcod.root.set_default_foreground(LIGHT_GREY);
+    for x in (10..game.map.width()).step_by(10) {
+        if let Some((screen_x, _)) = on_screen((x, camera.y)) {
+            tcod.root.print(screen_x, 0, x.to_string());
+        }
+    }
+    for y in (10..game.map.height()).step_by(10) {
+        if let Some((_, screen_y)) = on_screen((camera.x, y)) {
+            tcod.root.print(0, screen_y, y.to_string());
+        }
+    }
+}
+
+/// Draw the explored map a pixel a tile into the top-right corner, with the
+/// player, the stairs and any monsters in view or detected marked on it
+fn render_minimap(tcod: &mut Tcod, game: &Game, objects: &[Object]) {
+    let palette = game.palette;
+    let mut pixels = image::Image::new(game.map.width(), game.map.height());
+    for ((x, y), tile) in game.map.iter() {
+        let color = match (tile.explored, tile.block_site) {
+            (false, _) => BLACK,
+            (true, true) => palette.light_wall,
+            (true, false) => palette.dark_ground,
+        };
+        pixels.put_pixel(x, y, color);
+    }
+
+    let player = &objects[PLAYER];
+    for object in objects {
+        let color = if object.name == STAIRS_DOWN || object.name == STAIRS_UP {
+            if !game.map.tile(object.x, object.y).explored {
+                continue;
+            }
+            LIGHT_YELLOW
+        } else if object.alive && object.ai.is_some() && object.visible_to(player) {
+            let seen = object.tiles().any(|(x, y)| tcod.fov.is_in_fov(x, y));
+            if !seen && game.detect_monsters <= 0 {
+                continue;
+            }
+            LIGHT_RED
+        } else {
+            continue;
+        };
+        for (x, y) in object.tiles() {
+            pixels.put_pixel(x, y, color);
+        }
+    }
+    pixels.put_pixel(player.x, player.y, WHITE);
+
+    // blit_2x fits two pixels across and two down into each cell
+    let (width, height) = ((game.map.width() + 1) / 2, (game.map.height() + 1) / 2);
+    let mut minimap = Offscreen::new(width, height);
+    image::blit_2x(&pixels, (0, 0), (-1, -1), &mut minimap, (0, 0));
+    let corner = ((tcod.root.width() - width).max(0), 0);
+    blit(
+        &minimap,
+        (0, 0),
+        (width, height),
+        &mut tcod.root,
+        corner,
+        1.0,
+        0.9,
+    );
+}
+
+/// Redraw the tile backgrounds and remembered monsters into the map cache.
+/// `visible` are the objects drawn this frame.
+fn render_map(tcod: &mut Tcod, game: &mut Game, objects: &[Object]) {
+    tcod.map_cache.set_default_background(BLACK);
+    tcod.map_cache.clear();
+
+    // go through all tiles, and set their background color
+    let palette = game.palette;
+    for ((x, y), tile) in game.map.iter_mut() {
+        let is_visible = tcod.fov.is_in_fov(x, y);
+        let is_wall = tile.block_site;
+        let color = match (is_visible, is_wall) {
+            // outside of field of view:
+            (false, true) => palette.dark_wall,
+            (false, false) => palette.dark_ground,
+            // inside fov:
+            (true, true) => palette.light_wall,
+            (true, false) => palette.light_ground,
+        };
+        // water and lava shimmer a little every time the map is redrawn
+        let terrain_color = match tile.terrain {
+            Terrain::Floor => None,
+            Terrain::Water => Some(colors::lerp(
+                palette.water,
+                palette.water_light,
+                rand::random(),
+            )),
+            Terrain::Lava => Some(colors::lerp(
+                palette.lava,
+                palette.lava_light,
+                rand::random(),
+            )),
+        };
+        let color = match terrain_color {
+            Some(terrain_color) if is_visible => terrain_color,
+            Some(terrain_color) => colors::lerp(terrain_color, BLACK, 0.6),
+            None => color,
+        };
+
+        if is_visible {
+            tile.explored = true;
+            // refilled from the monsters in view below
+            tile.last_seen_monster = None;
+        }
+        if tile.explored {
+            tcod.map_cache
+                .set_char_background(x, y, color, BackgroundFlag::Set);
+            if let Some(door) = tile.door {
+                let (char, door_color) = match door {
+                    Door::Open => ('/', COLOR_DOOR),
+                    Door::Closed => ('+', COLOR_DOOR),
+                    Door::Locked { key } => ('+', KEY_KINDS[key].1),
+                };
+                let door_color = if is_visible {
+                    door_color
+                } else {
+                    colors::lerp(door_color, BLACK, 0.5)
+                };
+                tcod.map_cache.set_default_foreground(door_color);
+                tcod.map_cache.put_char(x, y, char, BackgroundFlag::None);
+            }
+        }
+
+        // out of view, show a faint ghost of whatever monster was here last
+        if let (false, Some((char, color))) = (is_visible, tile.last_seen_monster) {
+            tcod.map_cache
+                .set_default_foreground(colors::lerp(color, BLACK, 0.7));
+            tcod.map_cache.put_char(x, y, char, BackgroundFlag::None);
+        }
+    }
+
+    mark_monsters_in_view(tcod, &mut game.map, objects);
+}
+
+/// Remember where the monsters in view are for when they drop out of view.
+/// The ones only sensed by detection, or not seen at all, don't count.
+fn mark_monsters_in_view(tcod: &Tcod, map: &mut GameMap, objects: &[Object]) {
+    let player = &objects[PLAYER];
+    let monsters = objects
+        .iter()
+        .filter(|o| o.ai.is_some() && o.visible_to(player) && !o.hidden());
+    for object in monsters {
+        for (x, y) in object.tiles().filter(|&(x, y)| tcod.fov.is_in_fov(x, y)) {
+            map.tile_mut(x, y).last_seen_monster = Some((object.char, object.color));
+        }
+    }
+}
+
+/// Catch the monster ghosts up with a turn in which the player stayed put.
+/// Ghosts are only drawn out of view, and only tiles in view change here,
+/// so the cached map doesn't need redrawing for it.
+pub fn remember_monsters(tcod: &Tcod, game: &mut Game, objects: &[Object]) {
+    for ((x, y), tile) in game.map.iter_mut() {
+        if tile.last_seen_monster.is_some() && tcod.fov.is_in_fov(x, y) {
+            tile.last_seen_monster = None;
+        }
+    }
+    mark_monsters_in_view(tcod, &mut game.map, objects);
+}
+
+/// Whatever the player is suffering from, or enjoying, with the turns it
+/// has left
+fn render_status_effects(panel: &mut Offscreen, objects: &[Object]) {
+    for (x, label, color) in status_labels(&objects[PLAYER].status) {
+        panel.set_default_foreground(color);
+        panel.print_ex(
+            x,
+            STATUS_Y,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            label,
+        );
+    }
+}
+
+/// an effect as the panel shows it, "POIS:3"
+fn status_label(effect: &StatusEffect) -> String {
+    format!("{}:{}", effect.kind.name().to_uppercase(), effect.turns)
+}
+
+/// Where each effect's label goes along the status row. The ones that
+/// would run into the message log are left off.
+fn status_labels(status: &StatusEffects) -> Vec<(i32, String, Color)> {
+    let mut labels = vec![];
+    let mut x = 1;
+    for effect in status.iter() {
+        let label = status_label(effect);
+        let width = label.chars().count() as i32;
+        if x + width >= MSG_X {
+            break;
+        }
+        labels.push((x, label, effect.kind.color()));
+        x += width + 1;
+    }
+    labels
+}
+
+/// Full-screen, scrollable view of every message so far, opening at the
+/// newest messages
+pub fn show_message_log(tcod: &mut Tcod, messages: &Messages) {
+    let lines: Vec<_> = messages.iter().cloned().collect();
+    show_scrolling_page(tcod, "Message log", &lines, true);
+}
+
+/// Every command and the keys for it, straight from the keymap so it's
+/// right whatever `keys.toml` changed
+pub fn show_help(tcod: &mut Tcod) {
+    let mut commands = tcod.keymap.help();
+    // the controls that aren't in the keymap
+    commands.extend(
+        [
+            ("Alt+Enter", "toggle fullscreen"),
+            ("Left click", "walk to an explored tile, or pick a target"),
+            ("Right click", "stop walking, or cancel targeting"),
+        ]
+        .iter()
+        .map(|&(keys, description)| (keys.to_string(), description)),
+    );
+
+    let keys_width = commands
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<_> = commands
+        .iter()
+        .map(|(keys, description)| {
+            let line = format!("{:width$}  {}", keys, description, width = keys_width);
+            (line, WHITE)
+        })
+        .collect();
+    show_scrolling_page(tcod, "Commands", &lines, false);
+}
+
+/// A full-screen page of `lines` under a title, opening at the top or, with
+/// `at_end`, the bottom. Up/down scroll a line, page up/down a screen and
+/// escape closes it.
+fn show_scrolling_page(tcod: &mut Tcod, title: &str, lines: &[(String, Color)], at_end: bool) {
+    use tcod::input::KeyCode::*;
+
+    let width = tcod.root.width();
+    let height = tcod.root.height();
+
+    // first line is the title, last line the controls hint
+    let page_height = (height - 2) as usize;
+    let last_page_start = lines.len().saturating_sub(page_height);
+    let mut first_line = if at_end { last_page_start } else { 0 };
+
+    let mut window = Offscreen::new(width, height);
+    loop {
+        window.set_default_background(BLACK);
+        window.clear();
+
+        window.set_default_foreground(WHITE);
+        window.print_ex(
+            width / 2,
+            0,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            title,
+        );
+
+        for (line, (text, color)) in lines.iter().skip(first_line).take(page_height).enumerate() {
+            window.set_default_foreground(*color);
+            window.print(1, line as i32 + 1, text);
+        }
+
+        window.set_default_foreground(LIGHT_GREY);
+        window.print_ex(
+            width / 2,
+            height - 1,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "Up/Down, PgUp/PgDn to scroll, Esc to close",
+        );
+
+        blit(
+            &window,
+            (0, 0),
+            (width, height),
+            &mut tcod.root,
+            (0, 0),
+            1.0,
+            1.0,
+        );
+        tcod.root.flush();
+
+        let key = wait_for_key(&mut tcod.root);
+        first_line = match key.code {
+            Up => first_line.saturating_sub(1),
+            Down => cmp::min(first_line + 1, last_page_start),
+            PageUp => first_line.saturating_sub(page_height),
+            PageDown => cmp::min(first_line + page_height, last_page_start),
+            Escape => break,
+            _ => first_line,
+        };
+    }
+}
+
+/// Keep drawing the game until the player left-clicks a tile in view (and
+/// within `max_range` of them, if given). Right-click or escape cancels.
+pub fn target_tile(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &[Object],
+    max_range: Option<f32>,
+) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::Escape;
+
+    loop {
+        // render the screen. this erases the inventory and shows the names of
+        // objects under the mouse.
+        tcod.root.flush();
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => tcod.mouse = m,
+            Some((_, Event::Key(k))) => tcod.key = k,
+            _ => tcod.key = Default::default(),
+        }
+        render_all(tcod, game, objects, false);
+        if tcod.root.window_closed() {
+            return None;
+        }
+
+        let (x, y) = tcod
+            .camera
+            .to_map(tcod.mouse.cx as i32, tcod.mouse.cy as i32)
+            .unwrap_or((-1, -1));
+        let in_fov = game.map.in_bounds(x, y) && tcod.fov.is_in_fov(x, y);
+        let in_range = max_range.is_none_or(|range| objects[PLAYER].distance(x, y) <= range);
+        let (clicked, cancelled) = (tcod.mouse.lbutton_pressed, tcod.mouse.rbutton_pressed);
+        // the mouse state sticks around between events, so only count a click once
+        tcod.mouse.lbutton_pressed = false;
+        tcod.mouse.rbutton_pressed = false;
+
+        if clicked && in_fov && in_range {
+            return Some((x, y));
+        }
+        if cancelled || tcod.key.code == Escape {
+            return None;
+        }
+    }
+}
+
+/// Look around the map with a cursor the movement keys move, describing
+/// whatever is under it. Escape or the examine key again puts it away.
+pub fn examine(tcod: &mut Tcod, game: &mut Game, objects: &[Object]) {
+    let mut cursor = objects[PLAYER].pos();
+    loop {
+        render_all(tcod, game, objects, false);
+
+        if let Some((screen_x, screen_y)) = tcod.camera.to_screen(cursor.0, cursor.1) {
+            tcod.root
+                .set_char_background(screen_x, screen_y, LIGHT_YELLOW, BackgroundFlag::Set);
+        }
+        let mut lines = vec![("Looking around, Esc to stop".to_string(), LIGHT_GREY)];
+        let descriptions = describe(cursor.0, cursor.1, game, objects, &tcod.fov);
+        if descriptions.is_empty() {
+            lines.push(("You haven't been there.".to_string(), WHITE));
+        }
+        for description in descriptions {
+            let line = if description.detail.is_empty() {
+                description.name
+            } else {
+                format!("{}: {}", description.name, description.detail)
+            };
+            lines.push((line, WHITE));
+        }
+        tcod.root.set_default_background(BLACK);
+        for (row, (line, color)) in lines.iter().enumerate() {
+            tcod.root.set_default_foreground(*color);
+            tcod.root.print_ex(
+                0,
+                row as i32,
+                BackgroundFlag::Set,
+                TextAlignment::Left,
+                line,
+            );
+        }
+        tcod.root.flush();
+
+        // the same keys move the cursor as move the player
+        let key = wait_for_key(&mut tcod.root);
+        match tcod.keymap.action(key) {
+            Some(Action::Examine) | Some(Action::Quit) => break,
+            Some(action) => {
+                if let Some((dx, dy)) = action.step() {
+                    let camera = tcod.camera;
+                    cursor.0 = (cursor.0 + dx).clamp(camera.x, camera.x + camera.width - 1);
+                    cursor.1 = (cursor.1 + dy).clamp(camera.y, camera.y + camera.height - 1);
+                }
+            }
+            None if key.code == KeyCode::Escape => break,
+            None => (),
+        }
+    }
+}
+
+/// the line drawn for an arrow flying from `from` towards `to`
+pub fn projectile_glyph(from: (i32, i32), to: (i32, i32)) -> char {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    if dx.abs() > 2 * dy.abs() {
+        '-'
+    } else if dy.abs() > 2 * dx.abs() {
+        '|'
+    } else if (dx > 0) == (dy > 0) {
+        '\\'
+    } else {
+        '/'
+    }
+}
+
+/// Play `glyph` flying along `path` one tile a frame
+pub fn animate_projectile(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    objects: &[Object],
+    path: &[(i32, i32)],
+    glyph: char,
+    color: Color,
+) {
+    let projectile = ScreenEffect::Projectile {
+        path: path.to_vec(),
+        glyph,
+        color,
+        frame: 0,
+    };
+    play_effect(tcod, game, objects, projectile);
+}
+
+/// Wait for a key press without blocking the window: events are polled a
+/// frame at a time, so it still redraws and can be closed meanwhile.
+/// Closing it counts as pressing escape.
+pub fn wait_for_key(root: &mut Root) -> Key {
+    loop {
+        if root.window_closed() {
+            let mut escape = Key::default();
+            escape.code = KeyCode::Escape;
+            return escape;
+        }
+        if let Some((_, Event::Key(key))) = input::check_for_event(input::KEY_PRESS) {
+            return key;
+        }
+        root.flush();
+    }
+}
+
+/// Show a lettered list of options in a framed window over the screen and
+/// wait for a key. `width` is the room for text, it shrinks to fit small
+/// screens. Returns the index of the chosen option, if any.
+pub fn menu<T: AsRef<str>>(
+    header: &str,
+    options: &[T],
+    width: i32,
+    root: &mut Root,
+) -> Option<usize> {
+    assert!(
+        options.len() <= MAX_MENU_OPTIONS,
+        "Cannot have a menu with more than {} options.",
+        MAX_MENU_OPTIONS
+    );
+
+    // leave room for the frame on both sides
+    let width = cmp::min(width, root.width() - 2);
+
+    // calculate total height for the header (after auto-wrap) and one line per option
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, root.height(), header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    let mut window = Offscreen::new(width + 2, height + 2);
+    window.set_default_foreground(WHITE);
+    window.print_frame(
+        0,
+        0,
+        width + 2,
+        height + 2,
+        true,
+        BackgroundFlag::Set,
+        None::<&str>,
+    );
+    window.print_rect_ex(
+        1,
+        1,
+        width,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        header,
+    );
+
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        window.print_ex(
+            1,
+            1 + header_height + index as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!("({}) {}", menu_letter, option_text.as_ref()),
+        );
+    }
+
+    // blit in the middle of the screen, a bit see-through
+    let (window_width, window_height) = (width + 2, height + 2);
+    let x = root.width() / 2 - window_width / 2;
+    let y = root.height() / 2 - window_height / 2;
+    blit(
+        &window,
+        (0, 0),
+        (window_width, window_height),
+        root,
+        (x, y),
+        1.0,
+        0.7,
+    );
+
+    root.flush();
+    let key = wait_for_key(root);
+
+    // convert the letter to an index, anything else cancels
+    if key.printable.is_ascii_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+        if index < options.len() {
+            return Some(index);
+        }
+    }
+    None
+}
+
+pub fn inventory_menu(game: &Game, header: &str, root: &mut Root) -> Option<usize> {
+    let inventory = &game.inventory;
+    if inventory.is_empty() {
+        menu(header, &["Inventory is empty."], INVENTORY_WIDTH, root);
+        return None;
+    }
+
+    // show equipped items with the slot they're on
+    let options: Vec<String> = inventory
+        .iter()
+        .map(|item| match item.equipment {
+            Some(equipment) if equipment.equipped => {
+                format!("{} (on {})", item.name, equipment.slot)
+            }
+            _ => item_name(item, game),
+        })
+        .collect();
+    menu(header, &options, INVENTORY_WIDTH, root)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_bar(
+    panel: &mut Offscreen,
+    x: i32,
+    y: i32,
+    total_width: i32,
+    name: &str,
+    value: i32,
+    maximum: i32,
+    bar_color: Color,
+    back_color: Color,
+) {
+    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
+
+    // bar background
+    panel.set_default_background(back_color);
+    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+
+    // bar forground
+    panel.set_default_background(bar_color);
+    if bar_width > 0 {
+        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
+    }
+
+    panel.set_default_foreground(WHITE);
+    panel.print_ex(
+        x + total_width / 2,
+        y,
+        BackgroundFlag::None,
+        TextAlignment::Center,
+        format!("{}: {}/{}", name, value, maximum),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StatusKind;
+
+    #[test]
+    fn status_effects_show_their_turns_left() {
+        let mut status = StatusEffects::default();
+        status.add(StatusKind::Poison, 3, 1);
+        status.add(StatusKind::Regen, 5, 1);
+        let labels: Vec<_> = status_labels(&status)
+            .into_iter()
+            .map(|(x, label, _)| (x, label))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![(1, "POIS:3".to_string()), (8, "REGEN:5".to_string())]
+        );
+    }
+
+    #[test]
+    fn status_effects_stop_short_of_the_messages() {
+        let mut status = StatusEffects::default();
+        status.add(StatusKind::Poison, 10, 1);
+        status.add(StatusKind::Burning, 10, 1);
+        status.add(StatusKind::Regen, 10, 1);
+        status.add(StatusKind::SeeInvisible, 100, 1);
+        let labels = status_labels(&status);
+        assert!(labels.len() < 4);
+        for (x, label, _) in labels {
+            assert!(x + (label.chars().count() as i32) < MSG_X);
+        }
+    }
+}
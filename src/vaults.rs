@@ -0,0 +1,117 @@
+//! Vaults: hand-made rooms read from text files, which the level generator
+//! stamps into some levels in place of a plain room.
+//!
+//! Every `.txt` file in the vaults directory holds one vault, drawn row by
+//! row with one character per tile:
+//!
+//! ```text
+//! #  wall          .  floor          ~  water
+//! M  a monster     I  an item
+//! ```
+//!
+//! Lines starting with `;` are comments. The outermost ring of the drawing
+//! counts as the vault's walls. Tunnels join a vault to the other rooms at
+//! its center and dig through whatever walls lie in their way.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// the generator needs room for at least some floor inside the walls
+const MIN_VAULT_SIZE: i32 = 3;
+
+/// What a vault puts on one of its tiles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VaultTile {
+    Wall,
+    Floor,
+    Water,
+    // floor with a monster from the level's monster table
+    Monster,
+    // floor with an item from the level's item table
+    Item,
+}
+
+#[derive(Clone, Debug)]
+pub struct Vault {
+    pub width: i32,
+    pub height: i32,
+    tiles: Vec<VaultTile>,
+}
+
+impl Vault {
+    /// Read a vault from its drawing. Rows shorter than the longest one are
+    /// padded with wall.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with(';'))
+            .collect();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0) as i32;
+        let height = rows.len() as i32;
+        if width < MIN_VAULT_SIZE || height < MIN_VAULT_SIZE {
+            return Err(format!(
+                "a vault needs to be at least {0}x{0} tiles, this one is {1}x{2}",
+                MIN_VAULT_SIZE, width, height
+            ));
+        }
+
+        let mut tiles = Vec::with_capacity((width * height) as usize);
+        for (y, row) in rows.iter().enumerate() {
+            let mut chars = row.chars();
+            for x in 0..width {
+                let tile = match chars.next() {
+                    Some('#') | None => VaultTile::Wall,
+                    Some('.') => VaultTile::Floor,
+                    Some('~') => VaultTile::Water,
+                    Some('M') => VaultTile::Monster,
+                    Some('I') => VaultTile::Item,
+                    Some(other) => {
+                        return Err(format!("unknown tile '{}' at ({}, {})", other, x, y));
+                    }
+                };
+                tiles.push(tile);
+            }
+        }
+
+        Ok(Vault {
+            width,
+            height,
+            tiles,
+        })
+    }
+
+    /// the tile at (x, y), counted from the vault's top-left corner
+    pub fn tile(&self, x: i32, y: i32) -> VaultTile {
+        self.tiles[(y * self.width + x) as usize]
+    }
+}
+
+/// Read every vault in `dir`. One broken file doesn't stop the others from
+/// loading, its error is handed back along with the vaults that did.
+pub fn load_vaults(dir: &Path) -> Result<(Vec<Vault>, Vec<String>), Box<dyn Error>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    // the same vaults in the same order every run, so seeded levels repeat
+    paths.sort();
+
+    let mut vaults = vec![];
+    let mut errors = vec![];
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| Vault::parse(&text))
+        {
+            Ok(vault) => vaults.push(vault),
+            Err(error) => errors.push(format!("{}: {}", path.display(), error)),
+        }
+    }
+    Ok((vaults, errors))
+}